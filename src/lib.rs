@@ -0,0 +1,11434 @@
+//! Indexing, recall, and lifecycle logic behind the `exom` binary.
+//!
+//! Graph loading, note lookup, indexing ([`index_graph_data`]), and
+//! lifecycle runs ([`run_lifecycle`]) fail with a typed [`ExomError`] that
+//! callers can match on; its `source` chain carries the underlying cause.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use clap::ValueEnum;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use regex::Regex;
+use rust_stemmers::{Algorithm, Stemmer};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration as StdDuration, Instant, SystemTime};
+use unicode_segmentation::UnicodeSegmentation;
+use walkdir::WalkDir;
+
+const NOTE_DIRS: &[&str] = &[
+    "00_Inbox",
+    "10_Projects",
+    "20_Areas",
+    "30_Resources",
+    ARCHIVES_DIR,
+];
+
+/// Starter notes written by `init --with-templates`, relative to the root.
+pub const SEED_NOTES: &[(&str, &str)] = &[
+    (
+        "10_Projects/Example-Project.md",
+        include_str!("templates/project.md"),
+    ),
+    (
+        "20_Areas/Example-Area.md",
+        include_str!("templates/area.md"),
+    ),
+    (
+        "30_Resources/Example-Resource.md",
+        include_str!("templates/resource.md"),
+    ),
+];
+
+const INBOX_DIR: &str = "00_Inbox";
+const ARCHIVES_DIR: &str = "99_Archives";
+const ARCHIVE_INBOX_DIR: &str = "99_Archives/Inbox";
+const PRUNED_DIR: &str = "99_Archives/Pruned";
+const MERGED_DIR: &str = "99_Archives/Merged";
+const ARCHIVE_LOG_FILE: &str = ".neural/archive_log.jsonl";
+const CONSOLIDATED_PREFIX: &str = "consolidated";
+const METADATA_PREFIX: &str = "<!-- lifecycle";
+const DECAY_THRESHOLD_DAYS: u64 = 7;
+pub const DEFAULT_DECAY_SCALE: f64 = 90.0;
+const WORDS_PER_MINUTE: f64 = 200.0;
+pub const DEFAULT_MAX_INBOX: usize = 20;
+const CONSOLIDATE_LOOKBACK_DAYS: u64 = 7;
+/// Above this many notes `dedupe` warns that the comparison may be slow.
+pub const DEDUPE_WARN_NODES: usize = 5_000;
+/// Up to this many notes, similarity passes compare every pair; the
+/// rare-token filter only pays off (and only stays accurate) above it.
+const SIMILAR_ALL_PAIRS_MAX: usize = 500;
+const INDEX_MANIFEST_FILE: &str = "index_manifest.json";
+pub const HISTORY_FILE: &str = "history.jsonl";
+const DEFAULT_STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "has", "have", "in",
+    "is", "it", "its", "of", "on", "or", "that", "the", "this", "to", "was", "were", "will",
+    "with",
+];
+/// Bump whenever cached per-note data changes shape so stale manifests are ignored.
+const INDEX_MANIFEST_VERSION: u32 = 4;
+/// Bumped when `GraphData` changes in a way older readers cannot load.
+const GRAPH_SCHEMA_VERSION: u32 = 1;
+pub const BM25_K1: f64 = 1.2;
+pub const BM25_B: f64 = 0.75;
+const PAGERANK_DAMPING: f64 = 0.85;
+const PAGERANK_ITERATIONS: usize = 20;
+/// Recency factor for ghosts, which have no mtime: scored as if one
+/// half-life old, so unwritten notes never outrank fresh ones on recency.
+const GHOST_RECENCY_FACTOR: f64 = 0.5;
+
+static TOKEN_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"[A-Za-z0-9_-]+").unwrap());
+static ENGLISH_STEMMER: Lazy<Stemmer> = Lazy::new(|| Stemmer::create(Algorithm::English));
+static WIKILINK_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\[\[([^\]|#]+)(?:#([^\]|]+))?(?:\|([^\]]+))?\]\]").unwrap());
+
+static INLINE_CODE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"`[^`\n]+`").unwrap());
+static RELATION_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(RelationGrammar::Rel.pattern()).unwrap());
+
+#[derive(Clone, Debug, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LifecycleMode {
+    Decay,
+    Consolidate,
+    Archive,
+    /// Reverse the most recent archive run recorded in the move log
+    Unarchive,
+    /// Restore logged moves made between `--from` and `--to`
+    Restore,
+    /// Move fully decayed notes out of the inbox and archive into `99_Archives/Pruned`
+    Prune,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum DecayFn {
+    /// `days / scale`, capped at 1
+    Linear,
+    /// `1 - exp(-days / scale)`
+    Exponential,
+    /// Logistic curve centred at `scale` days
+    Sigmoid,
+}
+
+impl fmt::Display for DecayFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            DecayFn::Linear => "linear",
+            DecayFn::Exponential => "exponential",
+            DecayFn::Sigmoid => "sigmoid",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum ConsolidateGroup {
+    None,
+    /// First folder below the inbox
+    Folder,
+    /// First frontmatter tag
+    Tag,
+}
+
+impl fmt::Display for ConsolidateGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ConsolidateGroup::None => "none",
+            ConsolidateGroup::Folder => "folder",
+            ConsolidateGroup::Tag => "tag",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// How `score_nodes` rescales each component over the candidate set so that
+/// `RecallWeights` compare like with like.
+#[derive(Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreNormalization {
+    None,
+    Minmax,
+    Zscore,
+}
+
+impl fmt::Display for ScoreNormalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ScoreNormalization::None => "none",
+            ScoreNormalization::Minmax => "minmax",
+            ScoreNormalization::Zscore => "zscore",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+impl ScoreNormalization {
+    /// Rescales `values` in place. A constant column carries no ranking
+    /// signal, so it becomes all zeros under either normalization. Z-scores
+    /// are shifted so the column minimum is zero: recall keeps only positive
+    /// scores, and a below-mean match must not turn negative and drop out.
+    fn apply(self, values: &mut [f64]) {
+        if values.is_empty() {
+            return;
+        }
+        let (shift, scale) = match self {
+            ScoreNormalization::None => return,
+            ScoreNormalization::Minmax => {
+                let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+                let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                (min, max - min)
+            }
+            ScoreNormalization::Zscore => {
+                let n = values.len() as f64;
+                let mean = values.iter().sum::<f64>() / n;
+                let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+                let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+                (min, variance.sqrt())
+            }
+        };
+        for value in values.iter_mut() {
+            *value = if scale > 0.0 {
+                (*value - shift) / scale
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LexicalMode {
+    Overlap,
+    Bm25,
+    /// Shared tokens over the union of query and note vocabularies
+    Jaccard,
+    /// Twice the shared tokens over the two vocabulary sizes combined
+    Dice,
+}
+
+impl fmt::Display for LexicalMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            LexicalMode::Overlap => "overlap",
+            LexicalMode::Bm25 => "bm25",
+            LexicalMode::Jaccard => "jaccard",
+            LexicalMode::Dice => "dice",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SemanticMode {
+    Dot,
+    Cosine,
+}
+
+impl fmt::Display for SemanticMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            SemanticMode::Dot => "dot",
+            SemanticMode::Cosine => "cosine",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelationGrammar {
+    /// `REL:TYPE(from->to)[0.8]`
+    Rel,
+    /// `from -> to (type)` or `from -> to (type, 0.8)`, one per line
+    Arrow,
+    /// `[[from]] -type-> [[to]]`
+    Wikiarrow,
+}
+
+impl RelationGrammar {
+    fn pattern(self) -> &'static str {
+        match self {
+            RelationGrammar::Rel => {
+                r"REL:(?P<type>[A-Za-z0-9_]+)\((?P<from>.+?)\s*->\s*(?P<to>.+?)\)\[(?P<confidence>[0-9.]+)\]"
+            }
+            RelationGrammar::Arrow => {
+                r"(?m)^[ \t]*(?:[-*][ \t]+)?(?P<from>[^\n]+?)[ \t]*->[ \t]*(?P<to>[^\n(]+?)[ \t]*\((?P<type>[A-Za-z0-9_ ]+?)(?:,[ \t]*(?P<confidence>[0-9.]+))?\)[ \t]*$"
+            }
+            RelationGrammar::Wikiarrow => {
+                r"\[\[(?P<from>[^\]]+)\]\]\s*-(?P<type>[A-Za-z0-9_]+)->\s*\[\[(?P<to>[^\]]+)\]\]"
+            }
+        }
+    }
+}
+
+impl fmt::Display for RelationGrammar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            RelationGrammar::Rel => "rel",
+            RelationGrammar::Arrow => "arrow",
+            RelationGrammar::Wikiarrow => "wikiarrow",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphMode {
+    Indegree,
+    Pagerank,
+}
+
+impl fmt::Display for GraphMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            GraphMode::Indegree => "indegree",
+            GraphMode::Pagerank => "pagerank",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// How indexing represents a link or relation endpoint that names no note.
+#[derive(Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GhostMode {
+    /// A `ghost/<name>` node plus the edge to it.
+    #[default]
+    Keep,
+    /// Neither node nor edge.
+    Drop,
+    /// The edge to `ghost/<name>`, with no node behind it.
+    EdgesOnly,
+}
+
+impl GhostMode {
+    /// Edge endpoint for the unresolved name `raw`, or `None` to drop the edge.
+    fn endpoint(self, node_map: &mut BTreeMap<String, Node>, raw: &str) -> Option<String> {
+        match self {
+            GhostMode::Keep => Some(ensure_ghost_node(node_map, raw)),
+            GhostMode::Drop => None,
+            GhostMode::EdgesOnly => Some(format!("ghost/{}", raw)),
+        }
+    }
+}
+
+impl fmt::Display for GhostMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            GhostMode::Keep => "keep",
+            GhostMode::Drop => "drop",
+            GhostMode::EdgesOnly => "edges-only",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    Json,
+    Graphml,
+    Dot,
+}
+
+impl GraphFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            GraphFormat::Json => "json",
+            GraphFormat::Graphml => "graphml",
+            GraphFormat::Dot => "dot",
+        }
+    }
+}
+
+impl fmt::Display for GraphFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// A field `recall --columns` can print.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum RecallColumn {
+    Rank,
+    Score,
+    Title,
+    Path,
+    Stem,
+    Indegree,
+    Snippet,
+    Modified,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum SchemaFormat {
+    JsonSchema,
+}
+
+impl fmt::Display for SchemaFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaFormat::JsonSchema => write!(f, "json-schema"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecallProfile {
+    Balanced,
+    Lexical,
+    Semantic,
+    Graph,
+    Exact,
+}
+
+impl RecallProfile {
+    pub fn weights(self) -> RecallWeights {
+        let (lexical, graph, semantic) = match self {
+            RecallProfile::Balanced => (1.0, 1.0, 1.0),
+            RecallProfile::Lexical => (2.0, 0.5, 0.5),
+            RecallProfile::Semantic => (0.5, 0.5, 2.0),
+            RecallProfile::Graph => (0.5, 2.0, 0.5),
+            RecallProfile::Exact => (1.0, 0.0, 2.0),
+        };
+        RecallWeights {
+            lexical,
+            graph,
+            semantic,
+        }
+    }
+}
+
+impl fmt::Display for RecallProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            RecallProfile::Balanced => "balanced",
+            RecallProfile::Lexical => "lexical",
+            RecallProfile::Semantic => "semantic",
+            RecallProfile::Graph => "graph",
+            RecallProfile::Exact => "exact",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+impl fmt::Display for LifecycleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            LifecycleMode::Decay => "decay",
+            LifecycleMode::Consolidate => "consolidate",
+            LifecycleMode::Archive => "archive",
+            LifecycleMode::Unarchive => "unarchive",
+            LifecycleMode::Restore => "restore",
+            LifecycleMode::Prune => "prune",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+pub fn normalize_path(path: PathBuf) -> PathBuf {
+    if path.is_absolute() {
+        path
+    } else {
+        env::current_dir()
+            .unwrap_or_else(|_| PathBuf::from("."))
+            .join(path)
+    }
+}
+
+pub fn init_workflow(root: &Path) -> ExomResult<()> {
+    let extras = [".neural/cache", ".neural/exports"];
+    for dir in NOTE_DIRS.iter().chain(extras.iter()) {
+        let target = root.join(dir);
+        fs::create_dir_all(&target).map_err(io_at(&target))?;
+    }
+    Ok(())
+}
+
+/// Writes each `SEED_NOTES` entry that does not exist yet, so re-running
+/// init never overwrites a template the user has edited.
+pub fn write_seed_notes(root: &Path) -> ExomResult<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    for (rel, content) in SEED_NOTES {
+        let target = root.join(rel);
+        if target.exists() {
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(io_at(parent))?;
+        }
+        fs::write(&target, content).map_err(io_at(&target))?;
+        written.push(target);
+    }
+    Ok(written)
+}
+
+/// File extensions, without the dot, that mark a file as a note. Matching
+/// ignores case, so `md` also picks up `NOTE.MD`.
+#[derive(Clone)]
+pub struct NoteExtensions(Vec<String>);
+
+impl Default for NoteExtensions {
+    fn default() -> Self {
+        NoteExtensions(vec!["md".to_string()])
+    }
+}
+
+impl NoteExtensions {
+    /// From `--ext` values; a leading dot is tolerated and an empty list
+    /// falls back to `md`.
+    pub fn new(raw: &[String]) -> Self {
+        let extensions: Vec<String> = raw
+            .iter()
+            .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect();
+        if extensions.is_empty() {
+            NoteExtensions::default()
+        } else {
+            NoteExtensions(extensions)
+        }
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.0.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+    }
+
+    /// `name` without a trailing note extension, if it has one.
+    fn strip<'a>(&self, name: &'a str) -> &'a str {
+        match name.rsplit_once('.') {
+            Some((base, ext)) if self.0.iter().any(|known| ext.eq_ignore_ascii_case(known)) => base,
+            _ => name,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CollectedNotes {
+    notes: Vec<PathBuf>,
+    /// Markdown files skipped because they matched an `--exclude` glob.
+    excluded: usize,
+}
+
+/// Compiles `--exclude` patterns; they match paths relative to the notes root.
+pub fn build_exclude_set(patterns: &[String]) -> ExomResult<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|source| ExomError::Glob {
+            pattern: pattern.clone(),
+            source,
+        })?);
+    }
+    builder.build().map_err(|source| ExomError::Glob {
+        pattern: patterns.join(", "),
+        source,
+    })
+}
+
+/// Note files under each `NOTE_DIRS` folder. `max_depth` counts from that
+/// folder, so 1 keeps only files sitting directly inside it.
+fn collect_notes(
+    notes_root: &Path,
+    exclude: &GlobSet,
+    max_depth: Option<usize>,
+    extensions: &NoteExtensions,
+) -> ExomResult<CollectedNotes> {
+    let mut notes = Vec::new();
+    let mut excluded = 0;
+    for dir in NOTE_DIRS {
+        let target = notes_root.join(dir);
+        if !target.exists() {
+            continue;
+        }
+        let mut walker = WalkDir::new(&target);
+        if let Some(depth) = max_depth {
+            walker = walker.max_depth(depth);
+        }
+        for entry in walker
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            if extensions.matches(entry.path()) {
+                if !exclude.is_empty() {
+                    let rel = relative_note_id(entry.path(), notes_root)?;
+                    if exclude.is_match(&rel) {
+                        excluded += 1;
+                        continue;
+                    }
+                }
+                notes.push(entry.into_path());
+            }
+        }
+    }
+    Ok(CollectedNotes { notes, excluded })
+}
+
+fn relative_note_id(note: &Path, base: &Path) -> ExomResult<String> {
+    let rel = note
+        .strip_prefix(base)
+        .map_err(|_| ExomError::OutsideRoot {
+            path: note.to_path_buf(),
+            root: base.to_path_buf(),
+        })?;
+    Ok(rel
+        .iter()
+        .map(|os| os.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+fn title_from_file(path: &Path) -> ExomResult<String> {
+    let data = fs::read_to_string(path).unwrap_or_default();
+    Ok(title_from_text(&data, path))
+}
+
+fn title_from_text(data: &str, path: &Path) -> String {
+    for line in data.lines() {
+        if let Some(rest) = line.strip_prefix("# ") {
+            return rest.trim().to_string();
+        }
+    }
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Node {
+    pub id: String,
+    pub path: Option<String>,
+    pub title: String,
+    stem: String,
+    /// TF-IDF over title and body together.
+    #[serde(default)]
+    pub semantic: BTreeMap<String, f64>,
+    /// TF-IDF over the title alone, weighted with the corpus IDF, so recall
+    /// can boost title matches on top of `semantic`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    semantic_title: BTreeMap<String, f64>,
+    #[serde(default)]
+    term_counts: BTreeMap<String, usize>,
+    #[serde(default)]
+    semantic_norm: f64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    bigrams: BTreeMap<String, usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    aliases: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    /// File mtime (RFC 3339) at index time; `None` for ghost nodes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+    /// Externally computed vector from `index --embeddings`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding: Option<Vec<f32>>,
+    /// Words in the body (frontmatter excluded); 0 for ghost nodes.
+    #[serde(default)]
+    word_count: usize,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, JsonSchema)]
+struct Edge {
+    src: String,
+    dst: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    confidence: Option<f64>,
+    /// Display text of a `[[note|alias]]` link.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
+    /// How many times the same `(src, dst, kind)` link occurred; absent means once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    weight: Option<u32>,
+}
+
+impl Edge {
+    fn multiplicity(&self) -> f64 {
+        f64::from(self.weight.unwrap_or(1))
+    }
+}
+
+/// Collapses repeated `(src, dst, kind)` edges into one carrying the
+/// occurrence count, sorted by that key so `graph.json` diffs stay stable.
+/// The first occurrence keeps its alias; confidence keeps the strongest.
+fn dedup_edges(edges: Vec<Edge>) -> Vec<Edge> {
+    let mut merged: BTreeMap<(String, String, String), Edge> = BTreeMap::new();
+    for edge in edges {
+        let key = (edge.src.clone(), edge.dst.clone(), edge.kind.clone());
+        match merged.get_mut(&key) {
+            Some(existing) => {
+                existing.weight = Some(existing.weight.unwrap_or(1) + edge.weight.unwrap_or(1));
+                existing.confidence = match (existing.confidence, edge.confidence) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    (a, b) => a.or(b),
+                };
+            }
+            None => {
+                merged.insert(key, edge);
+            }
+        }
+    }
+    merged.into_values().collect()
+}
+
+#[derive(Default, Serialize, Deserialize, JsonSchema)]
+pub struct Stats {
+    notes: usize,
+    nodes: usize,
+    edges: usize,
+}
+
+#[derive(Default, Serialize, Deserialize, JsonSchema)]
+pub struct GraphData {
+    /// Schema the graph was written with; graphs from before versioning read as 0.
+    #[serde(default)]
+    version: u32,
+    /// Indexed vault roots; older graphs stored a single string.
+    #[serde(deserialize_with = "string_or_list")]
+    notes_root: Vec<String>,
+    /// Id prefix -> vault root, present only when several roots were merged.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    vaults: BTreeMap<String, String>,
+    pub nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    #[serde(default)]
+    stats: Stats,
+    #[serde(default)]
+    doc_freq: BTreeMap<String, usize>,
+    #[serde(default)]
+    total_docs: usize,
+    #[serde(default)]
+    stemmed: bool,
+    /// Stopwords removed at index time; recall strips the same set from queries.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    stopwords: BTreeSet<String>,
+    /// Built with `--unicode-tokens`; recall splits queries the same way.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    unicode_tokens: bool,
+    /// Shortest token kept at index time, from `--min-token-len`.
+    #[serde(default, skip_serializing_if = "keeps_every_length")]
+    min_token_len: usize,
+    /// Built with `--drop-numeric`; all-digit query tokens are dropped too.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    drop_numeric: bool,
+}
+
+fn keeps_every_length(min_len: &usize) -> bool {
+    *min_len <= 1
+}
+
+impl GraphData {
+    /// Absolute file behind a node path, routing prefixed ids to their vault.
+    fn note_file(&self, path: &str) -> PathBuf {
+        if let Some((prefix, rest)) = path.split_once('/') {
+            if let Some(root) = self.vaults.get(prefix) {
+                return Path::new(root).join(rest);
+            }
+        }
+        let root = self.notes_root.first().map(String::as_str).unwrap_or(".");
+        Path::new(root).join(path)
+    }
+}
+
+fn string_or_list<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(root) => vec![root],
+        OneOrMany::Many(roots) => roots,
+    })
+}
+
+pub struct IndexResult {
+    pub graph_path: PathBuf,
+    pub notes: usize,
+    pub nodes: usize,
+    pub edges: usize,
+    pub reused: usize,
+    pub rebuilt: usize,
+    pub excluded: usize,
+    /// `--embeddings` entries whose id matched no indexed note.
+    pub unmatched_embeddings: usize,
+}
+
+#[derive(Default)]
+pub struct IndexOptions {
+    pub force: bool,
+    pub tokens: TokenOptions,
+    pub positions: bool,
+    pub chunk_by_heading: bool,
+    /// Drop code from the tokenized text; `None` indexes it like prose.
+    pub strip_code: Option<CodeStripping>,
+    /// Resolve links across vaults when indexing several roots.
+    pub cross_link: bool,
+    pub exclude: GlobSet,
+    pub extensions: NoteExtensions,
+    /// Deepest file level collected below each note folder; `None` is unlimited.
+    pub max_depth: Option<usize>,
+    /// Threads used to scan notes; `None` uses one per CPU.
+    pub jobs: Option<usize>,
+    /// Emit INFERRED_RELATED edges from vector similarity when set.
+    pub related: Option<RelatedOptions>,
+    /// Graph destination; `None` writes `graph.json` in the out root.
+    pub graph_file: Option<PathBuf>,
+    /// External note vectors keyed by note id, from `--embeddings`.
+    pub embeddings: Option<HashMap<String, Vec<f32>>>,
+    pub ghosts: GhostMode,
+    /// Told how many notes have been scanned.
+    pub progress: Option<Arc<ProgressFn>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CodeStripping {
+    /// Lowercased fence languages left in, such as the `yaml` block capture writes.
+    pub keep_fences: BTreeSet<String>,
+    pub inline: bool,
+}
+
+impl CodeStripping {
+    /// `text` without fenced blocks (fence lines included) whose language is
+    /// not kept and, with `inline`, without `code` spans.
+    fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut out = String::with_capacity(text.len());
+        let mut fence: Option<bool> = None;
+        let mut changed = false;
+        for line in text.split_inclusive('\n') {
+            let trimmed = line.trim();
+            match fence {
+                Some(keep) => {
+                    if trimmed.starts_with("```") {
+                        fence = None;
+                    }
+                    if keep {
+                        out.push_str(line);
+                    } else {
+                        changed = true;
+                    }
+                }
+                None => {
+                    if let Some(info) = trimmed.strip_prefix("```") {
+                        let lang = info.split_whitespace().next().unwrap_or("").to_lowercase();
+                        let keep = self.keep_fences.contains(&lang);
+                        fence = Some(keep);
+                        if keep {
+                            out.push_str(line);
+                        } else {
+                            changed = true;
+                        }
+                    } else {
+                        out.push_str(line);
+                    }
+                }
+            }
+        }
+        if self.inline && INLINE_CODE_REGEX.is_match(&out) {
+            return Cow::Owned(INLINE_CODE_REGEX.replace_all(&out, " ").into_owned());
+        }
+        if changed {
+            Cow::Owned(out)
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RelatedOptions {
+    pub threshold: f64,
+    pub top_n: usize,
+}
+
+impl IndexOptions {
+    /// Settings that change per-note cached data; a mismatch voids the manifest.
+    fn cache_key(&self) -> String {
+        let mut key = format!("{};positions={}", self.tokens.fingerprint(), self.positions);
+        if self.chunk_by_heading {
+            key.push_str(";chunks=true");
+        }
+        if let Some(strip) = &self.strip_code {
+            let keep: Vec<&str> = strip.keep_fences.iter().map(String::as_str).collect();
+            key.push_str(&format!(
+                ";strip_code={};inline={}",
+                keep.join(","),
+                strip.inline
+            ));
+        }
+        key
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct IndexManifest {
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    tokenizer: String,
+    #[serde(default)]
+    settings: IndexSettings,
+    notes: BTreeMap<String, ManifestEntry>,
+}
+
+/// Build settings the graph was indexed with, so commands that edit notes and
+/// re-index (`merge`, `rename`) rebuild it the same way.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct IndexSettings {
+    positions: bool,
+    chunk_by_heading: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strip_code: Option<CodeStripping>,
+    cross_link: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    related: Option<RelatedOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    graph_file: Option<PathBuf>,
+    ghosts: GhostMode,
+}
+
+impl IndexSettings {
+    fn from_options(options: &IndexOptions) -> Self {
+        IndexSettings {
+            positions: options.positions,
+            chunk_by_heading: options.chunk_by_heading,
+            strip_code: options.strip_code.clone(),
+            cross_link: options.cross_link,
+            max_depth: options.max_depth,
+            related: options.related.clone(),
+            graph_file: options.graph_file.clone(),
+            ghosts: options.ghosts,
+        }
+    }
+}
+
+/// Options for re-indexing `out_root` the way it was last built: settings
+/// from the index manifest, tokenizer and embeddings from the graph. Either
+/// file missing falls back to the defaults it would have held.
+pub fn stored_index_options(
+    out_root: &Path,
+    exclude: GlobSet,
+    extensions: NoteExtensions,
+) -> IndexOptions {
+    let settings = fs::read_to_string(out_root.join(INDEX_MANIFEST_FILE))
+        .ok()
+        .and_then(|data| serde_json::from_str::<IndexManifest>(&data).ok())
+        .map(|manifest| manifest.settings)
+        .unwrap_or_default();
+    let graph_path = settings
+        .graph_file
+        .clone()
+        .unwrap_or_else(|| out_root.join("graph.json"));
+    let graph = load_graph(&graph_path).ok();
+    let embeddings: HashMap<String, Vec<f32>> = graph
+        .iter()
+        .flat_map(|graph| &graph.nodes)
+        .filter_map(|node| Some((node.id.clone(), node.embedding.clone()?)))
+        .collect();
+    IndexOptions {
+        tokens: graph
+            .as_ref()
+            .map(TokenOptions::from_graph)
+            .unwrap_or_default(),
+        positions: settings.positions,
+        chunk_by_heading: settings.chunk_by_heading,
+        strip_code: settings.strip_code,
+        cross_link: settings.cross_link,
+        exclude,
+        extensions,
+        max_depth: settings.max_depth,
+        related: settings.related,
+        graph_file: settings.graph_file,
+        embeddings: (!embeddings.is_empty()).then_some(embeddings),
+        ghosts: settings.ghosts,
+        ..Default::default()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestEntry {
+    mtime_ns: u64,
+    size: u64,
+    hash: String,
+    #[serde(default)]
+    links: Vec<WikiLink>,
+    #[serde(default)]
+    relations: Vec<TypedRelation>,
+    /// Heading sections from `--chunk-by-heading`, in note order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    chunks: Vec<ManifestChunk>,
+}
+
+/// Links and relations of one heading chunk, so chunked notes can be rebuilt
+/// from the previous graph like whole notes.
+#[derive(Clone, Serialize, Deserialize)]
+struct ManifestChunk {
+    id: String,
+    #[serde(default)]
+    links: Vec<WikiLink>,
+    #[serde(default)]
+    relations: Vec<TypedRelation>,
+}
+
+/// A `[[target#heading|alias]]` link as written in the note.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct WikiLink {
+    target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    heading: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TypedRelation {
+    #[serde(rename = "type")]
+    pub rel_type: String,
+    pub from: String,
+    pub to: String,
+    pub confidence: f64,
+}
+
+#[derive(Serialize)]
+pub struct CaptureReport {
+    pub note: String,
+    appended_at: String,
+    pub relation_count: usize,
+    /// Relations dropped by `--min-confidence`.
+    pub filtered_count: usize,
+    /// Inverse relations added by `--synthesize-inverse`.
+    pub synthesized_count: usize,
+    pub relations: Vec<TypedRelation>,
+    /// Distinct types rewritten by `--relation-types` normalization.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub normalized: Vec<TypeNormalization>,
+    /// Relations dropped by `--strict-types` because their type is not allowed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rejected: Vec<TypedRelation>,
+}
+
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TypeNormalization {
+    pub original: String,
+    pub normalized: String,
+}
+
+/// Relation type allowlist from `capture --relation-types`.
+pub struct RelationTypes {
+    allowed: BTreeSet<String>,
+    /// Drop relations with other types instead of keeping them.
+    strict: bool,
+}
+
+impl RelationTypes {
+    pub fn load(path: &Path, strict: bool) -> ExomResult<Self> {
+        let content = fs::read_to_string(path).map_err(io_at(path))?;
+        let allowed = content
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|word| !word.is_empty())
+            .map(normalize_relation_type)
+            .collect();
+        Ok(RelationTypes { allowed, strict })
+    }
+}
+
+/// Folds `caused-by`, `caused by`, and `causedBy` into `CAUSED_BY`.
+fn normalize_relation_type(raw: &str) -> String {
+    let mut out = String::new();
+    let mut previous: Option<char> = None;
+    for ch in raw.trim().chars() {
+        if ch == '-' || ch == '_' || ch.is_whitespace() {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+        } else {
+            if ch.is_uppercase()
+                && previous.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit())
+                && !out.ends_with('_')
+            {
+                out.push('_');
+            }
+            out.extend(ch.to_uppercase());
+        }
+        previous = Some(ch);
+    }
+    out.trim_end_matches('_').to_string()
+}
+
+pub struct CaptureOptions {
+    pub min_confidence: f64,
+    pub synthesize_inverse: bool,
+    pub symmetric_types: Vec<String>,
+    /// Parent note written as a `[[...]]` link above the captured text.
+    pub context: Option<String>,
+    /// Relation syntax; `None` uses the default `REL:` grammar.
+    pub relation_regex: Option<Regex>,
+    /// Normalizes relation types and, when strict, enforces the allowlist.
+    pub relation_types: Option<RelationTypes>,
+    /// Validated `--template` text; `None` uses the built-in entry layout.
+    pub template: Option<String>,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        CaptureOptions {
+            min_confidence: 0.0,
+            synthesize_inverse: false,
+            symmetric_types: vec!["RELATED_TO".to_string()],
+            context: None,
+            relation_regex: None,
+            relation_types: None,
+            template: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RecallResponse {
+    pub query: String,
+    pub top_k: usize,
+    pub results: Vec<RecallRow>,
+}
+
+#[derive(Serialize)]
+pub struct RelatedResponse {
+    pub note: String,
+    pub top_k: usize,
+    pub results: Vec<RecallRow>,
+}
+
+#[derive(Serialize)]
+pub struct LifecycleReport {
+    pub mode: LifecycleMode,
+    pub processed: usize,
+    pub touched: usize,
+    pub details: Vec<String>,
+    pub summary_path: Option<String>,
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize)]
+pub struct BenchmarkQuery {
+    query: String,
+    expected: Vec<String>,
+    /// Optional graded gains keyed by path or title, used for NDCG.
+    #[serde(default)]
+    relevance: HashMap<String, f64>,
+}
+
+impl BenchmarkQuery {
+    /// Gain for a result: its graded relevance if given, else 1.0 when expected.
+    fn gain(&self, row: &RecallRow) -> f64 {
+        let keys = row.path.iter().chain(std::iter::once(&row.title));
+        for key in keys {
+            if let Some(gain) = self.relevance.get(key) {
+                return *gain;
+            }
+            if self.expected.contains(key) {
+                return 1.0;
+            }
+        }
+        0.0
+    }
+
+    fn ideal_gains(&self) -> Vec<f64> {
+        let mut gains: Vec<f64> = self.relevance.values().copied().collect();
+        gains.extend(
+            self.expected
+                .iter()
+                .filter(|key| !self.relevance.contains_key(*key))
+                .map(|_| 1.0),
+        );
+        gains.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+        gains
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    #[serde(flatten)]
+    pub summary: BenchmarkSummary,
+    pub queries: Vec<QuerySummary>,
+}
+
+/// Aggregate benchmark metrics; the final line of `benchmark --jsonl`.
+#[derive(Serialize, Deserialize)]
+pub struct BenchmarkSummary {
+    pub hit_at_1: f64,
+    pub hit_at_3: f64,
+    pub hit_at_5: f64,
+    pub mrr: f64,
+    pub ndcg: f64,
+    pub precision_at_k: f64,
+    pub recall_at_k: f64,
+    pub top_k: usize,
+    pub avg_latency_ms: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QuerySummary {
+    pub query: String,
+    pub hit_rank: Option<usize>,
+    pub hit_path: Option<String>,
+    reciprocal_rank: f64,
+    ndcg: f64,
+    /// Share of the top-k results that are expected answers.
+    precision: f64,
+    /// Share of the expected answers found in the top-k results.
+    recall: f64,
+    pub latency_ms: f64,
+}
+
+/// One note's contribution to the index, produced independently per file so
+/// notes can be scanned in parallel.
+struct ScannedNote {
+    entries: Vec<NoteEntry>,
+    manifest: ManifestEntry,
+    reused: bool,
+}
+
+/// Reads, hashes, and tokenizes one note, or rebuilds its entry from the
+/// previous graph when the manifest shows it is unchanged.
+fn scan_note(
+    note: &Path,
+    id: &str,
+    previous_manifest: &IndexManifest,
+    previous_nodes: &HashMap<String, Node>,
+    options: &IndexOptions,
+) -> ExomResult<ScannedNote> {
+    let metadata = fs::metadata(note).map_err(io_at(note))?;
+    let size = metadata.len();
+    let mtime_ns = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0);
+    let cached = previous_manifest.notes.get(id);
+    let unchanged_stat = cached
+        .map(|entry| entry.size == size && entry.mtime_ns == mtime_ns)
+        .unwrap_or(false);
+
+    let (hash, content) = if unchanged_stat {
+        (
+            cached.map(|entry| entry.hash.clone()).unwrap_or_default(),
+            None,
+        )
+    } else {
+        let content = fs::read_to_string(note).unwrap_or_default();
+        (content_hash(&content), Some(content))
+    };
+    let reusable = cached.filter(|entry| entry.hash == hash).and_then(|entry| {
+        let mut reused = vec![reused_entry(
+            id,
+            &entry.links,
+            &entry.relations,
+            None,
+            previous_nodes,
+        )?];
+        for chunk in &entry.chunks {
+            reused.push(reused_entry(
+                &chunk.id,
+                &chunk.links,
+                &chunk.relations,
+                Some(id),
+                previous_nodes,
+            )?);
+        }
+        Some(reused)
+    });
+    let reused = reusable.is_some();
+
+    let mut parsed = match (reusable, content) {
+        (Some(entries), _) => entries,
+        (None, Some(content)) => parse_note_entries(note, id.to_string(), &content, options),
+        (None, None) => {
+            let content = fs::read_to_string(note).unwrap_or_default();
+            parse_note_entries(note, id.to_string(), &content, options)
+        }
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .map(|time| DateTime::<Utc>::from(time).to_rfc3339());
+    for entry in &mut parsed {
+        entry.modified = modified.clone();
+    }
+    let manifest = ManifestEntry {
+        mtime_ns,
+        size,
+        hash,
+        links: parsed[0].links.clone(),
+        relations: parsed[0].relations.clone(),
+        chunks: parsed[1..]
+            .iter()
+            .map(|chunk| ManifestChunk {
+                id: chunk.id.clone(),
+                links: chunk.links.clone(),
+                relations: chunk.relations.clone(),
+            })
+            .collect(),
+    };
+    Ok(ScannedNote {
+        entries: parsed,
+        manifest,
+        reused,
+    })
+}
+
+/// Rebuilds a note or chunk entry from its previous node and cached links, or
+/// `None` when the previous graph lacks the node.
+fn reused_entry(
+    id: &str,
+    links: &[WikiLink],
+    relations: &[TypedRelation],
+    parent: Option<&str>,
+    previous_nodes: &HashMap<String, Node>,
+) -> Option<NoteEntry> {
+    let node = previous_nodes.get(id)?.clone();
+    Some(NoteEntry {
+        id: id.to_string(),
+        title: node.title,
+        stem: node.stem,
+        counts: node.term_counts,
+        bigrams: node.bigrams,
+        links: links.to_vec(),
+        relations: relations.to_vec(),
+        frontmatter: Frontmatter {
+            tags: node.tags,
+            aliases: node.aliases,
+            status: node.status,
+        },
+        modified: None,
+        word_count: node.word_count,
+        parent: parent.map(str::to_string),
+    })
+}
+
+/// Per-note data extracted from a markdown file, either freshly parsed or
+/// reconstructed from the previous graph and manifest.
+struct NoteEntry {
+    id: String,
+    title: String,
+    stem: String,
+    counts: BTreeMap<String, usize>,
+    bigrams: BTreeMap<String, usize>,
+    links: Vec<WikiLink>,
+    relations: Vec<TypedRelation>,
+    frontmatter: Frontmatter,
+    modified: Option<String>,
+    word_count: usize,
+    /// Note id owning this heading chunk; `None` for whole notes.
+    parent: Option<String>,
+}
+
+fn parse_note_entry(note: &Path, id: String, content: &str, options: &IndexOptions) -> NoteEntry {
+    let (frontmatter, body) = split_frontmatter(content);
+    let frontmatter = frontmatter.map(parse_frontmatter).unwrap_or_default();
+    let title = title_from_text(body, note);
+    let stem = note
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let indexed = match &options.strip_code {
+        Some(strip) => strip.apply(body),
+        None => Cow::Borrowed(body),
+    };
+    let corpus = format!("{} {}", title, indexed);
+    let sequence = token_sequence(&corpus, &options.tokens);
+    let bigrams = if options.positions {
+        bigram_counts(&sequence)
+    } else {
+        BTreeMap::new()
+    };
+    let mut counts = BTreeMap::new();
+    for token in sequence {
+        *counts.entry(token).or_default() += 1;
+    }
+    let links = WIKILINK_REGEX
+        .captures_iter(content)
+        .map(|link| {
+            let part = |idx: usize| {
+                link.get(idx)
+                    .map(|m| m.as_str().trim().to_string())
+                    .filter(|text| !text.is_empty())
+            };
+            WikiLink {
+                target: part(1).unwrap_or_default(),
+                heading: part(2),
+                alias: part(3),
+            }
+        })
+        .collect();
+    let mut relations = parse_relations(body, &RELATION_REGEX);
+    relations.dedup();
+    NoteEntry {
+        id,
+        title,
+        stem,
+        counts,
+        bigrams,
+        links,
+        relations,
+        frontmatter,
+        modified: None,
+        word_count: body.unicode_words().count(),
+        parent: None,
+    }
+}
+
+/// The note itself plus, with `--chunk-by-heading`, one entry per `##`/`###`
+/// section. The parent keeps only the text before the first section heading,
+/// but its word count covers the whole note.
+fn parse_note_entries(
+    note: &Path,
+    id: String,
+    content: &str,
+    options: &IndexOptions,
+) -> Vec<NoteEntry> {
+    if !options.chunk_by_heading {
+        return vec![parse_note_entry(note, id, content, options)];
+    }
+    let (preamble, sections) = split_heading_sections(content);
+    let mut parent = parse_note_entry(note, id.clone(), preamble, options);
+    parent.word_count = split_frontmatter(content).1.unicode_words().count();
+    let mut entries = Vec::with_capacity(sections.len() + 1);
+    let mut slugs: HashMap<String, usize> = HashMap::new();
+    for (heading, text) in sections {
+        let base = heading_slug(heading);
+        let seen = slugs.entry(base.clone()).or_default();
+        *seen += 1;
+        let slug = if *seen == 1 {
+            base
+        } else {
+            format!("{}-{}", base, seen)
+        };
+        let mut chunk = parse_note_entry(note, format!("{}#{}", id, slug), text, options);
+        chunk.title = heading.to_string();
+        chunk.parent = Some(id.clone());
+        chunk.frontmatter = Frontmatter::default();
+        entries.push(chunk);
+    }
+    entries.insert(0, parent);
+    entries
+}
+
+/// Splits at `##`/`###` headings outside code fences, returning the text before
+/// the first one and `(heading, section text)` pairs; section text includes its
+/// heading line.
+fn split_heading_sections(content: &str) -> (&str, Vec<(&str, &str)>) {
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    let mut in_fence = false;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            let heading = trimmed
+                .strip_prefix("### ")
+                .or_else(|| trimmed.strip_prefix("## "));
+            if let Some(heading) = heading.map(str::trim).filter(|h| !h.is_empty()) {
+                starts.push((offset, heading));
+            }
+        }
+        offset += line.len();
+    }
+    let preamble = &content[..starts
+        .first()
+        .map(|(start, _)| *start)
+        .unwrap_or(content.len())];
+    let sections = starts
+        .iter()
+        .enumerate()
+        .map(|(idx, (start, heading))| {
+            let end = starts
+                .get(idx + 1)
+                .map(|(next, _)| *next)
+                .unwrap_or(content.len());
+            (*heading, &content[*start..end])
+        })
+        .collect();
+    (preamble, sections)
+}
+
+fn heading_slug(heading: &str) -> String {
+    let mut slug = String::new();
+    for ch in heading.chars() {
+        if ch.is_alphanumeric() {
+            slug.extend(ch.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-').to_string();
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+#[derive(Clone, Default)]
+struct Frontmatter {
+    tags: Vec<String>,
+    aliases: Vec<String>,
+    status: Option<String>,
+}
+
+/// Splits a leading `---` YAML block from the note body. Returns the raw YAML
+/// (without fences) and the remaining body; notes without one are untouched.
+fn split_frontmatter(content: &str) -> (Option<&str>, &str) {
+    let text = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let rest = match text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    {
+        Some(rest) => rest,
+        None => return (None, content),
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, content)
+}
+
+fn parse_frontmatter(raw: &str) -> Frontmatter {
+    let value: serde_yaml::Value = match serde_yaml::from_str(raw) {
+        Ok(value) => value,
+        Err(_) => return Frontmatter::default(),
+    };
+    let tags = yaml_string_list(value.get("tags"))
+        .into_iter()
+        .map(|tag| tag.trim_start_matches('#').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    let aliases = yaml_string_list(value.get("aliases"));
+    let status = value
+        .get("status")
+        .and_then(|status| status.as_str())
+        .map(|status| status.trim().to_string());
+    Frontmatter {
+        tags,
+        aliases,
+        status,
+    }
+}
+
+/// Accepts either a YAML sequence or a comma/space separated string.
+fn yaml_string_list(value: Option<&serde_yaml::Value>) -> Vec<String> {
+    match value {
+        Some(serde_yaml::Value::Sequence(items)) => items
+            .iter()
+            .filter_map(|item| match item {
+                serde_yaml::Value::String(text) => Some(text.trim().to_string()),
+                serde_yaml::Value::Number(number) => Some(number.to_string()),
+                _ => None,
+            })
+            .filter(|item| !item.is_empty())
+            .collect(),
+        Some(serde_yaml::Value::String(text)) => text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn index_graph_data(
+    notes_root: &Path,
+    out_root: &Path,
+    options: &IndexOptions,
+) -> ExomResult<IndexResult> {
+    index_vaults(&[notes_root.to_path_buf()], out_root, options)
+}
+
+/// Id prefix for each root: empty for a single vault, otherwise the root's
+/// folder name, suffixed when two vaults share a name.
+fn vault_prefixes(notes_roots: &[PathBuf]) -> Vec<String> {
+    if notes_roots.len() <= 1 {
+        return vec![String::new(); notes_roots.len()];
+    }
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    notes_roots
+        .iter()
+        .map(|root| {
+            let base = root
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| "vault".to_string());
+            let count = seen.entry(base.clone()).or_default();
+            *count += 1;
+            if *count == 1 {
+                base
+            } else {
+                format!("{}-{}", base, count)
+            }
+        })
+        .collect()
+}
+
+pub fn index_vaults(
+    notes_roots: &[PathBuf],
+    out_root: &Path,
+    options: &IndexOptions,
+) -> ExomResult<IndexResult> {
+    let prefixes = vault_prefixes(notes_roots);
+    let mut notes = Vec::new();
+    let mut excluded = 0;
+    for (root, prefix) in notes_roots.iter().zip(&prefixes) {
+        let scan = collect_notes(
+            root,
+            &options.exclude,
+            options.max_depth,
+            &options.extensions,
+        )?;
+        excluded += scan.excluded;
+        for note in scan.notes {
+            let rel = relative_note_id(&note, root)?;
+            let id = if prefix.is_empty() {
+                rel
+            } else {
+                format!("{}/{}", prefix, rel)
+            };
+            notes.push((note, id, prefix.as_str()));
+        }
+    }
+    // Sorting by id keeps node and edge order independent of directory
+    // listing order and of how the parallel scan is scheduled.
+    notes.sort_by(|a, b| a.1.cmp(&b.1));
+    let graph_path = options
+        .graph_file
+        .clone()
+        .unwrap_or_else(|| out_root.join("graph.json"));
+    let manifest_path = out_root.join(INDEX_MANIFEST_FILE);
+    let (previous_manifest, previous_nodes) = if options.force {
+        (IndexManifest::default(), HashMap::new())
+    } else {
+        load_index_cache(&manifest_path, &graph_path, options)
+    };
+
+    let mut manifest = IndexManifest {
+        version: INDEX_MANIFEST_VERSION,
+        tokenizer: options.cache_key(),
+        settings: IndexSettings::from_options(options),
+        ..Default::default()
+    };
+    let done = AtomicUsize::new(0);
+    let scan = |(note, id, _): &(PathBuf, String, &str)| {
+        let scanned = scan_note(note, id, &previous_manifest, &previous_nodes, options);
+        if let Some(progress) = &options.progress {
+            progress(done.fetch_add(1, Ordering::Relaxed) + 1, notes.len());
+        }
+        scanned
+    };
+    let scanned: Vec<ScannedNote> = match options.jobs {
+        Some(jobs) => rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .map_err(|err| {
+                ExomError::invalid(format!("cannot start {} indexing threads: {}", jobs, err))
+            })?
+            .install(|| notes.par_iter().map(scan).collect::<ExomResult<_>>())?,
+        None => notes.par_iter().map(scan).collect::<ExomResult<_>>()?,
+    };
+
+    let mut entries = Vec::new();
+    let mut vault_of: HashMap<String, &str> = HashMap::new();
+    let mut reused = 0;
+    for ((_, id, prefix), scanned) in notes.iter().zip(scanned) {
+        reused += usize::from(scanned.reused);
+        for entry in &scanned.entries {
+            vault_of.insert(entry.id.clone(), prefix);
+        }
+        manifest.notes.insert(id.clone(), scanned.manifest);
+        entries.extend(scanned.entries);
+    }
+
+    // Heading chunks share their note's stem, so only whole notes are link targets.
+    let id_by_stem = stem_index(entries.iter().filter(|entry| entry.parent.is_none()).map(
+        |entry| {
+            (
+                entry.id.as_str(),
+                entry.stem.as_str(),
+                entry.frontmatter.aliases.as_slice(),
+            )
+        },
+    ));
+    let mut node_map: BTreeMap<String, Node> = BTreeMap::new();
+    for entry in &entries {
+        node_map.insert(
+            entry.id.clone(),
+            Node {
+                id: entry.id.clone(),
+                path: Some(entry.parent.clone().unwrap_or_else(|| entry.id.clone())),
+                title: entry.title.clone(),
+                stem: entry.stem.clone(),
+                tags: entry.frontmatter.tags.clone(),
+                aliases: entry.frontmatter.aliases.clone(),
+                status: entry.frontmatter.status.clone(),
+                modified: entry.modified.clone(),
+                word_count: entry.word_count,
+                embedding: options
+                    .embeddings
+                    .as_ref()
+                    .and_then(|vectors| vectors.get(&entry.id).cloned()),
+                ..Default::default()
+            },
+        );
+    }
+    let unmatched_embeddings = options.embeddings.as_ref().map_or(0, |vectors| {
+        vectors
+            .keys()
+            .filter(|id| !node_map.contains_key(*id))
+            .count()
+    });
+
+    let mut id_by_title: HashMap<String, Vec<String>> = HashMap::new();
+    for entry in entries.iter().filter(|entry| entry.parent.is_none()) {
+        id_by_title
+            .entry(entry.title.to_lowercase())
+            .or_default()
+            .push(entry.id.clone());
+    }
+
+    // Without `--cross-link`, a merged vault only resolves names to its own notes.
+    let scoped = |candidates: Option<&Vec<String>>, vault: &str| -> Option<Vec<String>> {
+        let ids: Vec<String> = candidates?
+            .iter()
+            .filter(|id| options.cross_link || vault_of.get(id.as_str()) == Some(&vault))
+            .cloned()
+            .collect();
+        (!ids.is_empty()).then_some(ids)
+    };
+    let mut edges = Vec::new();
+    for entry in &entries {
+        let vault = vault_of.get(&entry.id).copied().unwrap_or_default();
+        if let Some(parent) = &entry.parent {
+            edges.push(Edge {
+                src: parent.clone(),
+                dst: entry.id.clone(),
+                kind: "CONTAINS".into(),
+                ..Default::default()
+            });
+        }
+        for link in &entry.links {
+            let raw = &link.target;
+            let key = Path::new(raw)
+                .file_name()
+                .map(|s| s.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| raw.to_lowercase());
+            if let Some(candidates) = scoped(id_by_stem.get(&key), vault) {
+                for dst in &candidates {
+                    // `[[note#heading]]` lands on the section chunk when one exists.
+                    let section = link
+                        .heading
+                        .as_deref()
+                        .map(|heading| format!("{}#{}", dst, heading_slug(heading)))
+                        .filter(|id| node_map.contains_key(id));
+                    edges.push(Edge {
+                        src: entry.id.clone(),
+                        dst: section.unwrap_or_else(|| dst.clone()),
+                        kind: "WIKILINK".into(),
+                        alias: link.alias.clone(),
+                        ..Default::default()
+                    });
+                }
+            } else if let Some(ghost) = options.ghosts.endpoint(&mut node_map, raw) {
+                edges.push(Edge {
+                    src: entry.id.clone(),
+                    dst: ghost,
+                    kind: "UNRESOLVED_LINK".into(),
+                    alias: link.alias.clone(),
+                    ..Default::default()
+                });
+            }
+        }
+        // Typed relation endpoints are free-text titles, so they resolve by
+        // stem/alias first and then by note title, falling back to ghosts.
+        for relation in &entry.relations {
+            let mut endpoint = |text: &str| -> Vec<String> {
+                let key = text.to_lowercase();
+                scoped(id_by_stem.get(&key), vault)
+                    .or_else(|| scoped(id_by_title.get(&key), vault))
+                    .unwrap_or_else(|| {
+                        options
+                            .ghosts
+                            .endpoint(&mut node_map, text)
+                            .into_iter()
+                            .collect()
+                    })
+            };
+            let sources = endpoint(&relation.from);
+            let targets = endpoint(&relation.to);
+            for src in &sources {
+                for dst in &targets {
+                    edges.push(Edge {
+                        src: src.clone(),
+                        dst: dst.clone(),
+                        kind: relation.rel_type.clone(),
+                        confidence: Some(relation.confidence),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    }
+
+    // Document frequencies are always recomputed over the full corpus, since a
+    // single changed note shifts the IDF of every token it contains.
+    let total_docs = entries.len();
+    let mut doc_freq: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in &entries {
+        for token in entry.counts.keys() {
+            *doc_freq.entry(token.clone()).or_default() += 1;
+        }
+    }
+
+    for entry in entries {
+        let weigh = |counts: &BTreeMap<String, usize>| -> BTreeMap<String, f64> {
+            counts
+                .iter()
+                .map(|(token, count)| {
+                    let df = doc_freq.get(token).copied().unwrap_or(0);
+                    (token.clone(), (*count as f64) * tfidf_idf(total_docs, df))
+                })
+                .collect()
+        };
+        let tfidf = weigh(&entry.counts);
+        let mut title_counts = BTreeMap::new();
+        for token in token_sequence(&entry.title, &options.tokens) {
+            *title_counts.entry(token).or_default() += 1;
+        }
+        if let Some(node) = node_map.get_mut(&entry.id) {
+            node.semantic_norm = vector_norm(&tfidf);
+            node.semantic = tfidf;
+            node.semantic_title = weigh(&title_counts);
+            node.term_counts = entry.counts;
+            node.bigrams = entry.bigrams;
+        }
+    }
+
+    if let Some(related) = &options.related {
+        edges.extend(inferred_related_edges(&node_map, related));
+    }
+    let edges = dedup_edges(edges);
+
+    let edges_count = edges.len();
+    let graph = GraphData {
+        version: GRAPH_SCHEMA_VERSION,
+        notes_root: notes_roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect(),
+        vaults: prefixes
+            .iter()
+            .zip(notes_roots)
+            .filter(|(prefix, _)| !prefix.is_empty())
+            .map(|(prefix, root)| (prefix.clone(), root.display().to_string()))
+            .collect(),
+        nodes: node_map.values().cloned().collect(),
+        edges,
+        stats: Stats {
+            notes: notes.len(),
+            nodes: node_map.len(),
+            edges: edges_count,
+        },
+        doc_freq,
+        total_docs,
+        stemmed: options.tokens.stem,
+        stopwords: options.tokens.stopwords.clone(),
+        unicode_tokens: options.tokens.unicode,
+        min_token_len: options.tokens.min_len,
+        drop_numeric: options.tokens.drop_numeric,
+    };
+
+    for dir in [graph_path.parent(), manifest_path.parent()]
+        .into_iter()
+        .flatten()
+    {
+        fs::create_dir_all(dir).map_err(io_at(dir))?;
+    }
+    write_atomic(
+        &graph_path,
+        serde_json::to_string_pretty(&graph).map_err(json_at(&graph_path))?,
+    )?;
+    write_atomic(
+        &manifest_path,
+        serde_json::to_string_pretty(&manifest).map_err(json_at(&manifest_path))?,
+    )?;
+    append_history(
+        &out_root.join(HISTORY_FILE),
+        &HistoryRecord {
+            timestamp: Utc::now().to_rfc3339(),
+            notes: graph.stats.notes,
+            nodes: graph.stats.nodes,
+            edges: graph.stats.edges,
+            ghost_nodes: graph.nodes.iter().filter(|n| n.path.is_none()).count(),
+        },
+    )?;
+
+    Ok(IndexResult {
+        graph_path,
+        notes: graph.stats.notes,
+        nodes: graph.stats.nodes,
+        edges: graph.stats.edges,
+        reused,
+        rebuilt: graph.stats.notes - reused,
+        excluded,
+        unmatched_embeddings,
+    })
+}
+
+/// One line of `history.jsonl`, appended by every index run.
+#[derive(Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub timestamp: String,
+    pub notes: usize,
+    pub nodes: usize,
+    pub edges: usize,
+    pub ghost_nodes: usize,
+}
+
+/// Writes `contents` to a sibling temp file and renames it over `path`, so a
+/// crash mid-write leaves either the old file or the complete new one.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> ExomResult<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| ExomError::invalid(format!("invalid write target {}", path.display())))?;
+    let tmp = dir.join(format!(
+        ".{}.tmp-{}",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    let result = (|| -> std::io::Result<()> {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result.map_err(io_at(path))
+}
+
+fn append_history(path: &Path, record: &HistoryRecord) -> ExomResult<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(io_at(path))?;
+    let line = serde_json::to_string(record).map_err(json_at(path))?;
+    writeln!(file, "{}", line).map_err(io_at(path))
+}
+
+pub fn load_history(path: &Path) -> ExomResult<Vec<HistoryRecord>> {
+    if !path.exists() {
+        return Err(ExomError::invalid(format!(
+            "No index history at {}. Run `exom index` first.",
+            path.display()
+        )));
+    }
+    let content = fs::read_to_string(path).map_err(io_at(path))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            serde_json::from_str(line).map_err(|source| ExomError::Parse {
+                path: path.to_path_buf(),
+                line: Some(idx + 1),
+                source,
+            })
+        })
+        .collect()
+}
+
+/// Block characters scaled between the series' minimum and maximum.
+pub fn sparkline(values: &[usize]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = values.iter().copied().min().unwrap_or(0);
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|value| {
+            if max == min {
+                BARS[0]
+            } else {
+                BARS[(value - min) * (BARS.len() - 1) / (max - min)]
+            }
+        })
+        .collect()
+}
+
+/// Maps lowercased stems and frontmatter aliases to the note ids they name,
+/// which is how `[[...]]` targets resolve. Keys with several ids are ambiguous.
+fn stem_index<'a, I>(notes: I) -> HashMap<String, Vec<String>>
+where
+    I: IntoIterator<Item = (&'a str, &'a str, &'a [String])>,
+{
+    let mut id_by_stem: HashMap<String, Vec<String>> = HashMap::new();
+    for (id, stem, aliases) in notes {
+        let stem_key = stem.to_lowercase();
+        id_by_stem
+            .entry(stem_key.clone())
+            .or_default()
+            .push(id.to_string());
+        for alias in aliases {
+            let alias_key = alias.to_lowercase();
+            if alias_key != stem_key {
+                id_by_stem
+                    .entry(alias_key)
+                    .or_default()
+                    .push(id.to_string());
+            }
+        }
+    }
+    id_by_stem
+}
+
+fn stem_collisions(id_by_stem: &HashMap<String, Vec<String>>) -> BTreeMap<String, Vec<String>> {
+    id_by_stem
+        .iter()
+        .filter_map(|(key, ids)| {
+            let mut unique = ids.clone();
+            unique.sort();
+            unique.dedup();
+            (unique.len() > 1).then(|| (key.clone(), unique))
+        })
+        .collect()
+}
+
+fn ensure_ghost_node(node_map: &mut BTreeMap<String, Node>, raw: &str) -> String {
+    let ghost = format!("ghost/{}", raw);
+    node_map.entry(ghost.clone()).or_insert_with(|| Node {
+        id: ghost.clone(),
+        path: None,
+        title: raw.to_string(),
+        stem: raw.to_string(),
+        ..Default::default()
+    });
+    ghost
+}
+
+/// What `run_watch` has to report while it runs.
+pub enum WatchEvent {
+    /// An index run, the initial one or a re-index, finished.
+    Indexed(IndexResult),
+    /// A re-index failed; watching continues.
+    IndexFailed(ExomError),
+    /// The file watcher reported an error; watching continues.
+    WatcherFailed(ExomError),
+}
+
+/// Indexes once, then re-indexes after note changes settle for `debounce`,
+/// until Ctrl-C. Progress goes to `report`; only setup errors and a failed
+/// first index end the run early.
+pub fn run_watch(
+    notes_root: &Path,
+    out_root: &Path,
+    options: &IndexOptions,
+    debounce: StdDuration,
+    mut report: impl FnMut(WatchEvent),
+) -> ExomResult<()> {
+    use notify::{RecursiveMode, Watcher};
+
+    let watch_error = |err: notify::Error| ExomError::Watch {
+        detail: err.to_string(),
+    };
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })
+    .map_err(watch_error)?;
+    let mut watched = 0;
+    for dir in NOTE_DIRS {
+        let target = notes_root.join(dir);
+        if target.exists() {
+            watcher
+                .watch(&target, RecursiveMode::Recursive)
+                .map_err(watch_error)?;
+            watched += 1;
+        }
+    }
+    if watched == 0 {
+        return Err(ExomError::invalid(format!(
+            "No note folders found under {}. Run `exom init` first.",
+            notes_root.display()
+        )));
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_flag = Arc::clone(&running);
+    ctrlc::set_handler(move || handler_flag.store(false, Ordering::SeqCst)).map_err(|err| {
+        ExomError::Watch {
+            detail: format!("cannot install Ctrl-C handler: {}", err),
+        }
+    })?;
+
+    report(WatchEvent::Indexed(index_graph_data(
+        notes_root, out_root, options,
+    )?));
+
+    let mut pending: Option<Instant> = None;
+    while running.load(Ordering::SeqCst) {
+        match rx.recv_timeout(StdDuration::from_millis(100)) {
+            Ok(Ok(event)) if is_note_event(&event, &options.extensions) => {
+                pending = Some(Instant::now())
+            }
+            Ok(Ok(_)) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Ok(Err(err)) => report(WatchEvent::WatcherFailed(watch_error(err))),
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+        if pending.is_some_and(|since| since.elapsed() >= debounce) {
+            pending = None;
+            report(match index_graph_data(notes_root, out_root, options) {
+                Ok(result) => WatchEvent::Indexed(result),
+                Err(err) => WatchEvent::IndexFailed(err),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Only note creations, edits, and deletions should trigger a re-index.
+fn is_note_event(event: &notify::Event, extensions: &NoteExtensions) -> bool {
+    use notify::EventKind;
+
+    matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) && event.paths.iter().any(|path| extensions.matches(path))
+}
+
+/// Loads the previous manifest and graph nodes for incremental indexing. Any
+/// failure simply disables reuse so the index is rebuilt from scratch.
+fn load_index_cache(
+    manifest_path: &Path,
+    graph_path: &Path,
+    options: &IndexOptions,
+) -> (IndexManifest, HashMap<String, Node>) {
+    let manifest = fs::read_to_string(manifest_path)
+        .ok()
+        .and_then(|data| serde_json::from_str::<IndexManifest>(&data).ok())
+        .filter(|manifest| {
+            manifest.version == INDEX_MANIFEST_VERSION && manifest.tokenizer == options.cache_key()
+        });
+    let graph = load_graph(graph_path).ok();
+    match (manifest, graph) {
+        (Some(manifest), Some(graph)) => {
+            let nodes = graph
+                .nodes
+                .into_iter()
+                .filter(|node| node.path.is_some())
+                .map(|node| (node.id.clone(), node))
+                .collect();
+            (manifest, nodes)
+        }
+        _ => (IndexManifest::default(), HashMap::new()),
+    }
+}
+
+/// FNV-1a hash of a note body; stable across toolchains unlike `DefaultHasher`.
+fn content_hash(content: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in content.as_bytes() {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Failures at the library boundary that callers may want to tell apart.
+#[derive(Debug)]
+pub enum ExomError {
+    /// No graph file at the path; `exom index` has not run.
+    GraphNotFound { path: PathBuf },
+    /// No node with this id, title, or stem.
+    NoteNotFound { key: String },
+    /// Reading, writing, moving, or listing `path` failed.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The file, or line `line` of a JSON Lines file, is not the JSON expected.
+    Parse {
+        path: PathBuf,
+        line: Option<usize>,
+        source: serde_json::Error,
+    },
+    /// The graph's `version` differs from `GRAPH_SCHEMA_VERSION`, or the
+    /// version matches but the contents do not (`source` is then set).
+    Schema {
+        path: PathBuf,
+        found: u64,
+        source: Option<serde_json::Error>,
+    },
+    /// An imported graph failed validation; one entry per problem.
+    Import {
+        path: PathBuf,
+        problems: Vec<String>,
+    },
+    /// Writing CSV to `path` failed.
+    Csv { path: PathBuf, source: csv::Error },
+    /// Reading or writing a standard stream, or another unnamed one, failed.
+    Stream { source: std::io::Error },
+    /// An `--exclude` glob does not compile.
+    Glob {
+        pattern: String,
+        source: globset::Error,
+    },
+    /// A search or relation pattern does not compile.
+    Regex {
+        pattern: String,
+        source: regex::Error,
+    },
+    /// A flag, query parameter, or file holds a value exom cannot use.
+    Invalid { message: String },
+    /// `path` is not under the notes root `root`.
+    OutsideRoot { path: PathBuf, root: PathBuf },
+    /// A merge or rename would overwrite `path`.
+    AlreadyExists { path: PathBuf },
+    /// An external program (`git`, `$EDITOR`) failed to start or exited non-zero.
+    Command { program: String, detail: String },
+    /// The file watcher or its Ctrl-C handler could not be set up.
+    Watch { detail: String },
+}
+
+pub type ExomResult<T> = std::result::Result<T, ExomError>;
+
+impl ExomError {
+    fn invalid(message: impl Into<String>) -> Self {
+        ExomError::Invalid {
+            message: message.into(),
+        }
+    }
+}
+
+/// `map_err` adapter tagging an I/O failure with the path involved.
+fn io_at(path: &Path) -> impl FnOnce(std::io::Error) -> ExomError + '_ {
+    move |source| ExomError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+fn stream_error(source: std::io::Error) -> ExomError {
+    ExomError::Stream { source }
+}
+
+/// `map_err` adapter tagging a JSON failure with the file it came from.
+fn json_at(path: &Path) -> impl FnOnce(serde_json::Error) -> ExomError + '_ {
+    move |source| ExomError::Parse {
+        path: path.to_path_buf(),
+        line: None,
+        source,
+    }
+}
+
+impl fmt::Display for ExomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExomError::GraphNotFound { path } => write!(
+                f,
+                "Graph not found: {}. Run `exom index` first.",
+                path.display()
+            ),
+            ExomError::NoteNotFound { key } => write!(f, "Note not found in graph: {}", key),
+            ExomError::Io { path, .. } => write!(f, "I/O failed on {}", path.display()),
+            ExomError::Parse {
+                path,
+                line: Some(line),
+                ..
+            } => write!(f, "{}:{}: cannot parse line", path.display(), line),
+            ExomError::Parse { path, .. } => write!(f, "cannot parse {}", path.display()),
+            ExomError::Schema { path, found, .. } => {
+                let expected = GRAPH_SCHEMA_VERSION as u64;
+                if *found < expected {
+                    write!(
+                        f,
+                        "graph {} was built with schema v{} (current v{}); run `exom index` to rebuild",
+                        path.display(),
+                        found,
+                        expected
+                    )
+                } else if *found > expected {
+                    write!(
+                        f,
+                        "graph {} uses schema v{}, newer than this exom supports (v{}); upgrade exom",
+                        path.display(),
+                        found,
+                        expected
+                    )
+                } else {
+                    write!(
+                        f,
+                        "graph {} does not match schema v{}; run `exom index` to rebuild",
+                        path.display(),
+                        expected
+                    )
+                }
+            }
+            ExomError::Import { path, problems } => write!(
+                f,
+                "{} failed validation:\n  {}",
+                path.display(),
+                problems.join("\n  ")
+            ),
+            ExomError::Csv { path, .. } => write!(f, "failed to write CSV {}", path.display()),
+            ExomError::Stream { .. } => write!(f, "I/O failed on a standard stream"),
+            ExomError::Glob { pattern, .. } => write!(f, "invalid --exclude glob {:?}", pattern),
+            ExomError::Regex { pattern, .. } => write!(f, "invalid pattern {:?}", pattern),
+            ExomError::Invalid { message } => write!(f, "{}", message),
+            ExomError::OutsideRoot { path, root } => {
+                write!(f, "{} is not inside {}", path.display(), root.display())
+            }
+            ExomError::AlreadyExists { path } => write!(f, "{} already exists", path.display()),
+            ExomError::Command { program, detail } => write!(f, "{}: {}", program, detail),
+            ExomError::Watch { detail } => write!(f, "watch failed: {}", detail),
+        }
+    }
+}
+
+impl std::error::Error for ExomError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExomError::Io { source, .. } | ExomError::Stream { source } => Some(source),
+            ExomError::Parse { source, .. } => Some(source),
+            ExomError::Schema {
+                source: Some(source),
+                ..
+            } => Some(source),
+            ExomError::Csv { source, .. } => Some(source),
+            ExomError::Glob { source, .. } => Some(source),
+            ExomError::Regex { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a graph, checking its schema version before deserializing so an
+/// outdated file asks for a rebuild instead of failing on a missing field.
+pub fn load_graph(graph_path: &Path) -> ExomResult<GraphData> {
+    let path = || graph_path.to_path_buf();
+    let data = fs::read_to_string(graph_path).map_err(io_at(graph_path))?;
+    let value: serde_json::Value = serde_json::from_str(&data).map_err(json_at(graph_path))?;
+    let found = value
+        .get("version")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(0);
+    if found != GRAPH_SCHEMA_VERSION as u64 {
+        return Err(ExomError::Schema {
+            path: path(),
+            found,
+            source: None,
+        });
+    }
+    serde_json::from_value(value).map_err(|source| ExomError::Schema {
+        path: path(),
+        found,
+        source: Some(source),
+    })
+}
+
+/// Graph JSON accepted by `exom import`: a superset of `GraphData` where only
+/// node ids and edge endpoints are required. Alternative field names used by
+/// other indexers are accepted as aliases (`links` for `edges`, `source` and
+/// `target` for endpoints, `kind` or `label` for the edge type, `name` or
+/// `label` for titles, `tfidf` or `vector` for the semantic vector).
+#[derive(Deserialize)]
+pub struct ImportGraph {
+    #[serde(default, deserialize_with = "string_or_list")]
+    notes_root: Vec<String>,
+    nodes: Vec<ImportNode>,
+    #[serde(default, alias = "links")]
+    edges: Vec<ImportEdge>,
+    #[serde(default)]
+    stemmed: bool,
+}
+
+#[derive(Deserialize)]
+struct ImportNode {
+    id: String,
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default, alias = "name", alias = "label")]
+    title: Option<String>,
+    #[serde(default)]
+    stem: Option<String>,
+    #[serde(default, alias = "tfidf", alias = "vector")]
+    semantic: BTreeMap<String, f64>,
+    #[serde(default)]
+    term_counts: BTreeMap<String, usize>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    modified: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ImportEdge {
+    #[serde(alias = "source")]
+    src: String,
+    #[serde(alias = "target")]
+    dst: String,
+    /// Defaults to `WIKILINK`.
+    #[serde(default, rename = "type", alias = "kind", alias = "label")]
+    kind: Option<String>,
+    #[serde(default)]
+    confidence: Option<f64>,
+    #[serde(default)]
+    alias: Option<String>,
+    #[serde(default)]
+    weight: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct ImportReport {
+    pub nodes: usize,
+    pub edges: usize,
+    /// Ghost nodes created for `ghost/...` endpoints the input did not list.
+    pub ghosts_added: usize,
+    /// Nodes whose term counts and TF-IDF vector were rebuilt from note files.
+    pub recomputed: usize,
+}
+
+/// Reads an `ImportGraph` and turns it into a canonical `GraphData`. Node ids
+/// must be unique and every edge endpoint must be a listed node or a
+/// `ghost/...` id; all violations are reported together. With `notes_root`,
+/// nodes lacking term counts are re-tokenized from their files, and vectors
+/// missing from the input are computed over the whole corpus.
+pub fn import_graph(
+    input: &Path,
+    notes_root: Option<&Path>,
+) -> ExomResult<(GraphData, ImportReport)> {
+    let data = fs::read_to_string(input).map_err(io_at(input))?;
+    let imported: ImportGraph = serde_json::from_str(&data).map_err(json_at(input))?;
+
+    let mut problems = Vec::new();
+    let mut node_map: BTreeMap<String, Node> = BTreeMap::new();
+    for node in imported.nodes {
+        if node.id.trim().is_empty() {
+            problems.push("node with an empty id".to_string());
+            continue;
+        }
+        if node_map.contains_key(&node.id) {
+            problems.push(format!("duplicate node id {:?}", node.id));
+            continue;
+        }
+        let fallback = Path::new(node.path.as_deref().unwrap_or(&node.id))
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        node_map.insert(
+            node.id.clone(),
+            Node {
+                id: node.id,
+                path: node.path,
+                title: node.title.unwrap_or_else(|| fallback.clone()),
+                stem: node.stem.unwrap_or(fallback),
+                semantic: node.semantic,
+                term_counts: node.term_counts,
+                tags: node.tags,
+                aliases: node.aliases,
+                status: node.status,
+                modified: node.modified,
+                ..Default::default()
+            },
+        );
+    }
+    let mut ghosts_added = 0;
+    let mut edges = Vec::new();
+    for edge in imported.edges {
+        let mut dangling = false;
+        for endpoint in [&edge.src, &edge.dst] {
+            if node_map.contains_key(endpoint) {
+                continue;
+            }
+            match endpoint.strip_prefix("ghost/") {
+                Some(raw) if !raw.is_empty() => {
+                    ensure_ghost_node(&mut node_map, raw);
+                    ghosts_added += 1;
+                }
+                _ => {
+                    problems.push(format!(
+                        "edge {} -> {} has unknown endpoint {:?}",
+                        edge.src, edge.dst, endpoint
+                    ));
+                    dangling = true;
+                }
+            }
+        }
+        if !dangling {
+            edges.push(Edge {
+                src: edge.src,
+                dst: edge.dst,
+                kind: edge.kind.unwrap_or_else(|| "WIKILINK".into()),
+                confidence: edge.confidence,
+                alias: edge.alias,
+                weight: edge.weight,
+            });
+        }
+    }
+    if !problems.is_empty() {
+        return Err(ExomError::Import {
+            path: input.to_path_buf(),
+            problems,
+        });
+    }
+
+    let tokens = TokenOptions {
+        stem: imported.stemmed,
+        ..Default::default()
+    };
+    let mut recomputed = 0;
+    let mut missing_vectors: HashSet<String> = HashSet::new();
+    for node in node_map.values_mut() {
+        if node.semantic.is_empty() {
+            missing_vectors.insert(node.id.clone());
+        }
+        let (Some(root), Some(path), true) = (notes_root, &node.path, node.term_counts.is_empty())
+        else {
+            continue;
+        };
+        let Ok(content) = fs::read_to_string(root.join(path)) else {
+            continue;
+        };
+        let (_, body) = split_frontmatter(&content);
+        let mut counts = BTreeMap::new();
+        for token in token_sequence(&format!("{} {}", node.title, body), &tokens) {
+            *counts.entry(token).or_default() += 1;
+        }
+        node.term_counts = counts;
+        recomputed += 1;
+    }
+    let documents: Vec<&Node> = node_map
+        .values()
+        .filter(|node| !node.term_counts.is_empty())
+        .collect();
+    let total_docs = documents.len();
+    let mut doc_freq: BTreeMap<String, usize> = BTreeMap::new();
+    for node in &documents {
+        for token in node.term_counts.keys() {
+            *doc_freq.entry(token.clone()).or_default() += 1;
+        }
+    }
+    for node in node_map.values_mut() {
+        if missing_vectors.contains(&node.id) {
+            node.semantic = node
+                .term_counts
+                .iter()
+                .map(|(token, count)| {
+                    let df = doc_freq.get(token).copied().unwrap_or(0);
+                    (token.clone(), *count as f64 * tfidf_idf(total_docs, df))
+                })
+                .collect();
+        }
+        node.semantic_norm = vector_norm(&node.semantic);
+    }
+
+    let edges = dedup_edges(edges);
+    let notes = node_map.values().filter(|node| node.path.is_some()).count();
+    let report = ImportReport {
+        nodes: node_map.len(),
+        edges: edges.len(),
+        ghosts_added,
+        recomputed,
+    };
+    let graph = GraphData {
+        version: GRAPH_SCHEMA_VERSION,
+        notes_root: match notes_root {
+            Some(root) => vec![root.display().to_string()],
+            None => imported.notes_root,
+        },
+        stats: Stats {
+            notes,
+            nodes: node_map.len(),
+            edges: edges.len(),
+        },
+        nodes: node_map.into_values().collect(),
+        edges,
+        doc_freq,
+        total_docs,
+        stemmed: imported.stemmed,
+        ..Default::default()
+    };
+    Ok((graph, report))
+}
+
+pub fn write_graph_export(graph: &GraphData, format: GraphFormat, target: &Path) -> ExomResult<()> {
+    let rendered = match format {
+        GraphFormat::Json => serde_json::to_string_pretty(graph).map_err(json_at(target))?,
+        GraphFormat::Graphml => render_graphml(graph),
+        GraphFormat::Dot => render_dot(graph),
+    };
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(io_at(parent))?;
+    }
+    write_atomic(target, rendered)
+}
+
+/// Slims a graph for `exom compact`. Dropping vectors also zeroes the norms,
+/// so recall scores such a graph on its lexical and link components only.
+pub fn compact_graph(graph: &mut GraphData, drop_semantic: bool, quantize: bool) {
+    // Shortest f32 decimal, so the JSON text shrinks along with the precision.
+    let round = |weight: f64| (weight as f32).to_string().parse::<f64>().unwrap_or(weight);
+    for node in &mut graph.nodes {
+        if drop_semantic {
+            node.semantic.clear();
+            node.semantic_title.clear();
+            node.semantic_norm = 0.0;
+        } else if quantize {
+            for weight in node
+                .semantic
+                .values_mut()
+                .chain(node.semantic_title.values_mut())
+            {
+                *weight = round(*weight);
+            }
+            node.semantic_norm = round(node.semantic_norm);
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct NodeCsvRow<'a> {
+    id: &'a str,
+    title: &'a str,
+    stem: &'a str,
+    path: Option<&'a str>,
+    indegree: usize,
+}
+
+#[derive(Serialize)]
+struct EdgeCsvRow<'a> {
+    src: &'a str,
+    dst: &'a str,
+    kind: &'a str,
+    confidence: Option<f64>,
+}
+
+fn csv_writer(target: &Path) -> ExomResult<csv::Writer<fs::File>> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(io_at(parent))?;
+    }
+    csv::Writer::from_path(target).map_err(csv_at(target))
+}
+
+fn csv_at(path: &Path) -> impl FnOnce(csv::Error) -> ExomError + '_ {
+    move |source| ExomError::Csv {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// Ghost nodes get an empty `path` cell.
+pub fn write_nodes_csv(graph: &GraphData, target: &Path) -> ExomResult<()> {
+    let indegree = indegree_map(graph);
+    let mut writer = csv_writer(target)?;
+    for node in &graph.nodes {
+        writer
+            .serialize(NodeCsvRow {
+                id: &node.id,
+                title: &node.title,
+                stem: &node.stem,
+                path: node.path.as_deref(),
+                indegree: indegree.get(node.id.as_str()).copied().unwrap_or(0),
+            })
+            .map_err(csv_at(target))?;
+    }
+    writer.flush().map_err(io_at(target))
+}
+
+/// Edges without a confidence get an empty `confidence` cell.
+pub fn write_edges_csv(graph: &GraphData, target: &Path) -> ExomResult<()> {
+    let mut writer = csv_writer(target)?;
+    for edge in &graph.edges {
+        writer
+            .serialize(EdgeCsvRow {
+                src: &edge.src,
+                dst: &edge.dst,
+                kind: &edge.kind,
+                confidence: edge.confidence,
+            })
+            .map_err(csv_at(target))?;
+    }
+    writer.flush().map_err(io_at(target))
+}
+
+fn dot_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Drops edges whose kind (compared case-insensitively) is not in `kinds`,
+/// then every node no remaining edge touches.
+pub fn retain_edge_kinds(graph: &mut GraphData, kinds: &[String]) {
+    let kinds: HashSet<String> = kinds
+        .iter()
+        .map(|kind| kind.trim().to_uppercase())
+        .collect();
+    graph
+        .edges
+        .retain(|edge| kinds.contains(&edge.kind.to_uppercase()));
+    let linked: HashSet<&str> = graph
+        .edges
+        .iter()
+        .flat_map(|edge| [edge.src.as_str(), edge.dst.as_str()])
+        .collect();
+    let nodes = std::mem::take(&mut graph.nodes);
+    graph.nodes = nodes
+        .into_iter()
+        .filter(|node| linked.contains(node.id.as_str()))
+        .collect();
+    graph.stats.nodes = graph.nodes.len();
+    graph.stats.edges = graph.edges.len();
+}
+
+/// Edge label for visual exports: the kind, plus the confidence when known.
+fn edge_label(edge: &Edge) -> String {
+    match edge.confidence {
+        Some(confidence) => format!("{} ({:.2})", edge.kind, confidence),
+        None => edge.kind.clone(),
+    }
+}
+
+fn render_dot(graph: &GraphData) -> String {
+    let mut out = String::from("digraph exomind {\n");
+    for node in &graph.nodes {
+        let style = if node.path.is_none() {
+            ", style=dashed"
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "  \"{}\" [label=\"{}\", stem=\"{}\"{}];\n",
+            dot_escape(&node.id),
+            dot_escape(&node.title),
+            dot_escape(&node.stem),
+            style
+        ));
+    }
+    for edge in &graph.edges {
+        out.push_str(&format!(
+            "  \"{}\" -> \"{}\" [label=\"{}\"];\n",
+            dot_escape(&edge.src),
+            dot_escape(&edge.dst),
+            dot_escape(&edge_label(edge))
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_graphml(graph: &GraphData) -> String {
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"title\" for=\"node\" attr.name=\"title\" attr.type=\"string\"/>\n",
+        "  <key id=\"stem\" for=\"node\" attr.name=\"stem\" attr.type=\"string\"/>\n",
+        "  <key id=\"ghost\" for=\"node\" attr.name=\"ghost\" attr.type=\"boolean\"/>\n",
+        "  <key id=\"kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+        "  <key id=\"confidence\" for=\"edge\" attr.name=\"confidence\" attr.type=\"double\"/>\n",
+        "  <graph id=\"exomind\" edgedefault=\"directed\">\n",
+    ));
+    for node in &graph.nodes {
+        out.push_str(&format!(
+            "    <node id=\"{}\"><data key=\"title\">{}</data><data key=\"stem\">{}</data><data key=\"ghost\">{}</data></node>\n",
+            xml_escape(&node.id),
+            xml_escape(&node.title),
+            xml_escape(&node.stem),
+            node.path.is_none()
+        ));
+    }
+    for edge in &graph.edges {
+        let confidence = edge
+            .confidence
+            .map(|value| format!("<data key=\"confidence\">{}</data>", value))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "    <edge source=\"{}\" target=\"{}\"><data key=\"kind\">{}</data>{}</edge>\n",
+            xml_escape(&edge.src),
+            xml_escape(&edge.dst),
+            xml_escape(&edge.kind),
+            confidence
+        ));
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+/// JSON Schema for `graph.json`, derived from the serde types so it tracks
+/// every field change.
+pub fn graph_json_schema() -> schemars::schema::RootSchema {
+    schemars::schema_for!(GraphData)
+}
+
+pub fn require_graph(graph_path: &Path) -> ExomResult<GraphData> {
+    if !graph_path.exists() {
+        return Err(ExomError::GraphNotFound {
+            path: graph_path.to_path_buf(),
+        });
+    }
+    load_graph(graph_path)
+}
+
+pub fn ensure_stemming_matches(graph: &GraphData, stem: bool) -> ExomResult<()> {
+    if graph.stemmed != stem {
+        return Err(ExomError::invalid(format!(
+            "Graph was indexed with stemming {} but recall requested it {}; rebuild with matching `--stem`.",
+            if graph.stemmed { "on" } else { "off" },
+            if stem { "on" } else { "off" }
+        )));
+    }
+    Ok(())
+}
+
+/// Finds a node by exact id, then by case-insensitive title or stem.
+fn resolve_node<'a>(graph: &'a GraphData, key: &str) -> Option<&'a Node> {
+    let lowered = key.to_lowercase();
+    graph
+        .nodes
+        .iter()
+        .find(|node| node.id == key)
+        .or_else(|| {
+            graph
+                .nodes
+                .iter()
+                .find(|node| node.title.to_lowercase() == lowered)
+        })
+        .or_else(|| {
+            graph
+                .nodes
+                .iter()
+                .find(|node| node.stem.to_lowercase() == lowered)
+        })
+}
+
+pub fn require_node<'a>(graph: &'a GraphData, key: &str) -> ExomResult<&'a Node> {
+    resolve_node(graph, key).ok_or_else(|| ExomError::NoteNotFound {
+        key: key.to_string(),
+    })
+}
+
+/// Recall's graph signal at default edge weights, shared by `stats`, the CSV
+/// export, backlinks, and the indegree column so they all agree. Every factor
+/// is one there, so the weighted sums are whole counts.
+fn indegree_map(graph: &GraphData) -> HashMap<&str, usize> {
+    weighted_indegree(graph, &EdgeWeights::default())
+        .into_iter()
+        .map(|(id, degree)| (id, degree.round() as usize))
+        .collect()
+}
+
+#[derive(Serialize)]
+pub struct Backlink {
+    src: String,
+    kind: String,
+    /// Display text the referrer used, from `[[target|alias]]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
+    #[serde(skip)]
+    src_indegree: usize,
+}
+
+/// Lists edges pointing at `target`, most-referenced referrers first.
+pub fn backlinks_for(graph: &GraphData, target: &str) -> Vec<Backlink> {
+    let indegree = indegree_map(graph);
+    let mut reverse: HashMap<&str, Vec<&Edge>> = HashMap::new();
+    for edge in &graph.edges {
+        reverse.entry(edge.dst.as_str()).or_default().push(edge);
+    }
+    let mut links: Vec<Backlink> = reverse
+        .get(target)
+        .map(|edges| {
+            edges
+                .iter()
+                .map(|edge| Backlink {
+                    src: edge.src.clone(),
+                    kind: edge.kind.clone(),
+                    alias: edge.alias.clone(),
+                    src_indegree: indegree.get(edge.src.as_str()).copied().unwrap_or(0),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    links.sort_by(|a, b| {
+        b.src_indegree
+            .cmp(&a.src_indegree)
+            .then_with(|| a.src.cmp(&b.src))
+    });
+    links
+}
+
+/// Backlinks grouped by edge kind; aliased links show the referrer's label
+/// next to the target's own title.
+pub fn write_backlinks<W: Write>(out: &mut W, target: &Node, links: &[Backlink]) -> ExomResult<()> {
+    let mut kinds: Vec<&str> = links.iter().map(|l| l.kind.as_str()).collect();
+    kinds.sort();
+    kinds.dedup();
+    (|| -> std::io::Result<()> {
+        writeln!(out, "BACKLINKS {} count={}", target.id, links.len())?;
+        for kind in kinds {
+            writeln!(out, "  {}", kind)?;
+            for link in links.iter().filter(|l| l.kind == kind) {
+                write!(out, "    - {}", link.src)?;
+                if let Some(alias) = &link.alias {
+                    write!(out, " as \"{}\" (title \"{}\")", alias, target.title)?;
+                }
+                writeln!(out, " (indegree={})", link.src_indegree)?;
+            }
+        }
+        Ok(())
+    })()
+    .map_err(stream_error)
+}
+
+#[derive(Serialize)]
+pub struct Sibling {
+    pub id: String,
+    pub shared_parents: usize,
+}
+
+/// Real notes that share at least one linking source with `target`, most
+/// shared sources first, ties by id. Wikilinks and captured relations count
+/// as links; section containment and inferred similarity do not.
+pub fn sibling_notes(graph: &GraphData, target: &str, topk: usize) -> Vec<Sibling> {
+    let links: Vec<&Edge> = graph
+        .edges
+        .iter()
+        .filter(|edge| !is_structural(&edge.kind) && edge.kind != "INFERRED_RELATED")
+        .collect();
+    let parents: HashSet<&str> = links
+        .iter()
+        .filter(|edge| edge.dst == target && edge.src != target)
+        .map(|edge| edge.src.as_str())
+        .collect();
+    let real: HashSet<&str> = graph
+        .nodes
+        .iter()
+        .filter(|node| node.path.is_some())
+        .map(|node| node.id.as_str())
+        .collect();
+    // Parallel edges from one parent (a link and a relation) count once.
+    let mut shared: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for edge in links {
+        if parents.contains(edge.src.as_str())
+            && edge.dst != target
+            && edge.dst != edge.src
+            && real.contains(edge.dst.as_str())
+        {
+            shared
+                .entry(edge.dst.as_str())
+                .or_default()
+                .insert(edge.src.as_str());
+        }
+    }
+    let mut siblings: Vec<Sibling> = shared
+        .into_iter()
+        .map(|(id, sources)| Sibling {
+            id: id.to_string(),
+            shared_parents: sources.len(),
+        })
+        .collect();
+    siblings.sort_by(|a, b| {
+        b.shared_parents
+            .cmp(&a.shared_parents)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    if topk > 0 {
+        siblings.truncate(topk);
+    }
+    siblings
+}
+
+#[derive(Serialize)]
+pub struct NodeInspection {
+    pub id: String,
+    pub title: String,
+    pub vector_size: usize,
+    pub tokens: Vec<TokenWeight>,
+}
+
+#[derive(Serialize)]
+pub struct TokenWeight {
+    pub token: String,
+    pub weight: f64,
+}
+
+/// The `top` heaviest entries of a node's TF-IDF vector, ties by token;
+/// `top == 0` keeps them all.
+pub fn top_tokens_of(node: &Node, top: usize) -> Vec<TokenWeight> {
+    let mut tokens: Vec<TokenWeight> = node
+        .semantic
+        .iter()
+        .map(|(token, weight)| TokenWeight {
+            token: token.clone(),
+            weight: *weight,
+        })
+        .collect();
+    tokens.sort_by(|a, b| {
+        b.weight
+            .partial_cmp(&a.weight)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.token.cmp(&b.token))
+    });
+    if top > 0 {
+        tokens.truncate(top);
+    }
+    tokens
+}
+
+#[derive(Serialize)]
+pub struct RecallRow {
+    /// Node id, for looking the hit back up in the graph.
+    #[serde(skip)]
+    id: String,
+    rank: usize,
+    score: f64,
+    title: String,
+    path: Option<String>,
+    /// Best-matching body line with query tokens in `**bold**`, when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snippet: Option<String>,
+    /// Estimated minutes to read the note, when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reading_minutes: Option<f64>,
+    /// Label a `[[note|alias]]` link between the two notes used (`related` only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explain: Option<ScoreBreakdown>,
+}
+
+/// Where a recall score came from. The weighted parts plus `spread` add up
+/// to the row's score; `recency` and any `--normalize` rescaling of the raw
+/// values have already been applied to them.
+#[derive(Serialize)]
+struct ScoreBreakdown {
+    lexical_raw: f64,
+    graph_raw: f64,
+    semantic_raw: f64,
+    /// Cosine similarity to `--query-embedding-file`, when given.
+    embedding_raw: f64,
+    lexical: f64,
+    graph: f64,
+    semantic: f64,
+    embedding: f64,
+    /// Activation received from neighbours under `--spread`.
+    spread: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recency: Option<f64>,
+}
+
+pub struct RecallWeights {
+    pub lexical: f64,
+    pub graph: f64,
+    pub semantic: f64,
+}
+
+impl RecallWeights {
+    pub fn with_overrides(
+        self,
+        lexical: Option<f64>,
+        graph: Option<f64>,
+        semantic: Option<f64>,
+    ) -> Self {
+        RecallWeights {
+            lexical: lexical.unwrap_or(self.lexical),
+            graph: graph.unwrap_or(self.graph),
+            semantic: semantic.unwrap_or(self.semantic),
+        }
+    }
+}
+
+pub struct RecallOptions {
+    pub lexical_mode: LexicalMode,
+    pub bm25_k1: f64,
+    pub bm25_b: f64,
+    pub semantic_mode: SemanticMode,
+    /// Per-component rescaling applied before `RecallWeights`.
+    pub normalize: ScoreNormalization,
+    pub edge_weights: EdgeWeights,
+    pub graph_mode: GraphMode,
+    pub pagerank_ghosts: bool,
+    pub proximity_weight: f64,
+    pub fuzzy: bool,
+    pub fuzzy_distance: usize,
+    /// Drop notes last modified before this date, and ghosts, before ranking.
+    pub since: Option<NaiveDate>,
+    pub synonyms: Option<Synonyms>,
+    /// Score floor applied before truncating to `topk`; positive scores only.
+    pub min_score: f64,
+    /// Ranked rows skipped before `topk` applies; see `page_recall_rows`.
+    pub offset: usize,
+    /// Read each hit's file and attach its best-matching line.
+    pub snippets: bool,
+    /// Attach `word_count / WORDS_PER_MINUTE` to each row.
+    pub reading_time: bool,
+    /// Attach a `ScoreBreakdown` to each row.
+    pub explain: bool,
+    /// Hops of spreading activation applied after node-local scoring.
+    pub spread: usize,
+    pub spread_decay: f64,
+    /// Extra semantic credit for query terms in the title; 1.0 adds none.
+    pub title_boost: f64,
+    /// Query vector matched against `Node::embedding` by cosine similarity.
+    pub query_embedding: Option<Vec<f32>>,
+    pub embedding_weight: f64,
+    /// Exponential age decay on node scores; `None` leaves scores untouched.
+    pub recency_halflife_days: Option<f64>,
+    /// Pseudo-relevance feedback: one extra pass with harvested terms.
+    pub prf: Option<PrfOptions>,
+}
+
+pub struct PrfOptions {
+    pub docs: usize,
+    pub terms: usize,
+    pub weight: f64,
+}
+
+impl Default for RecallOptions {
+    fn default() -> Self {
+        RecallOptions {
+            lexical_mode: LexicalMode::Overlap,
+            bm25_k1: BM25_K1,
+            bm25_b: BM25_B,
+            semantic_mode: SemanticMode::Dot,
+            normalize: ScoreNormalization::None,
+            edge_weights: EdgeWeights::default(),
+            graph_mode: GraphMode::Indegree,
+            pagerank_ghosts: false,
+            proximity_weight: 1.0,
+            fuzzy: false,
+            fuzzy_distance: 2,
+            since: None,
+            synonyms: None,
+            min_score: 0.0,
+            offset: 0,
+            snippets: false,
+            reading_time: false,
+            explain: false,
+            spread: 0,
+            spread_decay: 0.5,
+            title_boost: 1.0,
+            query_embedding: None,
+            embedding_weight: 1.0,
+            recency_halflife_days: None,
+            prf: None,
+        }
+    }
+}
+
+/// Query expansion table. Every key and its listed equivalents form one
+/// group, so expansion works in both directions.
+pub struct Synonyms {
+    groups: Vec<Vec<String>>,
+    weight: f64,
+}
+
+impl Synonyms {
+    pub fn load(path: &Path, weight: f64) -> ExomResult<Self> {
+        let path = normalize_path(path.to_path_buf());
+        let content = fs::read_to_string(&path).map_err(io_at(&path))?;
+        let map: BTreeMap<String, Vec<String>> =
+            serde_json::from_str(&content).map_err(json_at(&path))?;
+        Ok(Synonyms::from_map(map, weight))
+    }
+
+    fn from_map(map: BTreeMap<String, Vec<String>>, weight: f64) -> Self {
+        let groups = map
+            .into_iter()
+            .map(|(term, equivalents)| std::iter::once(term).chain(equivalents).collect())
+            .collect();
+        Synonyms { groups, weight }
+    }
+
+    /// Counts for tokens reachable from the query through a synonym group,
+    /// excluding tokens the query already contains.
+    fn expand(
+        &self,
+        query_counts: &HashMap<String, usize>,
+        options: &TokenOptions,
+    ) -> HashMap<String, usize> {
+        let mut expanded: HashMap<String, usize> = HashMap::new();
+        for group in &self.groups {
+            let group_tokens: Vec<Vec<String>> = group
+                .iter()
+                .map(|term| token_sequence(term, options))
+                .collect();
+            let hits: usize = group_tokens
+                .iter()
+                .flatten()
+                .filter_map(|token| query_counts.get(token))
+                .sum();
+            if hits == 0 {
+                continue;
+            }
+            for token in group_tokens.into_iter().flatten() {
+                if !query_counts.contains_key(&token) {
+                    *expanded.entry(token).or_default() += hits;
+                }
+            }
+        }
+        expanded
+    }
+}
+
+/// Corpus statistics needed for BM25, derived from the stored term counts.
+struct Bm25Stats {
+    doc_freq: HashMap<String, usize>,
+    total_docs: usize,
+    avg_doc_len: f64,
+}
+
+impl Bm25Stats {
+    fn from_graph(graph: &GraphData) -> Self {
+        let mut doc_freq: HashMap<String, usize> = HashMap::new();
+        let mut total_docs = 0;
+        let mut total_len = 0;
+        for node in graph.nodes.iter().filter(|n| !n.term_counts.is_empty()) {
+            total_docs += 1;
+            total_len += node.term_counts.values().sum::<usize>();
+            for token in node.term_counts.keys() {
+                *doc_freq.entry(token.clone()).or_default() += 1;
+            }
+        }
+        let avg_doc_len = if total_docs > 0 {
+            total_len as f64 / total_docs as f64
+        } else {
+            0.0
+        };
+        Bm25Stats {
+            doc_freq,
+            total_docs,
+            avg_doc_len,
+        }
+    }
+}
+
+/// Query-independent data recall derives from a graph. `LoadedGraph` builds
+/// it once so repeated recalls (`repl`, `serve`, `benchmark`) reuse it.
+struct GraphIndexes {
+    tokens: TokenOptions,
+    /// `tfidf_idf` of every token in the persisted document frequencies.
+    idf: BTreeMap<String, f64>,
+    /// IDF of a token that no indexed note contains.
+    unseen_idf: f64,
+    bm25: Bm25Stats,
+    /// Indegree under the default `EdgeWeights`, the common case.
+    indegree: HashMap<String, f64>,
+    /// PageRank per `EdgeWeights` and ghost setting, filled on first use.
+    pagerank: Mutex<Vec<PagerankEntry>>,
+}
+
+struct PagerankEntry {
+    weights: EdgeWeights,
+    ghosts: bool,
+    ranks: HashMap<String, f64>,
+}
+
+impl GraphIndexes {
+    fn build(graph: &GraphData) -> Self {
+        GraphIndexes {
+            tokens: TokenOptions::from_graph(graph),
+            idf: graph
+                .doc_freq
+                .iter()
+                .map(|(token, df)| (token.clone(), tfidf_idf(graph.total_docs, *df)))
+                .collect(),
+            unseen_idf: tfidf_idf(graph.total_docs, 0),
+            bm25: Bm25Stats::from_graph(graph),
+            indegree: weighted_indegree(graph, &EdgeWeights::default())
+                .into_iter()
+                .map(|(id, degree)| (id.to_string(), degree))
+                .collect(),
+            pagerank: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// PageRank of each node id, computed once per weights/ghosts pair.
+    fn pagerank<'a>(
+        &self,
+        graph: &'a GraphData,
+        weights: &EdgeWeights,
+        ghosts: bool,
+    ) -> HashMap<&'a str, f64> {
+        let mut cache = self.pagerank.lock().unwrap_or_else(|err| err.into_inner());
+        let cached = cache
+            .iter()
+            .position(|entry| entry.ghosts == ghosts && entry.weights == *weights);
+        let idx = cached.unwrap_or_else(|| {
+            cache.push(PagerankEntry {
+                weights: weights.clone(),
+                ghosts,
+                ranks: pagerank(graph, weights, ghosts)
+                    .into_iter()
+                    .map(|(id, rank)| (id.to_string(), rank))
+                    .collect(),
+            });
+            cache.len() - 1
+        });
+        let ranks = &cache[idx].ranks;
+        graph
+            .nodes
+            .iter()
+            .filter_map(|node| Some((node.id.as_str(), *ranks.get(&node.id)?)))
+            .collect()
+    }
+
+    fn idf(&self, token: &str) -> f64 {
+        self.idf.get(token).copied().unwrap_or(self.unseen_idf)
+    }
+}
+
+/// A graph loaded to answer many queries, with its `GraphIndexes`.
+pub struct LoadedGraph {
+    graph: GraphData,
+    indexes: GraphIndexes,
+}
+
+impl LoadedGraph {
+    /// Also fills in vector norms missing from graphs that predate them.
+    pub fn new(mut graph: GraphData) -> Self {
+        for node in graph.nodes.iter_mut().filter(|n| n.semantic_norm <= 0.0) {
+            node.semantic_norm = vector_norm(&node.semantic);
+        }
+        let indexes = GraphIndexes::build(&graph);
+        LoadedGraph { graph, indexes }
+    }
+
+    fn recall(
+        &self,
+        query: &str,
+        topk: usize,
+        weights: &RecallWeights,
+        options: &RecallOptions,
+    ) -> Vec<RecallRow> {
+        recall_indexed(&self.graph, &self.indexes, query, topk, weights, options)
+    }
+}
+
+impl std::ops::Deref for LoadedGraph {
+    type Target = GraphData;
+
+    fn deref(&self) -> &GraphData {
+        &self.graph
+    }
+}
+
+/// One-off recall; callers running many queries should use `LoadedGraph`.
+pub fn recall_from_graph(
+    graph: &GraphData,
+    query: &str,
+    topk: usize,
+    weights: &RecallWeights,
+    options: &RecallOptions,
+) -> Vec<RecallRow> {
+    let indexes = GraphIndexes::build(graph);
+    recall_indexed(graph, &indexes, query, topk, weights, options)
+}
+
+fn recall_indexed(
+    graph: &GraphData,
+    indexes: &GraphIndexes,
+    query: &str,
+    topk: usize,
+    weights: &RecallWeights,
+    options: &RecallOptions,
+) -> Vec<RecallRow> {
+    let token_options = &indexes.tokens;
+    let query_tokens = tokens(query, token_options);
+    let query_counts = token_counts(query, token_options);
+    let query_bigrams = bigram_counts(&token_sequence(query, token_options));
+    let expanded_counts = options
+        .synonyms
+        .as_ref()
+        .map(|synonyms| synonyms.expand(&query_counts, token_options))
+        .unwrap_or_default();
+    let expanded_tokens: HashSet<String> = expanded_counts.keys().cloned().collect();
+    let synonym_weight = options.synonyms.as_ref().map_or(0.0, |s| s.weight);
+    let mut query_terms = vec![(query_counts, 1.0)];
+    if !expanded_counts.is_empty() {
+        query_terms.push((expanded_counts, synonym_weight));
+    }
+    let mut breakdowns = HashMap::new();
+    let mut node_scores = score_nodes(
+        graph,
+        indexes,
+        &query_terms,
+        &query_bigrams,
+        weights,
+        options,
+        &mut breakdowns,
+    );
+    if let Some(prf) = &options.prf {
+        let feedback = feedback_terms(graph, &node_scores, &query_terms, prf);
+        if !feedback.is_empty() {
+            query_terms.push((feedback, prf.weight));
+            node_scores = score_nodes(
+                graph,
+                indexes,
+                &query_terms,
+                &query_bigrams,
+                weights,
+                options,
+                &mut breakdowns,
+            );
+        }
+    }
+
+    let mut scored = Vec::new();
+    for node in &graph.nodes {
+        let Some(&score) = node_scores.get(node.id.as_str()) else {
+            continue;
+        };
+        if score <= 0.0 || score < options.min_score {
+            continue;
+        }
+        let explain = breakdowns.remove(node.id.as_str()).map(|mut parts| {
+            parts.spread = score - parts.lexical - parts.graph - parts.semantic - parts.embedding;
+            parts
+        });
+        scored.push(RecallRow {
+            id: node.id.clone(),
+            rank: 0,
+            score,
+            title: node.title.clone(),
+            path: node.path.clone(),
+            snippet: None,
+            reading_minutes: options
+                .reading_time
+                .then(|| node.word_count as f64 / WORDS_PER_MINUTE),
+            alias: None,
+            explain,
+        });
+    }
+
+    scored.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    page_recall_rows(&mut scored, options.offset, topk);
+    let highlight: HashSet<String> = query_tokens.union(&expanded_tokens).cloned().collect();
+    for row in scored.iter_mut() {
+        if options.snippets {
+            row.snippet = row
+                .path
+                .as_deref()
+                .and_then(|path| note_snippet(&graph.note_file(path), &highlight, token_options));
+        }
+    }
+    scored
+}
+
+/// Numbers sorted rows by absolute position, then keeps `topk` of them after
+/// skipping `offset`. `topk == 0` keeps every row past the offset.
+pub fn page_recall_rows(rows: &mut Vec<RecallRow>, offset: usize, topk: usize) {
+    for (idx, row) in rows.iter_mut().enumerate() {
+        row.rank = idx + 1;
+    }
+    rows.drain(..offset.min(rows.len()));
+    if topk > 0 {
+        rows.truncate(topk);
+    }
+}
+
+/// Notes whose title or stem equals `title`, ordered by path, each scored 1.
+/// No ranking fallback: a miss returns nothing.
+pub fn exact_title_matches(
+    graph: &GraphData,
+    title: &str,
+    ignore_case: bool,
+    topk: usize,
+) -> Vec<RecallRow> {
+    let wanted = if ignore_case {
+        title.to_lowercase()
+    } else {
+        title.to_string()
+    };
+    let matches = |text: &str| {
+        if ignore_case {
+            text.to_lowercase() == wanted
+        } else {
+            text == wanted
+        }
+    };
+    let mut rows: Vec<RecallRow> = graph
+        .nodes
+        .iter()
+        .filter(|node| node.path.is_some() && (matches(&node.title) || matches(&node.stem)))
+        .map(|node| RecallRow {
+            id: node.id.clone(),
+            rank: 0,
+            score: 1.0,
+            title: node.title.clone(),
+            path: node.path.clone(),
+            snippet: None,
+            reading_minutes: None,
+            alias: None,
+            explain: None,
+        })
+        .collect();
+    rows.sort_by(|a, b| a.path.cmp(&b.path));
+    page_recall_rows(&mut rows, 0, topk);
+    rows
+}
+
+/// Node-local scores, plus any spreading activation, for weighted groups of
+/// query terms. The first group is the query itself; later groups (synonyms,
+/// feedback terms) add their lexical and semantic matches at their weight.
+/// With `options.explain`, each node's local parts also go to `breakdowns`.
+fn score_nodes<'a>(
+    graph: &'a GraphData,
+    indexes: &'a GraphIndexes,
+    query_terms: &[(HashMap<String, usize>, f64)],
+    query_bigrams: &BTreeMap<String, usize>,
+    weights: &RecallWeights,
+    options: &RecallOptions,
+    breakdowns: &mut HashMap<&'a str, ScoreBreakdown>,
+) -> HashMap<&'a str, f64> {
+    let token_options = &indexes.tokens;
+    let link_scores: HashMap<&str, f64> = match options.graph_mode {
+        GraphMode::Indegree if options.edge_weights == EdgeWeights::default() => indexes
+            .indegree
+            .iter()
+            .map(|(id, degree)| (id.as_str(), *degree))
+            .collect(),
+        GraphMode::Indegree => weighted_indegree(graph, &options.edge_weights),
+        GraphMode::Pagerank => {
+            // Scale so an average note scores 1.0, comparable to one inbound link.
+            let scale = graph.nodes.len() as f64;
+            indexes
+                .pagerank(graph, &options.edge_weights, options.pagerank_ghosts)
+                .into_iter()
+                .map(|(id, rank)| (id, rank * scale))
+                .collect()
+        }
+    };
+    let mut query_vector = BTreeMap::new();
+    for (counts, weight) in query_terms {
+        for (token, value) in query_tfidf(counts, indexes) {
+            *query_vector.entry(token).or_default() += weight * value;
+        }
+    }
+    let term_sets: Vec<(HashSet<String>, f64)> = query_terms
+        .iter()
+        .map(|(counts, weight)| (counts.keys().cloned().collect(), *weight))
+        .collect();
+    let query_tokens = &term_sets[0].0;
+    let now = Utc::now();
+
+    let mut candidates: Vec<(&str, ScoreBreakdown)> = Vec::new();
+    for node in &graph.nodes {
+        if let Some(cutoff) = options.since {
+            if !modified_since(node, cutoff) {
+                continue;
+            }
+        }
+        let lexical_for = |terms: &HashSet<String>| match options.lexical_mode {
+            LexicalMode::Bm25 => bm25_score(terms, &node.term_counts, &indexes.bm25, options),
+            LexicalMode::Overlap => {
+                let text = format!("{} {}", node.title, node.path.as_deref().unwrap_or(""));
+                lexical_overlap_score(terms, &text, token_options)
+            }
+            LexicalMode::Jaccard | LexicalMode::Dice => {
+                vocabulary_similarity(terms, &node.term_counts, options.lexical_mode)
+            }
+        };
+        let mut lexical = lexical_for(query_tokens)
+            + options.proximity_weight * proximity_bonus(query_bigrams, node);
+        for (terms, weight) in &term_sets[1..] {
+            lexical += weight * lexical_for(terms);
+        }
+        let lexical = if options.fuzzy {
+            lexical + fuzzy_title_score(query_tokens, node, token_options, options.fuzzy_distance)
+        } else {
+            lexical
+        };
+        let graph_value =
+            graph_influence(link_scores.get(node.id.as_str()).copied().unwrap_or(0.0));
+        let semantic = match options.semantic_mode {
+            SemanticMode::Cosine => cosine_score(&query_vector, node),
+            SemanticMode::Dot => semantic_score(&query_vector, &node.semantic),
+        } + (options.title_boost - 1.0)
+            * title_score(&query_vector, node, options.semantic_mode);
+        let embedding = match (&options.query_embedding, &node.embedding) {
+            (Some(query), Some(vector)) => embedding_cosine(query, vector),
+            _ => 0.0,
+        };
+        let recency = options
+            .recency_halflife_days
+            .map(|halflife| recency_factor(node, halflife, now));
+        candidates.push((
+            node.id.as_str(),
+            ScoreBreakdown {
+                lexical_raw: lexical,
+                graph_raw: graph_value,
+                semantic_raw: semantic,
+                embedding_raw: embedding,
+                lexical,
+                graph: graph_value,
+                semantic,
+                embedding,
+                spread: 0.0,
+                recency,
+            },
+        ));
+    }
+
+    let mut columns = [
+        candidates
+            .iter()
+            .map(|(_, p)| p.lexical)
+            .collect::<Vec<_>>(),
+        candidates.iter().map(|(_, p)| p.graph).collect(),
+        candidates.iter().map(|(_, p)| p.semantic).collect(),
+        candidates.iter().map(|(_, p)| p.embedding).collect(),
+    ];
+    for column in &mut columns {
+        options.normalize.apply(column);
+    }
+    let mut node_scores: HashMap<&str, f64> = HashMap::new();
+    for (idx, (id, mut parts)) in candidates.into_iter().enumerate() {
+        let decay = parts.recency.unwrap_or(1.0);
+        parts.lexical = decay * weights.lexical * columns[0][idx];
+        parts.graph = decay * weights.graph * columns[1][idx];
+        parts.semantic = decay * weights.semantic * columns[2][idx];
+        parts.embedding = decay * options.embedding_weight * columns[3][idx];
+        node_scores.insert(
+            id,
+            parts.lexical + parts.graph + parts.semantic + parts.embedding,
+        );
+        if options.explain {
+            breakdowns.insert(id, parts);
+        }
+    }
+    if options.spread > 0 {
+        spread_activation(graph, &mut node_scores, options);
+    }
+
+    node_scores
+}
+
+/// Rocchio-style feedback: the `terms` highest-weight TF-IDF terms summed
+/// over the `docs` best-scoring notes, leaving out terms already queried.
+fn feedback_terms(
+    graph: &GraphData,
+    scores: &HashMap<&str, f64>,
+    query_terms: &[(HashMap<String, usize>, f64)],
+    prf: &PrfOptions,
+) -> HashMap<String, usize> {
+    let mut top: Vec<(&str, f64)> = scores
+        .iter()
+        .filter(|(_, score)| **score > 0.0)
+        .map(|(id, score)| (*id, *score))
+        .collect();
+    top.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.cmp(b.0))
+    });
+    let top: HashSet<&str> = top.into_iter().take(prf.docs).map(|(id, _)| id).collect();
+    let mut summed: BTreeMap<&str, f64> = BTreeMap::new();
+    for node in graph
+        .nodes
+        .iter()
+        .filter(|node| top.contains(node.id.as_str()))
+    {
+        for (token, weight) in &node.semantic {
+            if !query_terms
+                .iter()
+                .any(|(counts, _)| counts.contains_key(token))
+            {
+                *summed.entry(token.as_str()).or_default() += weight;
+            }
+        }
+    }
+    let mut ranked: Vec<(&str, f64)> = summed.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    ranked
+        .into_iter()
+        .take(prf.terms)
+        .map(|(token, _)| (token.to_string(), 1))
+        .collect()
+}
+
+/// Pushes `spread_decay` of each node's positive score to its neighbours, in
+/// both edge directions, for `spread` hops. Each edge scales the flow by its
+/// confidence and `EdgeWeights` factor. Only nodes already in `scores` (those
+/// that passed the recall filters) can gain score.
+fn spread_activation<'a>(
+    graph: &'a GraphData,
+    scores: &mut HashMap<&'a str, f64>,
+    options: &RecallOptions,
+) {
+    let mut frontier: HashMap<&str, f64> = scores
+        .iter()
+        .filter(|(_, score)| **score > 0.0)
+        .map(|(id, score)| (*id, *score))
+        .collect();
+    for _ in 0..options.spread {
+        let mut next: HashMap<&str, f64> = HashMap::new();
+        for edge in graph.edges.iter().filter(|edge| !is_structural(&edge.kind)) {
+            let factor = options.spread_decay
+                * edge.confidence.unwrap_or(1.0)
+                * options.edge_weights.factor(&edge.kind);
+            if factor <= 0.0 {
+                continue;
+            }
+            if let Some(activation) = frontier.get(edge.src.as_str()) {
+                *next.entry(edge.dst.as_str()).or_default() += activation * factor;
+            }
+            if let Some(activation) = frontier.get(edge.dst.as_str()) {
+                *next.entry(edge.src.as_str()).or_default() += activation * factor;
+            }
+        }
+        for (id, activation) in &next {
+            if let Some(score) = scores.get_mut(id) {
+                *score += activation;
+            }
+        }
+        frontier = next;
+    }
+}
+
+/// The body line sharing the most distinct tokens with the query, with those
+/// tokens wrapped in `**`. Unreadable files and notes with no overlap give `None`.
+fn note_snippet(
+    path: &Path,
+    query_tokens: &HashSet<String>,
+    options: &TokenOptions,
+) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let (_, body) = split_frontmatter(&content);
+    let mut best: Option<(usize, &str)> = None;
+    for line in body.lines() {
+        let line = line.trim().trim_start_matches('#').trim();
+        if line.is_empty() || line.starts_with(METADATA_PREFIX) {
+            continue;
+        }
+        let overlap = tokens(line, options).intersection(query_tokens).count();
+        if overlap > best.map_or(0, |(count, _)| count) {
+            best = Some((overlap, line));
+        }
+    }
+    let (_, line) = best?;
+    let matched = |word: &str| {
+        token_sequence(word, options)
+            .first()
+            .is_some_and(|token| query_tokens.contains(token))
+    };
+    if options.unicode {
+        return Some(
+            line.split_word_bounds()
+                .map(|piece| {
+                    if matched(piece) {
+                        format!("**{}**", piece)
+                    } else {
+                        piece.to_string()
+                    }
+                })
+                .collect(),
+        );
+    }
+    let highlighted = TOKEN_REGEX.replace_all(line, |caps: &regex::Captures| {
+        let word = &caps[0];
+        if matched(word) {
+            format!("**{}**", word)
+        } else {
+            word.to_string()
+        }
+    });
+    Some(highlighted.into_owned())
+}
+
+fn modified_since(node: &Node, cutoff: NaiveDate) -> bool {
+    node.modified
+        .as_deref()
+        .and_then(|raw| DateTime::parse_from_rfc3339(raw).ok())
+        .map(|time| time.with_timezone(&Utc).date_naive() >= cutoff)
+        .unwrap_or(false)
+}
+
+/// `0.5^(age / halflife)` from the stored mtime. Ghosts get
+/// `GHOST_RECENCY_FACTOR`, notes without a timestamp are not decayed, and
+/// future mtimes count as brand new.
+fn recency_factor(node: &Node, halflife_days: f64, now: DateTime<Utc>) -> f64 {
+    if halflife_days <= 0.0 {
+        return 1.0;
+    }
+    if node.path.is_none() {
+        return GHOST_RECENCY_FACTOR;
+    }
+    let Some(modified) = node
+        .modified
+        .as_deref()
+        .and_then(|raw| DateTime::parse_from_rfc3339(raw).ok())
+    else {
+        return 1.0;
+    };
+    let age_days = (now - modified.with_timezone(&Utc)).num_seconds().max(0) as f64 / 86_400.0;
+    0.5f64.powf(age_days / halflife_days)
+}
+
+pub fn parse_since(raw: &str) -> ExomResult<NaiveDate> {
+    parse_day(raw, "--since")
+}
+
+pub fn parse_day(raw: &str, flag: &str) -> ExomResult<NaiveDate> {
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d").map_err(|err| {
+        ExomError::invalid(format!(
+            "invalid {} date {:?}; expected YYYY-MM-DD ({})",
+            flag, raw, err
+        ))
+    })
+}
+
+/// Tokenizer settings that must match between indexing and recall.
+#[derive(Clone, Default)]
+pub struct TokenOptions {
+    pub stem: bool,
+    /// Lowercased surface forms dropped before stemming.
+    pub stopwords: BTreeSet<String>,
+    /// Segment words with Unicode rules rather than `TOKEN_REGEX`.
+    pub unicode: bool,
+    /// Tokens with fewer characters are dropped; 0 and 1 keep everything.
+    pub min_len: usize,
+    /// Drop tokens made only of ASCII digits.
+    pub drop_numeric: bool,
+}
+
+impl TokenOptions {
+    pub fn from_graph(graph: &GraphData) -> Self {
+        TokenOptions {
+            stem: graph.stemmed,
+            stopwords: graph.stopwords.clone(),
+            unicode: graph.unicode_tokens,
+            min_len: graph.min_token_len,
+            drop_numeric: graph.drop_numeric,
+        }
+    }
+
+    /// Whether a lowercased word survives the stopword, length, and numeric filters.
+    fn keeps(&self, normalized: &str) -> bool {
+        !self.stopwords.contains(normalized)
+            && (self.min_len <= 1 || normalized.chars().count() >= self.min_len)
+            && !(self.drop_numeric && normalized.chars().all(|c| c.is_ascii_digit()))
+    }
+
+    /// Compact description stored in the index manifest so a settings change
+    /// invalidates cached token counts.
+    fn fingerprint(&self) -> String {
+        let mut fingerprint = format!("stem={}", self.stem);
+        if !self.stopwords.is_empty() {
+            let joined: Vec<&str> = self.stopwords.iter().map(String::as_str).collect();
+            fingerprint.push_str(&format!(";stopwords={}", content_hash(&joined.join("\n"))));
+        }
+        if self.unicode {
+            fingerprint.push_str(";tokenizer=unicode");
+        }
+        if self.min_len > 1 {
+            fingerprint.push_str(&format!(";min_len={}", self.min_len));
+        }
+        if self.drop_numeric {
+            fingerprint.push_str(";numeric=drop");
+        }
+        fingerprint
+    }
+
+    /// Raw word spans in document order, before lowercasing and filtering.
+    fn words<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        if self.unicode {
+            text.unicode_words().collect()
+        } else {
+            TOKEN_REGEX.find_iter(text).map(|m| m.as_str()).collect()
+        }
+    }
+}
+
+/// Builds the stopword set from an optional word file plus the built-in list.
+pub fn load_stopwords(path: Option<&Path>, include_default: bool) -> ExomResult<BTreeSet<String>> {
+    let mut words: BTreeSet<String> = BTreeSet::new();
+    if include_default {
+        words.extend(DEFAULT_STOPWORDS.iter().map(|word| word.to_string()));
+    }
+    if let Some(path) = path {
+        let path = normalize_path(path.to_path_buf());
+        let content = fs::read_to_string(&path).map_err(io_at(&path))?;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            words.extend(line.split_whitespace().map(str::to_lowercase));
+        }
+    }
+    Ok(words)
+}
+
+/// One ` | `-separated line per hit with just the requested columns. Fields
+/// a hit lacks (a ghost's path, an unmodified time) print as `-`.
+pub fn write_recall_columns<W: Write>(
+    out: &mut W,
+    graph: &GraphData,
+    rows: &[RecallRow],
+    columns: &[RecallColumn],
+) -> ExomResult<()> {
+    let indegree = indegree_map(graph);
+    let nodes: HashMap<&str, &Node> = graph.nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+    for row in rows {
+        let node = nodes.get(row.id.as_str());
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|column| match column {
+                RecallColumn::Rank => format!("{:02}", row.rank),
+                RecallColumn::Score => format!("{:.2}", row.score),
+                RecallColumn::Title => row.title.clone(),
+                RecallColumn::Path => row.path.clone().unwrap_or_else(|| "-".into()),
+                RecallColumn::Stem => node.map_or_else(|| "-".into(), |n| n.stem.clone()),
+                RecallColumn::Indegree => indegree
+                    .get(row.id.as_str())
+                    .copied()
+                    .unwrap_or(0)
+                    .to_string(),
+                RecallColumn::Snippet => row.snippet.clone().unwrap_or_else(|| "-".into()),
+                RecallColumn::Modified => node
+                    .and_then(|n| n.modified.clone())
+                    .unwrap_or_else(|| "-".into()),
+            })
+            .collect();
+        writeln!(out, "{}", fields.join(" | ")).map_err(stream_error)?;
+    }
+    Ok(())
+}
+
+pub fn write_recall_rows<W: Write>(out: &mut W, rows: &[RecallRow]) -> ExomResult<()> {
+    recall_row_lines(out, rows).map_err(stream_error)
+}
+
+fn recall_row_lines<W: Write>(out: &mut W, rows: &[RecallRow]) -> std::io::Result<()> {
+    for row in rows {
+        write!(
+            out,
+            "{:02}. score={:.2} | {} | {}",
+            row.rank,
+            row.score,
+            row.title,
+            row.path.as_deref().unwrap_or("None")
+        )?;
+        if let Some(minutes) = row.reading_minutes {
+            write!(out, " | ~{:.1} min", minutes)?;
+        }
+        if let Some(alias) = &row.alias {
+            write!(out, " | via \"{}\"", alias)?;
+        }
+        writeln!(out)?;
+        if let Some(snippet) = &row.snippet {
+            writeln!(out, "    > {}", snippet)?;
+        }
+        if let Some(parts) = &row.explain {
+            write!(
+                out,
+                "    = lexical {:.3} (raw {:.3}) + graph {:.3} (raw {:.3}) + semantic {:.3} (raw {:.3})",
+                parts.lexical,
+                parts.lexical_raw,
+                parts.graph,
+                parts.graph_raw,
+                parts.semantic,
+                parts.semantic_raw
+            )?;
+            if parts.embedding_raw != 0.0 {
+                write!(
+                    out,
+                    " + embedding {:.3} (raw {:.3})",
+                    parts.embedding, parts.embedding_raw
+                )?;
+            }
+            if parts.spread.abs() > 1e-12 {
+                write!(out, " + spread {:.3}", parts.spread)?;
+            }
+            if let Some(recency) = parts.recency {
+                write!(out, " [recency x{:.3}]", recency)?;
+            }
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Absolute path of the `rank`-th recall hit, joined onto the graph's notes root.
+pub fn resolve_recall_hit(graph: &GraphData, query: &str, rank: usize) -> ExomResult<PathBuf> {
+    if rank == 0 {
+        return Err(ExomError::invalid("--rank is 1-based"));
+    }
+    let rows = recall_from_graph(
+        graph,
+        query,
+        rank,
+        &RecallProfile::Balanced.weights(),
+        &RecallOptions::default(),
+    );
+    let Some(row) = rows.get(rank - 1) else {
+        return Err(ExomError::invalid(format!(
+            "Recall returned {} result(s) for {:?}; rank {} is out of range.",
+            rows.len(),
+            query,
+            rank
+        )));
+    };
+    match &row.path {
+        Some(path) => Ok(graph.note_file(path)),
+        None => Err(ExomError::invalid(format!(
+            "Hit {} ({}) is a ghost node with no file; create the note first.",
+            rank, row.title
+        ))),
+    }
+}
+
+pub fn open_in_editor(path: &Path) -> ExomResult<()> {
+    let editor = env::var("EDITOR")
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| {
+            ExomError::invalid("$EDITOR is not set; use --print-only to print the path instead")
+        })?;
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or_default();
+    let command_error = |detail: String| ExomError::Command {
+        program: program.to_string(),
+        detail,
+    };
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|err| command_error(format!("failed to launch editor: {}", err)))?;
+    if !status.success() {
+        return Err(command_error(format!("editor exited with {}", status)));
+    }
+    Ok(())
+}
+
+/// Reads queries line by line until EOF. Lines starting with `:` adjust the
+/// session (`:weights L G S`, `:topk N`, `:quit`) instead of running recall.
+pub fn run_repl<R: BufRead, W: Write>(
+    graph: &LoadedGraph,
+    input: R,
+    out: &mut W,
+    topk: usize,
+    prompt: bool,
+) -> ExomResult<()> {
+    repl_session(graph, input, out, topk, prompt).map_err(stream_error)
+}
+
+fn repl_session<R: BufRead, W: Write>(
+    graph: &LoadedGraph,
+    input: R,
+    out: &mut W,
+    topk: usize,
+    prompt: bool,
+) -> std::io::Result<()> {
+    let mut weights = RecallWeights {
+        lexical: 1.0,
+        graph: 1.0,
+        semantic: 1.0,
+    };
+    let mut topk = topk;
+    let options = RecallOptions::default();
+    if prompt {
+        write!(out, "exom> ")?;
+        out.flush()?;
+    }
+    for line in input.lines() {
+        let line = line?;
+        let trimmed = line.trim();
+        if let Some(command) = trimmed.strip_prefix(':') {
+            let mut parts = command.split_whitespace();
+            match parts.next() {
+                Some("weights") => {
+                    let values: Vec<f64> = parts.filter_map(|v| v.parse().ok()).collect();
+                    if let [lexical, graph_weight, semantic] = values[..] {
+                        weights = RecallWeights {
+                            lexical,
+                            graph: graph_weight,
+                            semantic,
+                        };
+                        writeln!(
+                            out,
+                            "weights lexical={} graph={} semantic={}",
+                            lexical, graph_weight, semantic
+                        )?;
+                    } else {
+                        writeln!(out, "usage: :weights <lexical> <graph> <semantic>")?;
+                    }
+                }
+                Some("topk") => match parts.next().and_then(|v| v.parse().ok()) {
+                    Some(value) => {
+                        topk = value;
+                        writeln!(out, "topk={}", topk)?;
+                    }
+                    None => writeln!(out, "usage: :topk <n>")?,
+                },
+                Some("quit") | Some("q") => break,
+                _ => writeln!(out, "commands: :weights L G S, :topk N, :quit")?,
+            }
+        } else if !trimmed.is_empty() {
+            let rows = graph.recall(trimmed, topk, &weights, &options);
+            if rows.is_empty() {
+                writeln!(out, "(no results)")?;
+            }
+            recall_row_lines(out, &rows)?;
+        }
+        if prompt {
+            write!(out, "exom> ")?;
+            out.flush()?;
+        }
+    }
+    if prompt {
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Routes one `exom serve` request URL to a status code and JSON body. Kept
+/// free of the HTTP layer so it can be exercised directly.
+pub fn handle_api_request(graph: &LoadedGraph, url: &str) -> (u16, String) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    match path {
+        "/healthz" => (
+            200,
+            serde_json::json!({"status": "ok", "nodes": graph.nodes.len()}).to_string(),
+        ),
+        "/recall" => match api_recall(graph, &parse_query_string(query)) {
+            Ok(response) => match serde_json::to_string(&response) {
+                Ok(body) => (200, body),
+                Err(err) => api_error(500, &err.to_string()),
+            },
+            Err(err) => api_error(400, &err.to_string()),
+        },
+        _ => api_error(404, &format!("no route for {}", path)),
+    }
+}
+
+fn api_recall(graph: &LoadedGraph, params: &HashMap<String, String>) -> ExomResult<RecallResponse> {
+    let query = params
+        .get("q")
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| ExomError::invalid("missing query parameter `q`"))?
+        .clone();
+    let number = |key: &str| -> ExomResult<Option<f64>> {
+        params
+            .get(key)
+            .map(|raw| {
+                raw.parse::<f64>().map_err(|_| {
+                    ExomError::invalid(format!("`{}` must be a number, got {:?}", key, raw))
+                })
+            })
+            .transpose()
+    };
+    let top_k = match params.get("topk") {
+        Some(raw) => raw.parse().map_err(|_| {
+            ExomError::invalid(format!(
+                "`topk` must be a non-negative integer (0 = all), got {:?}",
+                raw
+            ))
+        })?,
+        None => 10,
+    };
+    let profile = match params.get("profile") {
+        Some(raw) => RecallProfile::from_str(raw, true)
+            .map_err(|_| ExomError::invalid(format!("unknown profile {:?}", raw)))?,
+        None => RecallProfile::Balanced,
+    };
+    let weights = profile.weights().with_overrides(
+        number("lexical_weight")?,
+        number("graph_weight")?,
+        number("semantic_weight")?,
+    );
+    let results = graph.recall(&query, top_k, &weights, &RecallOptions::default());
+    Ok(RecallResponse {
+        query,
+        top_k,
+        results,
+    })
+}
+
+pub fn api_error(status: u16, message: &str) -> (u16, String) {
+    (status, serde_json::json!({"error": message}).to_string())
+}
+
+/// Decodes `a=1&b=two+words` style query strings, including `%XX` escapes.
+fn parse_query_string(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let escaped = (bytes[idx] == b'%')
+            .then(|| raw.get(idx + 1..idx + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[idx], escaped) {
+            (_, Some(byte)) => {
+                decoded.push(byte);
+                idx += 3;
+                continue;
+            }
+            (b'+', None) => decoded.push(b' '),
+            (byte, None) => decoded.push(byte),
+        }
+        idx += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Normalized tokens in document order.
+fn token_sequence(text: &str, options: &TokenOptions) -> Vec<String> {
+    options
+        .words(text)
+        .into_iter()
+        .map(str::to_lowercase)
+        .filter(|normalized| options.keeps(normalized))
+        .map(|normalized| {
+            if options.stem {
+                ENGLISH_STEMMER.stem(&normalized).into_owned()
+            } else {
+                normalized
+            }
+        })
+        .collect()
+}
+
+fn token_counts(text: &str, options: &TokenOptions) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for token in token_sequence(text, options) {
+        *counts.entry(token).or_default() += 1;
+    }
+    counts
+}
+
+/// Adjacent token pairs keyed as `"first second"`.
+fn bigram_counts(sequence: &[String]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for pair in sequence.windows(2) {
+        *counts
+            .entry(format!("{} {}", pair[0], pair[1]))
+            .or_default() += 1;
+    }
+    counts
+}
+
+/// Bonus for each consecutive query token pair that also appears adjacently
+/// in the node. Nodes indexed without `--positions` never receive it.
+fn proximity_bonus(query_bigrams: &BTreeMap<String, usize>, node: &Node) -> f64 {
+    if node.bigrams.is_empty() {
+        return 0.0;
+    }
+    query_bigrams
+        .keys()
+        .filter(|bigram| node.bigrams.contains_key(*bigram))
+        .count() as f64
+}
+
+pub fn tokens(text: &str, options: &TokenOptions) -> HashSet<String> {
+    token_counts(text, options).into_keys().collect()
+}
+
+fn lexical_overlap_score(
+    query_tokens: &HashSet<String>,
+    text: &str,
+    options: &TokenOptions,
+) -> f64 {
+    let node_tokens = tokens(text, options);
+    (query_tokens.intersection(&node_tokens).count() * 2) as f64
+}
+
+/// Set similarity in [0, 1] between the query tokens and the note's whole
+/// vocabulary, so a note is not rewarded just for containing more words.
+fn vocabulary_similarity(
+    query_tokens: &HashSet<String>,
+    term_counts: &BTreeMap<String, usize>,
+    mode: LexicalMode,
+) -> f64 {
+    let shared = query_tokens
+        .iter()
+        .filter(|token| term_counts.contains_key(*token))
+        .count() as f64;
+    if shared == 0.0 {
+        return 0.0;
+    }
+    let (query_len, note_len) = (query_tokens.len() as f64, term_counts.len() as f64);
+    match mode {
+        LexicalMode::Dice => 2.0 * shared / (query_len + note_len),
+        _ => shared / (query_len + note_len - shared),
+    }
+}
+
+/// Shortest query token `--fuzzy` will try to correct; shorter words have
+/// too many neighbours within a couple of edits.
+const FUZZY_MIN_TOKEN_LEN: usize = 4;
+
+/// Partial credit for query tokens missing from the note that are a few edits
+/// away from a title token. Only titles are fuzzed, to keep recall cheap.
+fn fuzzy_title_score(
+    query_tokens: &HashSet<String>,
+    node: &Node,
+    options: &TokenOptions,
+    max_distance: usize,
+) -> f64 {
+    let title_tokens = tokens(&node.title, options);
+    query_tokens
+        .iter()
+        .filter(|token| token.chars().count() >= FUZZY_MIN_TOKEN_LEN)
+        .filter(|token| !title_tokens.contains(*token) && !node.term_counts.contains_key(*token))
+        .filter_map(|token| {
+            title_tokens
+                .iter()
+                .map(|candidate| {
+                    let distance = strsim::levenshtein(token, candidate);
+                    let longest = token.chars().count().max(candidate.chars().count());
+                    (distance, longest)
+                })
+                .filter(|(distance, _)| *distance <= max_distance)
+                .min_by_key(|(distance, _)| *distance)
+        })
+        // Same scale as an exact overlap hit (2.0), discounted per edit.
+        .map(|(distance, longest)| 2.0 * (1.0 - distance as f64 / longest as f64))
+        .sum()
+}
+
+fn bm25_score(
+    query_tokens: &HashSet<String>,
+    term_counts: &BTreeMap<String, usize>,
+    stats: &Bm25Stats,
+    options: &RecallOptions,
+) -> f64 {
+    if term_counts.is_empty() || stats.avg_doc_len <= 0.0 {
+        return 0.0;
+    }
+    let doc_len = term_counts.values().sum::<usize>() as f64;
+    let total_docs = stats.total_docs as f64;
+    let length_norm = 1.0 - options.bm25_b + options.bm25_b * doc_len / stats.avg_doc_len;
+    query_tokens
+        .iter()
+        .filter_map(|token| {
+            let tf = *term_counts.get(token)? as f64;
+            let df = stats.doc_freq.get(token).copied().unwrap_or(0) as f64;
+            let idf = ((total_docs - df + 0.5) / (df + 0.5) + 1.0).ln();
+            Some(idf * tf * (options.bm25_k1 + 1.0) / (tf + options.bm25_k1 * length_norm))
+        })
+        .sum()
+}
+
+fn graph_influence(indegree: f64) -> f64 {
+    indegree.min(10.0) * 0.1
+}
+
+/// Captured `REL:` relations: every edge kind except wikilinks, inferred
+/// similarity edges and section containment.
+fn is_typed_relation(kind: &str) -> bool {
+    !matches!(
+        kind,
+        "WIKILINK" | "UNRESOLVED_LINK" | "INFERRED_RELATED" | "CONTAINS"
+    )
+}
+
+/// Note-to-section containment describes layout, not a citation, so it is
+/// left out of graph scoring.
+fn is_structural(kind: &str) -> bool {
+    kind == "CONTAINS"
+}
+
+/// Per-kind edge factors for graph scoring. Kinds without an explicit
+/// factor count one, except typed relations, which count `relation`.
+#[derive(Clone, PartialEq)]
+pub struct EdgeWeights {
+    relation: f64,
+    kinds: BTreeMap<String, f64>,
+}
+
+impl Default for EdgeWeights {
+    fn default() -> Self {
+        EdgeWeights {
+            relation: 1.0,
+            kinds: BTreeMap::new(),
+        }
+    }
+}
+
+impl EdgeWeights {
+    /// Parses repeated `KIND=FACTOR` overrides on top of the relation factor.
+    pub fn parse(relation: f64, overrides: &[String]) -> ExomResult<Self> {
+        let mut kinds = BTreeMap::new();
+        for raw in overrides {
+            let (kind, factor) = raw
+                .split_once('=')
+                .filter(|(kind, _)| !kind.trim().is_empty())
+                .ok_or_else(|| {
+                    ExomError::invalid(format!(
+                        "invalid --edge-weight {:?}; expected KIND=FACTOR",
+                        raw
+                    ))
+                })?;
+            let factor: f64 = factor.trim().parse().map_err(|_| {
+                ExomError::invalid(format!(
+                    "invalid --edge-weight {:?}; factor must be a number",
+                    raw
+                ))
+            })?;
+            kinds.insert(kind.trim().to_string(), factor);
+        }
+        Ok(EdgeWeights { relation, kinds })
+    }
+
+    fn factor(&self, kind: &str) -> f64 {
+        match self.kinds.get(kind) {
+            Some(factor) => *factor,
+            None if is_typed_relation(kind) => self.relation,
+            None => 1.0,
+        }
+    }
+}
+
+/// Indegree summed over per-kind edge factors times each edge's weight.
+fn weighted_indegree<'a>(graph: &'a GraphData, weights: &EdgeWeights) -> HashMap<&'a str, f64> {
+    let mut indegree: HashMap<&str, f64> = HashMap::new();
+    for edge in graph.edges.iter().filter(|edge| !is_structural(&edge.kind)) {
+        *indegree.entry(edge.dst.as_str()).or_default() +=
+            weights.factor(&edge.kind) * edge.multiplicity();
+    }
+    indegree
+}
+
+/// PageRank by power iteration; ranks sum to one. Each edge carries its
+/// `EdgeWeights` factor (times its weight) of a wikilink's share, and rank from notes without
+/// outgoing links is spread evenly. Unresolved links are ignored unless
+/// `include_ghosts` is set.
+fn pagerank<'a>(
+    graph: &'a GraphData,
+    weights: &EdgeWeights,
+    include_ghosts: bool,
+) -> HashMap<&'a str, f64> {
+    let count = graph.nodes.len();
+    if count == 0 {
+        return HashMap::new();
+    }
+    let index: HashMap<&str, usize> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(idx, node)| (node.id.as_str(), idx))
+        .collect();
+    let mut outgoing: Vec<Vec<(usize, f64)>> = vec![Vec::new(); count];
+    for edge in &graph.edges {
+        if is_structural(&edge.kind) || (edge.kind == "UNRESOLVED_LINK" && !include_ghosts) {
+            continue;
+        }
+        let (Some(&src), Some(&dst)) = (index.get(edge.src.as_str()), index.get(edge.dst.as_str()))
+        else {
+            continue;
+        };
+        let factor = weights.factor(&edge.kind) * edge.multiplicity();
+        if factor > 0.0 {
+            outgoing[src].push((dst, factor));
+        }
+    }
+    let totals: Vec<f64> = outgoing
+        .iter()
+        .map(|links| links.iter().map(|(_, factor)| factor).sum())
+        .collect();
+
+    let base = 1.0 / count as f64;
+    let mut ranks = vec![base; count];
+    for _ in 0..PAGERANK_ITERATIONS {
+        let dangling: f64 = (0..count)
+            .filter(|&idx| totals[idx] <= 0.0)
+            .map(|idx| ranks[idx])
+            .sum();
+        let mut next = vec![(1.0 - PAGERANK_DAMPING + PAGERANK_DAMPING * dangling) * base; count];
+        for (src, links) in outgoing.iter().enumerate() {
+            if totals[src] <= 0.0 {
+                continue;
+            }
+            for &(dst, factor) in links {
+                next[dst] += PAGERANK_DAMPING * ranks[src] * factor / totals[src];
+            }
+        }
+        ranks = next;
+    }
+    graph
+        .nodes
+        .iter()
+        .zip(ranks)
+        .map(|(node, rank)| (node.id.as_str(), rank))
+        .collect()
+}
+
+fn semantic_score(query_vector: &BTreeMap<String, f64>, vector: &BTreeMap<String, f64>) -> f64 {
+    query_vector
+        .iter()
+        .map(|(token, weight)| vector.get(token).copied().unwrap_or(0.0) * weight)
+        .sum()
+}
+
+/// Inverse document frequency used for the stored TF-IDF vectors.
+fn tfidf_idf(total_docs: usize, df: usize) -> f64 {
+    ((total_docs as f64 + 1.0) / (df as f64 + 1.0)).ln() + 1.0
+}
+
+fn vector_norm(vector: &BTreeMap<String, f64>) -> f64 {
+    vector.values().map(|w| w * w).sum::<f64>().sqrt()
+}
+
+/// Weights query token counts with the graph's persisted document frequencies
+/// so the query lives in the same TF-IDF space as the node vectors. Graphs
+/// without `doc_freq` degrade to raw counts (every IDF is 1.0).
+fn query_tfidf(
+    query_counts: &HashMap<String, usize>,
+    indexes: &GraphIndexes,
+) -> BTreeMap<String, f64> {
+    query_counts
+        .iter()
+        .map(|(token, count)| (token.clone(), *count as f64 * indexes.idf(token)))
+        .collect()
+}
+
+/// Stored L2 norm of a node's vector, recomputed for graphs that predate it.
+fn node_norm(node: &Node) -> f64 {
+    if node.semantic_norm > 0.0 {
+        node.semantic_norm
+    } else {
+        vector_norm(&node.semantic)
+    }
+}
+
+fn sparse_cosine(
+    a: &BTreeMap<String, f64>,
+    a_norm: f64,
+    b: &BTreeMap<String, f64>,
+    b_norm: f64,
+) -> f64 {
+    if a_norm <= 0.0 || b_norm <= 0.0 {
+        return 0.0;
+    }
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let dot: f64 = small
+        .iter()
+        .map(|(token, weight)| large.get(token).copied().unwrap_or(0.0) * weight)
+        .sum();
+    dot / (a_norm * b_norm)
+}
+
+#[derive(Deserialize)]
+struct EmbeddingLine {
+    id: String,
+    #[serde(alias = "embedding")]
+    vector: Vec<f32>,
+}
+
+/// Note vectors from a JSONL file of `{"id": ..., "vector": [...]}` lines.
+/// Every vector must have the same dimension.
+pub fn load_embeddings(path: &Path) -> ExomResult<HashMap<String, Vec<f32>>> {
+    let content = fs::read_to_string(path).map_err(io_at(path))?;
+    let mut vectors = HashMap::new();
+    let mut dimension = None;
+    for (idx, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parsed: EmbeddingLine =
+            serde_json::from_str(line).map_err(|source| ExomError::Parse {
+                path: path.to_path_buf(),
+                line: Some(idx + 1),
+                source,
+            })?;
+        let expected = *dimension.get_or_insert(parsed.vector.len());
+        if parsed.vector.len() != expected {
+            return Err(ExomError::invalid(format!(
+                "{}:{}: embedding for {} has {} dimensions, expected {}",
+                path.display(),
+                idx + 1,
+                parsed.id,
+                parsed.vector.len(),
+                expected
+            )));
+        }
+        vectors.insert(parsed.id, parsed.vector);
+    }
+    Ok(vectors)
+}
+
+/// A query vector: a bare JSON array or an object with a `vector` field.
+pub fn load_query_embedding(path: &Path) -> ExomResult<Vec<f32>> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum QueryEmbedding {
+        Bare(Vec<f32>),
+        Wrapped {
+            #[serde(alias = "embedding")]
+            vector: Vec<f32>,
+        },
+    }
+
+    let content = fs::read_to_string(path).map_err(io_at(path))?;
+    let parsed: QueryEmbedding = serde_json::from_str(&content).map_err(json_at(path))?;
+    Ok(match parsed {
+        QueryEmbedding::Bare(vector) | QueryEmbedding::Wrapped { vector } => vector,
+    })
+}
+
+/// Fails unless the graph carries note embeddings of the query's dimension.
+pub fn check_embedding_dimension(graph: &GraphData, query: &[f32]) -> ExomResult<()> {
+    let Some(sample) = graph.nodes.iter().find_map(|node| node.embedding.as_ref()) else {
+        return Err(ExomError::invalid(
+            "graph has no note embeddings; re-index with `--embeddings`",
+        ));
+    };
+    if sample.len() != query.len() {
+        return Err(ExomError::invalid(format!(
+            "query embedding has {} dimensions but the graph's note embeddings have {}",
+            query.len(),
+            sample.len()
+        )));
+    }
+    Ok(())
+}
+
+fn embedding_cosine(a: &[f32], b: &[f32]) -> f64 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        let (x, y) = (f64::from(*x), f64::from(*y));
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a > 0.0 && norm_b > 0.0 {
+        dot / (norm_a.sqrt() * norm_b.sqrt())
+    } else {
+        0.0
+    }
+}
+
+fn cosine_score(query_vector: &BTreeMap<String, f64>, node: &Node) -> f64 {
+    sparse_cosine(
+        query_vector,
+        vector_norm(query_vector),
+        &node.semantic,
+        node_norm(node),
+    )
+}
+
+/// The title vector's share of the semantic score, scaled like the full
+/// score in `mode` so `--title-boost` adds to it in the same units.
+fn title_score(query_vector: &BTreeMap<String, f64>, node: &Node, mode: SemanticMode) -> f64 {
+    let dot = semantic_score(query_vector, &node.semantic_title);
+    match mode {
+        SemanticMode::Dot => dot,
+        SemanticMode::Cosine => {
+            let norms = vector_norm(query_vector) * node_norm(node);
+            if norms > 0.0 {
+                dot / norms
+            } else {
+                0.0
+            }
+        }
+    }
+}
+
+fn node_similarity(a: &Node, b: &Node) -> f64 {
+    sparse_cosine(&a.semantic, node_norm(a), &b.semantic, node_norm(b))
+}
+
+/// Hop counts from `start` over non-structural edges in either direction.
+fn hop_distances<'a>(graph: &'a GraphData, start: &'a str) -> HashMap<&'a str, usize> {
+    let mut adjacency: HashMap<&str, Vec<&str>> = HashMap::new();
+    for edge in graph.edges.iter().filter(|edge| !is_structural(&edge.kind)) {
+        adjacency.entry(&edge.src).or_default().push(&edge.dst);
+        adjacency.entry(&edge.dst).or_default().push(&edge.src);
+    }
+    let mut distances = HashMap::from([(start, 0)]);
+    let mut queue = std::collections::VecDeque::from([start]);
+    while let Some(id) = queue.pop_front() {
+        let next = distances[id] + 1;
+        for neighbour in adjacency.get(id).into_iter().flatten() {
+            if !distances.contains_key(neighbour) {
+                distances.insert(neighbour, next);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+    distances
+}
+
+/// Other real notes ranked by `graph * (1 / hops) + semantic * cosine`
+/// against `target`; the lexical weight is unused.
+pub fn related_notes(
+    graph: &GraphData,
+    target: &Node,
+    topk: usize,
+    weights: &RecallWeights,
+) -> Vec<RecallRow> {
+    let distances = hop_distances(graph, &target.id);
+    // Prefer the label a neighbour used for the target, then the reverse.
+    let mut aliases: HashMap<&str, &str> = HashMap::new();
+    for edge in &graph.edges {
+        let Some(alias) = edge.alias.as_deref() else {
+            continue;
+        };
+        if edge.dst == target.id {
+            aliases.insert(edge.src.as_str(), alias);
+        } else if edge.src == target.id {
+            aliases.entry(edge.dst.as_str()).or_insert(alias);
+        }
+    }
+    let mut scored: Vec<RecallRow> = graph
+        .nodes
+        .iter()
+        .filter(|node| node.id != target.id && node.path.is_some())
+        .filter_map(|node| {
+            let proximity = distances
+                .get(node.id.as_str())
+                .map_or(0.0, |hops| 1.0 / *hops as f64);
+            let score =
+                weights.graph * proximity + weights.semantic * node_similarity(target, node);
+            (score > 0.0).then(|| RecallRow {
+                id: node.id.clone(),
+                rank: 0,
+                score,
+                title: node.title.clone(),
+                path: node.path.clone(),
+                snippet: None,
+                reading_minutes: None,
+                alias: aliases.get(node.id.as_str()).map(|alias| alias.to_string()),
+                explain: None,
+            })
+        })
+        .collect();
+    scored.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    scored.truncate(topk);
+    for (idx, row) in scored.iter_mut().enumerate() {
+        row.rank = idx + 1;
+    }
+    scored
+}
+
+/// Called with `(done, total)` as a long run advances; the caller decides
+/// how to show it. Index scans call it from worker threads.
+pub type ProgressFn = dyn Fn(usize, usize) + Send + Sync;
+
+pub fn run_benchmark(
+    graph: &LoadedGraph,
+    dataset: &[BenchmarkQuery],
+    topk: usize,
+    progress: Option<&(dyn Fn(usize, usize) + Send + Sync)>,
+) -> ExomResult<BenchmarkReport> {
+    let mut queries = Vec::with_capacity(dataset.len());
+    let summary = stream_benchmark(graph, dataset, topk, |query| {
+        queries.push(query);
+        if let Some(progress) = progress {
+            progress(queries.len(), dataset.len());
+        }
+        Ok(())
+    })?;
+    Ok(BenchmarkReport { summary, queries })
+}
+
+/// Resolves expected paths and titles to node ids, so a note listed by both
+/// counts once. Entries naming no node keep their own key and stay unfound.
+fn expected_node_ids(graph: &GraphData, expected: &HashSet<String>) -> HashSet<String> {
+    expected
+        .iter()
+        .map(|key| {
+            graph
+                .nodes
+                .iter()
+                .find(|node| node.path.as_deref() == Some(key.as_str()) || node.title == *key)
+                .map(|node| node.id.clone())
+                .unwrap_or_else(|| key.clone())
+        })
+        .collect()
+}
+
+/// Runs every dataset query, handing each `QuerySummary` to `emit` as soon as
+/// it is scored so callers can stream results instead of buffering them.
+pub fn stream_benchmark<F>(
+    graph: &LoadedGraph,
+    dataset: &[BenchmarkQuery],
+    topk: usize,
+    mut emit: F,
+) -> ExomResult<BenchmarkSummary>
+where
+    F: FnMut(QuerySummary) -> ExomResult<()>,
+{
+    let weights = RecallWeights {
+        lexical: 1.0,
+        graph: 1.0,
+        semantic: 1.0,
+    };
+    let mut total_latency = 0.0;
+    let mut hit1 = 0;
+    let mut hit3 = 0;
+    let mut hit5 = 0;
+    let mut rr_sum = 0.0;
+    let mut ndcg_sum = 0.0;
+    let mut precision_sum = 0.0;
+    let mut recall_sum = 0.0;
+
+    for entry in dataset {
+        let expected: HashSet<String> = entry.expected.iter().cloned().collect();
+        let start = Instant::now();
+        let rows = graph.recall(&entry.query, topk, &weights, &RecallOptions::default());
+        let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+        total_latency += latency_ms;
+        let mut hit_rank = None;
+        let mut hit_path = None;
+        for (idx, row) in rows.iter().enumerate() {
+            let matched = row
+                .path
+                .as_deref()
+                .map(|p| expected.contains(p))
+                .unwrap_or(false)
+                || expected.contains(&row.title);
+            if matched {
+                hit_rank = Some(idx + 1);
+                hit_path = row.path.clone().or_else(|| Some(row.title.clone()));
+                break;
+            }
+        }
+        let expected_ids = expected_node_ids(&graph.graph, &expected);
+        let found: HashSet<&str> = rows
+            .iter()
+            .map(|row| row.id.as_str())
+            .filter(|id| expected_ids.contains(*id))
+            .collect();
+        let precision = if topk > 0 {
+            found.len() as f64 / topk as f64
+        } else {
+            0.0
+        };
+        let recall = if expected_ids.is_empty() {
+            0.0
+        } else {
+            found.len() as f64 / expected_ids.len() as f64
+        };
+        precision_sum += precision;
+        recall_sum += recall;
+        let reciprocal_rank = hit_rank.map(|rank| 1.0 / rank as f64).unwrap_or(0.0);
+        let gains: Vec<f64> = rows.iter().map(|row| entry.gain(row)).collect();
+        let ndcg = ndcg_at_k(&gains, &entry.ideal_gains(), topk);
+        rr_sum += reciprocal_rank;
+        ndcg_sum += ndcg;
+        if let Some(rank) = hit_rank {
+            if rank <= 1 {
+                hit1 += 1;
+            }
+            if rank <= 3 {
+                hit3 += 1;
+            }
+            if rank <= 5 {
+                hit5 += 1;
+            }
+        }
+        emit(QuerySummary {
+            query: entry.query.clone(),
+            hit_rank,
+            hit_path,
+            reciprocal_rank,
+            ndcg,
+            precision,
+            recall,
+            latency_ms,
+        })?;
+    }
+
+    let total = dataset.len() as f64;
+    let summary = BenchmarkSummary {
+        hit_at_1: if total > 0.0 {
+            hit1 as f64 / total
+        } else {
+            0.0
+        },
+        hit_at_3: if total > 0.0 {
+            hit3 as f64 / total
+        } else {
+            0.0
+        },
+        hit_at_5: if total > 0.0 {
+            hit5 as f64 / total
+        } else {
+            0.0
+        },
+        mrr: if total > 0.0 { rr_sum / total } else { 0.0 },
+        ndcg: if total > 0.0 { ndcg_sum / total } else { 0.0 },
+        precision_at_k: if total > 0.0 {
+            precision_sum / total
+        } else {
+            0.0
+        },
+        recall_at_k: if total > 0.0 { recall_sum / total } else { 0.0 },
+        top_k: topk,
+        avg_latency_ms: if total > 0.0 {
+            total_latency / total
+        } else {
+            0.0
+        },
+    };
+    Ok(summary)
+}
+
+pub fn render_benchmark_markdown(
+    report: &BenchmarkReport,
+    graph_path: &Path,
+    generated_at: DateTime<Utc>,
+) -> String {
+    let summary = &report.summary;
+    let mut out = String::new();
+    out.push_str("# Recall benchmark\n\n");
+    out.push_str(&format!(
+        "- Generated: {}\n- Graph: `{}`\n- Top-k: {}\n- Queries: {}\n\n",
+        generated_at.format("%Y-%m-%d %H:%M:%S UTC"),
+        graph_path.display(),
+        summary.top_k,
+        report.queries.len()
+    ));
+    out.push_str("## Summary\n\n");
+    out.push_str("| hit@1 | hit@3 | hit@5 | MRR | NDCG | avg latency (ms) |\n");
+    out.push_str("|---|---|---|---|---|---|\n");
+    out.push_str(&format!(
+        "| {:.3} | {:.3} | {:.3} | {:.3} | {:.3} | {:.3} |\n\n",
+        summary.hit_at_1,
+        summary.hit_at_3,
+        summary.hit_at_5,
+        summary.mrr,
+        summary.ndcg,
+        summary.avg_latency_ms
+    ));
+    out.push_str("## Queries\n\n");
+    out.push_str("| # | Query | Result | Rank | Target |\n");
+    out.push_str("|---|---|---|---|---|\n");
+    for (idx, query) in report.queries.iter().enumerate() {
+        let (result, rank) = match query.hit_rank {
+            Some(rank) => ("hit", rank.to_string()),
+            None => ("miss", "-".to_string()),
+        };
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} |\n",
+            idx + 1,
+            query.query.replace('|', "\\|"),
+            result,
+            rank,
+            query.hit_path.as_deref().unwrap_or("-").replace('|', "\\|")
+        ));
+    }
+    out
+}
+
+pub fn load_benchmark_report(path: &Path) -> ExomResult<BenchmarkReport> {
+    let data = fs::read_to_string(path).map_err(io_at(path))?;
+    serde_json::from_str(&data).map_err(json_at(path))
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RankChange {
+    Regressed,
+    Improved,
+    Unchanged,
+}
+
+impl fmt::Display for RankChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            RankChange::Regressed => "REGRESSED",
+            RankChange::Improved => "IMPROVED",
+            RankChange::Unchanged => "UNCHANGED",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Serialize)]
+pub struct MetricDelta {
+    pub name: &'static str,
+    pub old: f64,
+    pub new: f64,
+    pub delta: f64,
+}
+
+#[derive(Serialize)]
+pub struct QueryDelta {
+    pub query: String,
+    pub old_rank: Option<usize>,
+    pub new_rank: Option<usize>,
+    pub change: RankChange,
+}
+
+#[derive(Serialize)]
+pub struct BenchmarkComparison {
+    pub metrics: Vec<MetricDelta>,
+    /// Queries in both reports: regressions first, then improvements.
+    pub queries: Vec<QueryDelta>,
+    pub only_old: Vec<String>,
+    pub only_new: Vec<String>,
+}
+
+/// Aligns two reports by query text. A miss ranks below every hit.
+pub fn compare_benchmarks(old: &BenchmarkReport, new: &BenchmarkReport) -> BenchmarkComparison {
+    let metric = |name, pick: fn(&BenchmarkSummary) -> f64| MetricDelta {
+        name,
+        old: pick(&old.summary),
+        new: pick(&new.summary),
+        delta: pick(&new.summary) - pick(&old.summary),
+    };
+    let metrics = vec![
+        metric("hit@1", |s| s.hit_at_1),
+        metric("hit@3", |s| s.hit_at_3),
+        metric("hit@5", |s| s.hit_at_5),
+        metric("mrr", |s| s.mrr),
+        metric("ndcg", |s| s.ndcg),
+        metric("precision@k", |s| s.precision_at_k),
+        metric("recall@k", |s| s.recall_at_k),
+        metric("latency_ms", |s| s.avg_latency_ms),
+    ];
+
+    let new_ranks: HashMap<&str, Option<usize>> = new
+        .queries
+        .iter()
+        .map(|query| (query.query.as_str(), query.hit_rank))
+        .collect();
+    let old_queries: HashSet<&str> = old.queries.iter().map(|q| q.query.as_str()).collect();
+    let mut queries = Vec::new();
+    let mut only_old = Vec::new();
+    for query in &old.queries {
+        let Some(&new_rank) = new_ranks.get(query.query.as_str()) else {
+            only_old.push(query.query.clone());
+            continue;
+        };
+        let position = |rank: Option<usize>| rank.unwrap_or(usize::MAX);
+        let change = match position(new_rank).cmp(&position(query.hit_rank)) {
+            std::cmp::Ordering::Less => RankChange::Improved,
+            std::cmp::Ordering::Greater => RankChange::Regressed,
+            std::cmp::Ordering::Equal => RankChange::Unchanged,
+        };
+        queries.push(QueryDelta {
+            query: query.query.clone(),
+            old_rank: query.hit_rank,
+            new_rank,
+            change,
+        });
+    }
+    // Stable sort keeps dataset order within each group.
+    queries.sort_by_key(|query| match query.change {
+        RankChange::Regressed => 0,
+        RankChange::Improved => 1,
+        RankChange::Unchanged => 2,
+    });
+    let only_new = new
+        .queries
+        .iter()
+        .filter(|query| !old_queries.contains(query.query.as_str()))
+        .map(|query| query.query.clone())
+        .collect();
+    BenchmarkComparison {
+        metrics,
+        queries,
+        only_old,
+        only_new,
+    }
+}
+
+fn dcg(gains: &[f64]) -> f64 {
+    gains
+        .iter()
+        .enumerate()
+        .map(|(idx, gain)| gain / ((idx + 2) as f64).log2())
+        .sum()
+}
+
+/// NDCG over the first `k` results; `ideal` must be sorted descending.
+fn ndcg_at_k(gains: &[f64], ideal: &[f64], k: usize) -> f64 {
+    let ideal_dcg = dcg(&ideal[..ideal.len().min(k)]);
+    if ideal_dcg <= 0.0 {
+        return 0.0;
+    }
+    dcg(&gains[..gains.len().min(k)]) / ideal_dcg
+}
+
+#[derive(Serialize)]
+pub struct SearchHit {
+    pub id: String,
+    pub line: usize,
+    pub text: String,
+    #[serde(skip)]
+    pub before: Vec<(usize, String)>,
+    #[serde(skip)]
+    pub after: Vec<(usize, String)>,
+}
+
+pub fn build_search_regex(pattern: &str, regex: bool, ignore_case: bool) -> ExomResult<Regex> {
+    let source = if regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+    regex::RegexBuilder::new(&source)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|source| ExomError::Regex {
+            pattern: pattern.to_string(),
+            source,
+        })
+}
+
+pub fn search_notes(
+    notes_root: &Path,
+    matcher: &Regex,
+    context: usize,
+    since: Option<NaiveDate>,
+    exclude: &GlobSet,
+    extensions: &NoteExtensions,
+) -> ExomResult<Vec<SearchHit>> {
+    let mut notes = collect_notes(notes_root, exclude, None, extensions)?.notes;
+    notes.sort();
+    let mut hits = Vec::new();
+    for note in &notes {
+        if let Some(cutoff) = since {
+            let modified = fs::metadata(note)
+                .map_err(io_at(note))?
+                .modified()
+                .ok()
+                .map(DateTime::<Utc>::from);
+            if modified.is_none_or(|time| time.date_naive() < cutoff) {
+                continue;
+            }
+        }
+        let id = relative_note_id(note, notes_root)?;
+        let content = fs::read_to_string(note).unwrap_or_default();
+        let lines: Vec<&str> = content.lines().collect();
+        for (idx, line) in lines.iter().enumerate() {
+            if !matcher.is_match(line) {
+                continue;
+            }
+            let start = idx.saturating_sub(context);
+            let end = (idx + context + 1).min(lines.len());
+            hits.push(SearchHit {
+                id: id.clone(),
+                line: idx + 1,
+                text: line.to_string(),
+                before: (start..idx)
+                    .map(|i| (i + 1, lines[i].to_string()))
+                    .collect(),
+                after: (idx + 1..end)
+                    .map(|i| (i + 1, lines[i].to_string()))
+                    .collect(),
+            });
+        }
+    }
+    Ok(hits)
+}
+
+#[derive(Serialize)]
+pub struct StatsReport {
+    pub nodes: usize,
+    pub real_nodes: usize,
+    pub ghost_nodes: usize,
+    pub edges: usize,
+    pub edges_by_kind: BTreeMap<String, usize>,
+    pub orphans: usize,
+    pub top_linked: Vec<LinkedNode>,
+    pub avg_vector_size: f64,
+    pub median_vector_size: f64,
+    pub total_words: usize,
+    pub avg_words: f64,
+}
+
+#[derive(Serialize)]
+pub struct LinkedNode {
+    pub id: String,
+    pub indegree: usize,
+}
+
+/// Note count per lowercased tag; a note repeating a tag counts once.
+pub fn tag_counts(graph: &GraphData) -> BTreeMap<String, usize> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for node in &graph.nodes {
+        let tags: BTreeSet<String> = node.tags.iter().map(|tag| tag.to_lowercase()).collect();
+        for tag in tags {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+    counts
+}
+
+pub fn notes_with_tag(graph: &GraphData, tag: &str) -> Vec<String> {
+    let wanted = tag.trim_start_matches('#').to_lowercase();
+    graph
+        .nodes
+        .iter()
+        .filter(|node| node.tags.iter().any(|t| t.to_lowercase() == wanted))
+        .map(|node| node.id.clone())
+        .collect()
+}
+
+#[derive(Serialize)]
+pub struct UnresolvedLink {
+    source: String,
+    target: String,
+}
+
+/// Every `UNRESOLVED_LINK` edge, with the ghost resolved back to the link
+/// text, ordered by target and then source.
+pub fn unresolved_links(graph: &GraphData) -> Vec<UnresolvedLink> {
+    let titles: HashMap<&str, &str> = graph
+        .nodes
+        .iter()
+        .map(|node| (node.id.as_str(), node.title.as_str()))
+        .collect();
+    let mut links: Vec<UnresolvedLink> = graph
+        .edges
+        .iter()
+        .filter(|edge| edge.kind == "UNRESOLVED_LINK")
+        .map(|edge| UnresolvedLink {
+            source: edge.src.clone(),
+            target: titles
+                .get(edge.dst.as_str())
+                .map(|title| title.to_string())
+                .unwrap_or_else(|| edge.dst.trim_start_matches("ghost/").to_string()),
+        })
+        .collect();
+    links.sort_by(|a, b| (&a.target, &a.source).cmp(&(&b.target, &b.source)));
+    links
+}
+
+/// Sources per missing target, most referenced target first.
+pub fn group_unresolved(links: &[UnresolvedLink]) -> Vec<(&str, Vec<&str>)> {
+    let mut groups: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for link in links {
+        groups.entry(&link.target).or_default().push(&link.source);
+    }
+    let mut groups: Vec<(&str, Vec<&str>)> = groups.into_iter().collect();
+    groups.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(b.0)));
+    groups
+}
+
+/// Writes `00_Inbox/<target>.md` holding just a title for each missing
+/// target, named after the last path segment so the link resolves on the
+/// next index. Existing files are left alone. Returns the created note ids.
+pub fn create_link_stubs(notes_root: &Path, links: &[UnresolvedLink]) -> ExomResult<Vec<String>> {
+    let inbox = notes_root.join(INBOX_DIR);
+    let mut created = Vec::new();
+    for (target, _) in group_unresolved(links) {
+        let Some(name) = Path::new(target).file_name() else {
+            continue;
+        };
+        let path = inbox.join(format!("{}.md", name.to_string_lossy()));
+        if path.exists() {
+            continue;
+        }
+        fs::create_dir_all(&inbox).map_err(io_at(&inbox))?;
+        fs::write(&path, format!("# {}\n", name.to_string_lossy())).map_err(io_at(&path))?;
+        created.push(relative_note_id(&path, notes_root)?);
+    }
+    Ok(created)
+}
+
+#[derive(Clone, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EdgeKey {
+    pub src: String,
+    pub dst: String,
+    pub kind: String,
+}
+
+#[derive(Serialize)]
+pub struct TitleChange {
+    pub id: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Serialize)]
+pub struct GraphDiff {
+    pub added_nodes: Vec<String>,
+    pub removed_nodes: Vec<String>,
+    pub added_edges: Vec<EdgeKey>,
+    pub removed_edges: Vec<EdgeKey>,
+    pub retitled: Vec<TitleChange>,
+}
+
+/// Set differences between two graphs: nodes by id, edges by
+/// `(src, dst, kind)`, and nodes present in both whose title changed.
+/// Every list is sorted.
+pub fn diff_graphs(old: &GraphData, new: &GraphData) -> GraphDiff {
+    let titles = |graph: &GraphData| -> BTreeMap<String, String> {
+        graph
+            .nodes
+            .iter()
+            .map(|node| (node.id.clone(), node.title.clone()))
+            .collect()
+    };
+    let edge_keys = |graph: &GraphData| -> BTreeSet<EdgeKey> {
+        graph
+            .edges
+            .iter()
+            .map(|edge| EdgeKey {
+                src: edge.src.clone(),
+                dst: edge.dst.clone(),
+                kind: edge.kind.clone(),
+            })
+            .collect()
+    };
+    let (old_nodes, new_nodes) = (titles(old), titles(new));
+    let (old_edges, new_edges) = (edge_keys(old), edge_keys(new));
+    GraphDiff {
+        added_nodes: new_nodes
+            .keys()
+            .filter(|id| !old_nodes.contains_key(*id))
+            .cloned()
+            .collect(),
+        removed_nodes: old_nodes
+            .keys()
+            .filter(|id| !new_nodes.contains_key(*id))
+            .cloned()
+            .collect(),
+        added_edges: new_edges.difference(&old_edges).cloned().collect(),
+        removed_edges: old_edges.difference(&new_edges).cloned().collect(),
+        retitled: old_nodes
+            .iter()
+            .filter_map(|(id, old_title)| {
+                let new_title = new_nodes.get(id)?;
+                (new_title != old_title).then(|| TitleChange {
+                    id: id.clone(),
+                    old: old_title.clone(),
+                    new: new_title.clone(),
+                })
+            })
+            .collect(),
+    }
+}
+
+#[derive(Serialize)]
+pub struct ComponentsReport {
+    pub components: usize,
+    pub largest: usize,
+    /// Component sizes, largest first.
+    pub sizes: Vec<usize>,
+    /// Nodes with no edge to any other counted node.
+    pub orphans: Vec<String>,
+}
+
+/// Union-find over the graph with edges taken as undirected. Edges whose
+/// endpoint is not a counted node (an excluded or edges-only ghost) are skipped.
+pub fn connected_components(graph: &GraphData, include_ghosts: bool) -> ComponentsReport {
+    let nodes: Vec<&Node> = graph
+        .nodes
+        .iter()
+        .filter(|node| include_ghosts || node.path.is_some())
+        .collect();
+    let index: HashMap<&str, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(idx, node)| (node.id.as_str(), idx))
+        .collect();
+    let mut parent: Vec<usize> = (0..nodes.len()).collect();
+    fn find(parent: &mut [usize], mut idx: usize) -> usize {
+        while parent[idx] != idx {
+            parent[idx] = parent[parent[idx]];
+            idx = parent[idx];
+        }
+        idx
+    }
+    for edge in &graph.edges {
+        let (Some(&src), Some(&dst)) = (index.get(edge.src.as_str()), index.get(edge.dst.as_str()))
+        else {
+            continue;
+        };
+        let (a, b) = (find(&mut parent, src), find(&mut parent, dst));
+        if a != b {
+            parent[a] = b;
+        }
+    }
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for idx in 0..nodes.len() {
+        let root = find(&mut parent, idx);
+        members.entry(root).or_default().push(idx);
+    }
+    let mut sizes: Vec<usize> = members.values().map(Vec::len).collect();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    let mut orphans: Vec<String> = members
+        .values()
+        .filter(|group| group.len() == 1)
+        .map(|group| nodes[group[0]].id.clone())
+        .collect();
+    orphans.sort();
+    ComponentsReport {
+        components: sizes.len(),
+        largest: sizes.first().copied().unwrap_or(0),
+        sizes,
+        orphans,
+    }
+}
+
+pub fn graph_stats(graph: &GraphData, top: usize) -> StatsReport {
+    let indegree = indegree_map(graph);
+    let mut connected: HashSet<&str> = HashSet::new();
+    let mut edges_by_kind: BTreeMap<String, usize> = BTreeMap::new();
+    for edge in &graph.edges {
+        connected.insert(edge.src.as_str());
+        connected.insert(edge.dst.as_str());
+        *edges_by_kind.entry(edge.kind.clone()).or_default() += 1;
+    }
+
+    let real: Vec<&Node> = graph.nodes.iter().filter(|n| n.path.is_some()).collect();
+    // Heading sections repeat their note's words, so word totals skip them.
+    let sections: HashSet<&str> = graph
+        .edges
+        .iter()
+        .filter(|edge| is_structural(&edge.kind))
+        .map(|edge| edge.dst.as_str())
+        .collect();
+    let orphans = real
+        .iter()
+        .filter(|n| !connected.contains(n.id.as_str()))
+        .count();
+
+    let mut top_linked: Vec<LinkedNode> = graph
+        .nodes
+        .iter()
+        .filter_map(|node| {
+            indegree.get(node.id.as_str()).map(|count| LinkedNode {
+                id: node.id.clone(),
+                indegree: *count,
+            })
+        })
+        .collect();
+    top_linked.sort_by(|a, b| b.indegree.cmp(&a.indegree).then_with(|| a.id.cmp(&b.id)));
+    top_linked.truncate(top);
+
+    let mut sizes: Vec<usize> = real.iter().map(|n| n.semantic.len()).collect();
+    sizes.sort_unstable();
+    let avg_vector_size = if sizes.is_empty() {
+        0.0
+    } else {
+        sizes.iter().sum::<usize>() as f64 / sizes.len() as f64
+    };
+    let median_vector_size = match sizes.len() {
+        0 => 0.0,
+        len if len % 2 == 1 => sizes[len / 2] as f64,
+        len => (sizes[len / 2 - 1] + sizes[len / 2]) as f64 / 2.0,
+    };
+
+    let notes: Vec<&&Node> = real
+        .iter()
+        .filter(|n| !sections.contains(n.id.as_str()))
+        .collect();
+    let total_words: usize = notes.iter().map(|n| n.word_count).sum();
+    let avg_words = if notes.is_empty() {
+        0.0
+    } else {
+        total_words as f64 / notes.len() as f64
+    };
+
+    StatsReport {
+        nodes: graph.nodes.len(),
+        real_nodes: real.len(),
+        ghost_nodes: graph.nodes.len() - real.len(),
+        edges: graph.edges.len(),
+        edges_by_kind,
+        orphans,
+        top_linked,
+        avg_vector_size,
+        median_vector_size,
+        total_words,
+        avg_words,
+    }
+}
+
+#[derive(Serialize)]
+pub struct DuplicatePair {
+    pub a: String,
+    pub b: String,
+    pub similarity: f64,
+}
+
+/// Pairs of real notes at or above `threshold`, most similar first; see
+/// `similar_pairs` for which pairs get compared.
+pub fn find_duplicates(graph: &GraphData, threshold: f64) -> Vec<DuplicatePair> {
+    let nodes: Vec<&Node> = graph
+        .nodes
+        .iter()
+        .filter(|n| n.path.is_some() && !n.semantic.is_empty())
+        .collect();
+    let mut pairs: Vec<DuplicatePair> = similar_pairs(&nodes, threshold)
+        .into_iter()
+        .map(|(a, b, similarity)| DuplicatePair {
+            a: nodes[a].id.clone(),
+            b: nodes[b].id.clone(),
+            similarity,
+        })
+        .collect();
+    pairs.sort_by(|x, y| {
+        y.similarity
+            .partial_cmp(&x.similarity)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| x.a.cmp(&y.a))
+            .then_with(|| x.b.cmp(&y.b))
+    });
+    pairs
+}
+
+/// Index pairs of `nodes` whose vector cosine reaches `threshold`. Small
+/// sets compare every pair; larger ones only pairs sharing at least one rare
+/// token, which keeps this far below quadratic on real vaults.
+fn similar_pairs(nodes: &[&Node], threshold: f64) -> Vec<(usize, usize, f64)> {
+    let similar = |(a, b): (usize, usize)| {
+        let similarity = node_similarity(nodes[a], nodes[b]);
+        (similarity >= threshold).then_some((a, b, similarity))
+    };
+    if nodes.len() <= SIMILAR_ALL_PAIRS_MAX {
+        return (0..nodes.len())
+            .flat_map(|a| (a + 1..nodes.len()).map(move |b| (a, b)))
+            .filter_map(similar)
+            .collect();
+    }
+    let mut postings: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (idx, node) in nodes.iter().enumerate() {
+        for token in node.semantic.keys() {
+            postings.entry(token.as_str()).or_default().push(idx);
+        }
+    }
+    let rare_cutoff = (nodes.len() / 10).max(2);
+    let mut candidates: HashSet<(usize, usize)> = HashSet::new();
+    for ids in postings.values().filter(|ids| ids.len() <= rare_cutoff) {
+        for (pos, a) in ids.iter().enumerate() {
+            for b in &ids[pos + 1..] {
+                candidates.insert((*a, *b));
+            }
+        }
+    }
+
+    candidates.into_iter().filter_map(similar).collect()
+}
+
+/// Bidirectional INFERRED_RELATED edges for similar note pairs, strongest
+/// first, skipping pairs where either note already has `top_n` neighbours.
+fn inferred_related_edges(
+    node_map: &BTreeMap<String, Node>,
+    options: &RelatedOptions,
+) -> Vec<Edge> {
+    let nodes: Vec<&Node> = node_map
+        .values()
+        .filter(|n| n.path.is_some() && !n.semantic.is_empty())
+        .collect();
+    let mut pairs = similar_pairs(&nodes, options.threshold);
+    pairs.sort_by(|x, y| {
+        y.2.partial_cmp(&x.2)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| (x.0, x.1).cmp(&(y.0, y.1)))
+    });
+    let mut neighbours = vec![0usize; nodes.len()];
+    let mut edges = Vec::new();
+    for (a, b, similarity) in pairs {
+        if neighbours[a] >= options.top_n || neighbours[b] >= options.top_n {
+            continue;
+        }
+        neighbours[a] += 1;
+        neighbours[b] += 1;
+        for (src, dst) in [(a, b), (b, a)] {
+            edges.push(Edge {
+                src: nodes[src].id.clone(),
+                dst: nodes[dst].id.clone(),
+                kind: "INFERRED_RELATED".into(),
+                confidence: Some(similarity),
+                ..Default::default()
+            });
+        }
+    }
+    edges
+}
+
+#[derive(Serialize)]
+pub struct DoctorReport {
+    pub ok: bool,
+    pub checks: Vec<CheckResult>,
+}
+
+#[derive(Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub ok: bool,
+    pub info: String,
+}
+
+pub struct DoctorOptions {
+    pub stem: bool,
+    pub exclude: GlobSet,
+    pub extensions: NoteExtensions,
+    /// Remediate failed checks where possible instead of only reporting them.
+    pub fix: bool,
+    /// Aged inbox notes tolerated by the `inbox_backlog` check.
+    pub max_inbox: usize,
+}
+
+impl Default for DoctorOptions {
+    fn default() -> Self {
+        DoctorOptions {
+            stem: false,
+            exclude: GlobSet::empty(),
+            extensions: NoteExtensions::default(),
+            fix: false,
+            max_inbox: DEFAULT_MAX_INBOX,
+        }
+    }
+}
+
+pub fn doctor_report(
+    notes_root: &Path,
+    graph_path: &Path,
+    options: &DoctorOptions,
+) -> DoctorReport {
+    let mut checks = Vec::new();
+    let missing_dirs: Vec<&str> = NOTE_DIRS
+        .iter()
+        .copied()
+        .filter(|dir| !notes_root.join(dir).is_dir())
+        .collect();
+    let mut dirs_ok = missing_dirs.is_empty();
+    let mut dirs_info = if dirs_ok {
+        "all present".to_string()
+    } else {
+        format!("missing {}", missing_dirs.join(", "))
+    };
+    if !dirs_ok && options.fix {
+        match init_workflow(notes_root) {
+            Ok(()) => {
+                dirs_ok = true;
+                dirs_info = format!("fixed: created {}", missing_dirs.join(", "));
+            }
+            Err(err) => dirs_info = format!("{}; fix failed: {:#}", dirs_info, err),
+        }
+    }
+
+    let notes_root_exists = notes_root.exists();
+    checks.push(CheckResult {
+        name: "notes_root_exists",
+        ok: notes_root_exists,
+        info: notes_root.display().to_string(),
+    });
+    checks.push(CheckResult {
+        name: "note_dirs",
+        ok: dirs_ok,
+        info: dirs_info,
+    });
+
+    let markdown_count = if notes_root_exists {
+        NOTE_DIRS
+            .iter()
+            .map(|dir| notes_root.join(dir))
+            .filter(|dir| dir.exists())
+            .map(|dir| {
+                WalkDir::new(dir)
+                    .into_iter()
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_type().is_file())
+                    .filter(|entry| options.extensions.matches(entry.path()))
+                    .count()
+            })
+            .sum()
+    } else {
+        0
+    };
+
+    checks.push(CheckResult {
+        name: "markdown_notes_detected",
+        ok: markdown_count > 0,
+        info: format!("count={}", markdown_count),
+    });
+
+    if notes_root_exists {
+        let mut stems = Vec::new();
+        let scan = collect_notes(notes_root, &options.exclude, None, &options.extensions)
+            .unwrap_or_default();
+        for note in scan.notes {
+            let id = relative_note_id(&note, notes_root).unwrap_or_default();
+            let stem = note
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let content = fs::read_to_string(&note).unwrap_or_default();
+            let aliases = split_frontmatter(&content)
+                .0
+                .map(|raw| parse_frontmatter(raw).aliases)
+                .unwrap_or_default();
+            stems.push((id, stem, aliases));
+        }
+        let collisions =
+            stem_collisions(&stem_index(stems.iter().map(|(id, stem, aliases)| {
+                (id.as_str(), stem.as_str(), aliases.as_slice())
+            })));
+        checks.push(CheckResult {
+            name: "stem_collisions",
+            ok: collisions.is_empty(),
+            info: if collisions.is_empty() {
+                "none".to_string()
+            } else {
+                collisions
+                    .iter()
+                    .map(|(key, ids)| format!("{}: {}", key, ids.join(", ")))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            },
+        });
+
+        let inbox = gather_inbox_notes(notes_root, &options.extensions).unwrap_or_default();
+        let now = SystemTime::now();
+        let aged = inbox
+            .iter()
+            .filter_map(|note| fs::metadata(note).and_then(|m| m.modified()).ok())
+            .filter(|modified| duration_since_days(now, *modified) >= DECAY_THRESHOLD_DAYS as f64)
+            .count();
+        checks.push(CheckResult {
+            name: "inbox_backlog",
+            ok: aged <= options.max_inbox,
+            info: format!("aged={} total={}", aged, inbox.len()),
+        });
+    }
+
+    let mut graph_exists = graph_path.exists();
+    let mut graph_info = graph_path.display().to_string();
+    if !graph_exists && options.fix && markdown_count > 0 {
+        match rebuild_missing_graph(notes_root, graph_path, options) {
+            Ok(result) => {
+                graph_exists = true;
+                graph_info = format!("{} (fixed: indexed {} notes)", graph_info, result.notes);
+            }
+            Err(err) => graph_info = format!("{}; fix failed: {:#}", graph_info, err),
+        }
+    }
+    checks.push(CheckResult {
+        name: "graph_exists",
+        ok: graph_exists,
+        info: graph_info,
+    });
+
+    match load_graph(graph_path) {
+        Ok(graph) => checks.push(CheckResult {
+            name: "graph_stemming",
+            ok: graph.stemmed == options.stem,
+            info: format!("graph={} expected={}", graph.stemmed, options.stem),
+        }),
+        Err(err) if graph_exists => checks.push(CheckResult {
+            name: "graph_schema",
+            ok: false,
+            info: format!("{:#}", err),
+        }),
+        Err(_) => {}
+    }
+
+    let ok = checks.iter().all(|c| c.ok);
+    DoctorReport { ok, checks }
+}
+
+/// Indexes into the graph's folder; only the default `graph.json` name can be
+/// produced, since the indexer always writes that file.
+fn rebuild_missing_graph(
+    notes_root: &Path,
+    graph_path: &Path,
+    options: &DoctorOptions,
+) -> ExomResult<IndexResult> {
+    let out_root = graph_path
+        .parent()
+        .ok_or_else(|| ExomError::invalid("graph path has no parent directory"))?;
+    if graph_path.file_name().and_then(|name| name.to_str()) != Some("graph.json") {
+        return Err(ExomError::invalid(
+            "the indexer only writes graph.json; run `exom index` manually",
+        ));
+    }
+    let index_options = IndexOptions {
+        tokens: TokenOptions {
+            stem: options.stem,
+            ..Default::default()
+        },
+        exclude: options.exclude.clone(),
+        extensions: options.extensions.clone(),
+        ..Default::default()
+    };
+    index_graph_data(notes_root, out_root, &index_options)
+}
+
+/// Warning text when `root` exists but holds none of the `NOTE_DIRS`, which
+/// usually means a typo'd path or a workspace that was never initialized.
+pub fn uninitialized_workspace_hint(root: &Path) -> Option<String> {
+    if !root.is_dir() || NOTE_DIRS.iter().any(|dir| root.join(dir).is_dir()) {
+        return None;
+    }
+    Some(format!(
+        "{} contains none of {}; the workspace may be uninitialized (try `exom init --path {}`)",
+        root.display(),
+        NOTE_DIRS.join(", "),
+        root.display()
+    ))
+}
+
+pub fn ensure_workflow_dirs(root: &Path) -> ExomResult<()> {
+    init_workflow(root)
+}
+
+/// Resolves capture text from `--input`, then `--file`, then piped stdin.
+/// Callers pass `stdin` only when it is not an interactive terminal.
+pub fn capture_input_text<R: Read>(
+    input: Option<String>,
+    file: Option<PathBuf>,
+    stdin: Option<R>,
+) -> ExomResult<String> {
+    if let Some(text) = input {
+        Ok(text)
+    } else if let Some(path) = file {
+        let normalized = normalize_path(path);
+        fs::read_to_string(&normalized).map_err(io_at(&normalized))
+    } else {
+        let mut text = String::new();
+        if let Some(mut reader) = stdin {
+            reader.read_to_string(&mut text).map_err(stream_error)?;
+        }
+        if text.trim().is_empty() {
+            return Err(ExomError::invalid(
+                "Either --input or --file is required for capture.",
+            ));
+        }
+        Ok(text)
+    }
+}
+
+pub fn resolve_capture_note(notes_root: &Path, out_note: Option<PathBuf>) -> PathBuf {
+    if let Some(path) = out_note {
+        if path.is_absolute() {
+            path
+        } else {
+            notes_root.join(path)
+        }
+    } else {
+        let date = Utc::now().date_naive();
+        notes_root
+            .join(INBOX_DIR)
+            .join(format!("{}-auto.md", date.format("%Y-%m-%d")))
+    }
+}
+
+pub fn run_capture(
+    notes_root: &Path,
+    target: &Path,
+    input_text: &str,
+    options: &CaptureOptions,
+) -> ExomResult<CaptureReport> {
+    let now = Utc::now();
+    let grammar = options.relation_regex.as_ref().unwrap_or(&RELATION_REGEX);
+    let mut parsed = parse_relations(input_text.trim_end(), grammar);
+    let mut normalized = BTreeSet::new();
+    let mut rejected = Vec::new();
+    if let Some(types) = &options.relation_types {
+        for relation in &mut parsed {
+            let folded = normalize_relation_type(&relation.rel_type);
+            if folded != relation.rel_type {
+                normalized.insert(TypeNormalization {
+                    original: std::mem::replace(&mut relation.rel_type, folded.clone()),
+                    normalized: folded,
+                });
+            }
+        }
+        if types.strict {
+            let (kept, dropped) = parsed
+                .into_iter()
+                .partition(|relation| types.allowed.contains(&relation.rel_type));
+            parsed = kept;
+            rejected = dropped;
+        }
+    }
+    let parsed_count = parsed.len();
+    let mut relations: Vec<TypedRelation> = parsed
+        .into_iter()
+        .filter(|relation| relation.confidence >= options.min_confidence)
+        .collect();
+    let filtered_count = parsed_count - relations.len();
+    let synthesized_count = if options.synthesize_inverse {
+        synthesize_inverses(&mut relations, &options.symmetric_types)
+    } else {
+        0
+    };
+    let yaml_block = build_relations_yaml(&relations)?;
+    let body = capture_body(input_text.trim_end(), grammar, &relations);
+    let body = body.as_str();
+    let entry = match &options.template {
+        Some(template) => {
+            render_capture_template(template, now, body, options.context.as_deref(), &yaml_block)?
+        }
+        None => {
+            let context = options
+                .context
+                .as_deref()
+                .map(|parent| format!("Context: [[{}]]\n\n", parent))
+                .unwrap_or_default();
+            format!(
+                "## Capture @{}\n\n{}{}\n\n```yaml\n{}\n```\n\n",
+                now.format("%Y-%m-%d %H:%M:%S UTC"),
+                context,
+                body,
+                yaml_block
+            )
+        }
+    };
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(io_at(parent))?;
+    }
+    let note_exists = target.exists();
+    (|| -> std::io::Result<()> {
+        let note_len = if note_exists {
+            fs::metadata(target)?.len()
+        } else {
+            0
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(target)?;
+        if note_exists && note_len > 0 {
+            writeln!(file)?;
+        }
+        if !note_exists {
+            writeln!(file, "# Auto capture\n")?;
+        }
+        file.write_all(entry.as_bytes())
+    })()
+    .map_err(io_at(target))?;
+
+    let relative =
+        relative_note_id(target, notes_root).unwrap_or_else(|_| target.display().to_string());
+    Ok(CaptureReport {
+        note: relative,
+        appended_at: now.to_rfc3339(),
+        relation_count: relations.len(),
+        filtered_count,
+        synthesized_count,
+        relations,
+        normalized: normalized.into_iter().collect(),
+        rejected,
+    })
+}
+
+/// The captured text with its relation syntax taken out, followed by the
+/// kept relations as `REL:` lines. The index reads relations from the body,
+/// so this keeps filtered, rejected, and unnormalized relations out of the
+/// graph and brings in synthesized inverses and custom-grammar relations.
+fn capture_body(text: &str, grammar: &Regex, relations: &[TypedRelation]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let stripped = grammar.replace_all(line, "");
+        if stripped != line && stripped.trim().is_empty() {
+            continue;
+        }
+        lines.push(stripped.trim_end().to_string());
+    }
+    lines.extend(relations.iter().map(|relation| {
+        format!(
+            "REL:{}({} -> {})[{}]",
+            relation.rel_type, relation.from, relation.to, relation.confidence
+        )
+    }));
+    lines.join("\n")
+}
+
+static TEMPLATE_PLACEHOLDER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*([A-Za-z_]+)\s*\}\}").unwrap());
+const TEMPLATE_PLACEHOLDERS: &[&str] = &["body", "timestamp", "date", "relations_yaml", "context"];
+
+/// Reads a capture template, rejecting unknown placeholders and templates
+/// that would drop the captured text.
+pub fn load_capture_template(path: &Path) -> ExomResult<String> {
+    let template = fs::read_to_string(path).map_err(io_at(path))?;
+    let names: Vec<&str> = TEMPLATE_PLACEHOLDER
+        .captures_iter(&template)
+        .filter_map(|caps| caps.get(1).map(|m| m.as_str()))
+        .collect();
+    if let Some(unknown) = names
+        .iter()
+        .find(|name| !TEMPLATE_PLACEHOLDERS.contains(name))
+    {
+        return Err(ExomError::invalid(format!(
+            "template {} uses unknown placeholder {{{{{}}}}}; expected one of {}",
+            path.display(),
+            unknown,
+            TEMPLATE_PLACEHOLDERS.join(", ")
+        )));
+    }
+    if !names.contains(&"body") {
+        return Err(ExomError::invalid(format!(
+            "template {} must contain {{{{body}}}}",
+            path.display()
+        )));
+    }
+    Ok(template)
+}
+
+/// Fills a capture template; `{{context}}` becomes `[[parent]]` or nothing.
+fn render_capture_template(
+    template: &str,
+    now: DateTime<Utc>,
+    body: &str,
+    context: Option<&str>,
+    relations_yaml: &str,
+) -> ExomResult<String> {
+    let has_context = TEMPLATE_PLACEHOLDER
+        .captures_iter(template)
+        .any(|caps| &caps[1] == "context");
+    if context.is_some() && !has_context {
+        return Err(ExomError::invalid(
+            "--context needs a {{context}} placeholder in the capture template",
+        ));
+    }
+    let mut entry = TEMPLATE_PLACEHOLDER
+        .replace_all(template, |caps: &regex::Captures| match &caps[1] {
+            "body" => body.to_string(),
+            "timestamp" => now.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            "date" => now.format("%Y-%m-%d").to_string(),
+            "relations_yaml" => relations_yaml.to_string(),
+            "context" => context
+                .map(|parent| format!("[[{}]]", parent))
+                .unwrap_or_default(),
+            _ => caps[0].to_string(),
+        })
+        .into_owned();
+    if !entry.ends_with('\n') {
+        entry.push('\n');
+    }
+    Ok(entry)
+}
+
+/// Link target for `capture --context`: the matching note's stem, so the link
+/// resolves on the next index. `None` when no note matches.
+pub fn resolve_capture_context(graph: &GraphData, context: &str) -> Option<String> {
+    resolve_node(graph, context)
+        .filter(|node| node.path.is_some())
+        .map(|node| node.stem.clone())
+}
+
+/// Appends `to -> from` for each symmetric relation lacking its reverse,
+/// keeping the original confidence. Returns how many were added.
+fn synthesize_inverses(relations: &mut Vec<TypedRelation>, symmetric_types: &[String]) -> usize {
+    let mut inverses = Vec::new();
+    for relation in relations.iter() {
+        let symmetric = symmetric_types
+            .iter()
+            .any(|kind| kind.eq_ignore_ascii_case(&relation.rel_type));
+        if !symmetric || relation.from == relation.to {
+            continue;
+        }
+        let inverse = TypedRelation {
+            rel_type: relation.rel_type.clone(),
+            from: relation.to.clone(),
+            to: relation.from.clone(),
+            confidence: relation.confidence,
+        };
+        let exists = relations.iter().chain(inverses.iter()).any(|known| {
+            known.rel_type == inverse.rel_type
+                && known.from == inverse.from
+                && known.to == inverse.to
+        });
+        if !exists {
+            inverses.push(inverse);
+        }
+    }
+    let added = inverses.len();
+    relations.extend(inverses);
+    added
+}
+
+fn build_relations_yaml(relations: &[TypedRelation]) -> ExomResult<String> {
+    #[derive(Serialize)]
+    struct Block<'a> {
+        relations: &'a [TypedRelation],
+    }
+
+    let raw = serde_yaml::to_string(&Block { relations })
+        .map_err(|err| ExomError::invalid(format!("cannot render relations: {}", err)))?;
+    let trimmed = raw.strip_prefix("---\n").unwrap_or(&raw);
+    Ok(trimmed.trim_end_matches('\n').to_string())
+}
+
+/// Extracts relations with a grammar regex exposing `from`, `to` and `type`
+/// groups. A missing `confidence` group counts as certain (1.0).
+fn parse_relations(text: &str, grammar: &Regex) -> Vec<TypedRelation> {
+    let mut relations = Vec::new();
+    for cap in grammar.captures_iter(text) {
+        let rel_type = cap
+            .name("type")
+            .map(|m| m.as_str().trim().replace(char::is_whitespace, "_"))
+            .unwrap_or_default();
+        let from = cap
+            .name("from")
+            .map(|m| m.as_str().trim())
+            .unwrap_or("")
+            .to_string();
+        let to = cap
+            .name("to")
+            .map(|m| m.as_str().trim())
+            .unwrap_or("")
+            .to_string();
+        let confidence = match cap.name("confidence") {
+            Some(m) => m.as_str().parse::<f64>().unwrap_or(0.0),
+            None => 1.0,
+        };
+        if rel_type.is_empty() || from.is_empty() || to.is_empty() {
+            continue;
+        }
+        relations.push(TypedRelation {
+            rel_type,
+            from,
+            to,
+            confidence,
+        });
+    }
+    relations
+}
+
+/// Compiles a custom relation pattern, or the named built-in grammar, and
+/// checks it exposes the groups `parse_relations` reads.
+pub fn relation_regex(pattern: Option<&str>, grammar: RelationGrammar) -> ExomResult<Regex> {
+    let source = pattern.unwrap_or(grammar.pattern());
+    let regex = Regex::new(source).map_err(|source_err| ExomError::Regex {
+        pattern: source.to_string(),
+        source: source_err,
+    })?;
+    let names: HashSet<&str> = regex.capture_names().flatten().collect();
+    let missing: Vec<&str> = ["from", "to", "type"]
+        .into_iter()
+        .filter(|name| !names.contains(name))
+        .collect();
+    if !missing.is_empty() {
+        return Err(ExomError::invalid(format!(
+            "relation pattern must define named groups (?P<from>..), (?P<to>..) and (?P<type>..); missing {}",
+            missing.join(", ")
+        )));
+    }
+    Ok(regex)
+}
+
+pub fn print_json<T: Serialize>(value: &T) -> ExomResult<()> {
+    write_json(&mut std::io::stdout().lock(), value)
+}
+
+pub fn write_json<W: Write, T: Serialize>(out: &mut W, value: &T) -> ExomResult<()> {
+    let text = serde_json::to_string_pretty(value).map_err(|err| stream_error(err.into()))?;
+    writeln!(out, "{}", text).map_err(stream_error)
+}
+
+/// Stdout, or the `--output` file with its parent folders created.
+pub fn output_writer(path: Option<&Path>) -> ExomResult<Box<dyn Write>> {
+    let Some(path) = path else {
+        return Ok(Box::new(std::io::stdout().lock()));
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_at(parent))?;
+    }
+    let file = fs::File::create(path).map_err(io_at(path))?;
+    Ok(Box::new(std::io::BufWriter::new(file)))
+}
+
+pub fn write_jsonl_line<W: Write, T: Serialize>(out: &mut W, value: &T) -> ExomResult<()> {
+    serde_json::to_writer(&mut *out, value).map_err(|err| stream_error(err.into()))?;
+    out.write_all(b"\n").map_err(stream_error)
+}
+
+pub fn write_jsonl<W: Write, T: Serialize>(out: &mut W, rows: &[T]) -> ExomResult<()> {
+    for row in rows {
+        write_jsonl_line(out, row)?;
+    }
+    Ok(())
+}
+
+pub struct LifecycleOptions {
+    pub older_than_days: u64,
+    pub prune_threshold: f64,
+    pub group_by: ConsolidateGroup,
+    /// Body lines quoted under each consolidated note; 0 lists titles only.
+    pub excerpt_lines: usize,
+    /// Inclusive UTC day range of logged moves `restore` undoes; `None` is open.
+    pub restore_from: Option<NaiveDate>,
+    pub restore_to: Option<NaiveDate>,
+    pub decay_fn: DecayFn,
+    pub decay_scale: f64,
+    pub extensions: NoteExtensions,
+    pub dry_run: bool,
+}
+
+impl Default for LifecycleOptions {
+    fn default() -> Self {
+        LifecycleOptions {
+            older_than_days: 30,
+            prune_threshold: 1.0,
+            group_by: ConsolidateGroup::None,
+            excerpt_lines: 0,
+            restore_from: None,
+            restore_to: None,
+            decay_fn: DecayFn::Linear,
+            decay_scale: DEFAULT_DECAY_SCALE,
+            extensions: NoteExtensions::default(),
+            dry_run: false,
+        }
+    }
+}
+
+pub fn run_lifecycle(
+    notes_root: &Path,
+    mode: LifecycleMode,
+    options: &LifecycleOptions,
+) -> ExomResult<LifecycleReport> {
+    let run = match mode {
+        LifecycleMode::Decay => run_decay,
+        LifecycleMode::Consolidate => run_consolidate,
+        LifecycleMode::Archive => run_archive,
+        LifecycleMode::Unarchive => run_unarchive,
+        LifecycleMode::Restore => run_restore,
+        LifecycleMode::Prune => run_prune,
+    };
+    run(notes_root, options)
+}
+
+fn run_decay(notes_root: &Path, options: &LifecycleOptions) -> ExomResult<LifecycleReport> {
+    let notes = gather_inbox_notes(notes_root, &options.extensions)?;
+    let mut details = Vec::new();
+    let now = SystemTime::now();
+    for note in &notes {
+        let metadata = fs::metadata(note).map_err(io_at(note))?;
+        let modified = metadata.modified().unwrap_or(now);
+        let age_days = duration_since_days(now, modified);
+        if age_days >= DECAY_THRESHOLD_DAYS as f64 {
+            let last_reviewed = DateTime::<Utc>::from(modified).date_naive();
+            let score = compute_decay_score(age_days, options.decay_fn, options.decay_scale);
+            let changed = if options.dry_run {
+                let content = fs::read_to_string(note).map_err(io_at(note))?;
+                render_decay_metadata(&content, last_reviewed, score).is_some()
+            } else {
+                apply_decay_metadata(note, last_reviewed, score)?
+            };
+            if changed {
+                details.push(format!(
+                    "{} {} decay_score={:.3}",
+                    if options.dry_run {
+                        "Would mark"
+                    } else {
+                        "Marked"
+                    },
+                    relative_note_id(note, notes_root)?,
+                    score
+                ));
+            }
+        }
+    }
+    Ok(LifecycleReport {
+        mode: LifecycleMode::Decay,
+        processed: notes.len(),
+        touched: details.len(),
+        details,
+        summary_path: None,
+        dry_run: options.dry_run,
+    })
+}
+
+/// An inbox note old enough to be listed in the consolidation summary.
+struct ConsolidateCandidate {
+    note: PathBuf,
+    modified: DateTime<Utc>,
+    title: String,
+    /// Id relative to the notes root, as printed in the summary.
+    rel: String,
+    /// Body lines quoted under the entry; empty without `excerpt_lines`.
+    excerpt: Vec<String>,
+}
+
+fn run_consolidate(notes_root: &Path, options: &LifecycleOptions) -> ExomResult<LifecycleReport> {
+    let notes = gather_inbox_notes(notes_root, &options.extensions)?;
+    let now = Utc::now();
+    let cutoff = now - Duration::days(CONSOLIDATE_LOOKBACK_DAYS as i64);
+    let mut candidates = Vec::new();
+    for note in &notes {
+        let metadata = fs::metadata(note).map_err(io_at(note))?;
+        let modified = metadata.modified().unwrap_or(SystemTime::now());
+        let modified_dt = DateTime::<Utc>::from(modified);
+        if modified_dt < cutoff {
+            let rel = relative_note_id(note, notes_root)?;
+            let title = title_from_file(note)?;
+            let excerpt = if options.excerpt_lines > 0 {
+                let content = fs::read_to_string(note).map_err(io_at(note))?;
+                note_excerpt(&content, options.excerpt_lines)
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            candidates.push(ConsolidateCandidate {
+                note: note.clone(),
+                modified: modified_dt,
+                title,
+                rel,
+                excerpt,
+            });
+        }
+    }
+    candidates.sort_by(|a, b| a.note.cmp(&b.note));
+
+    let summary_name = format!("{}-{}.md", CONSOLIDATED_PREFIX, now.format("%Y-%m"));
+    let summary_path = notes_root.join(ARCHIVES_DIR).join(summary_name);
+    let mut content = String::new();
+    content.push_str(&format!(
+        "# Consolidated summary for {}\nGenerated: {}\n\n## Notes older than {} days\n\n",
+        now.format("%B %Y"),
+        now.format("%Y-%m-%d %H:%M:%S UTC"),
+        CONSOLIDATE_LOOKBACK_DAYS
+    ));
+    let line = |candidate: &ConsolidateCandidate| {
+        let mut entry = format!(
+            "- {} | {} | {}\n",
+            candidate.rel,
+            candidate.modified.format("%Y-%m-%d %H:%M:%S UTC"),
+            candidate.title
+        );
+        for text in &candidate.excerpt {
+            entry.push_str(&format!("  > {}\n", text));
+        }
+        entry
+    };
+    if candidates.is_empty() {
+        content.push_str("No eligible inbox notes.\n");
+    } else if options.group_by == ConsolidateGroup::None {
+        for candidate in &candidates {
+            content.push_str(&line(candidate));
+        }
+    } else {
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for candidate in &candidates {
+            let key = consolidate_group_key(&candidate.note, notes_root, options.group_by)
+                .unwrap_or_else(|| UNCATEGORIZED_GROUP.to_string());
+            groups.entry(key).or_default().push(line(candidate));
+        }
+        // Uncategorized notes go last, after the named groups.
+        let uncategorized = groups.remove(UNCATEGORIZED_GROUP);
+        for (name, members) in groups
+            .iter()
+            .map(|(name, members)| (name.as_str(), members))
+            .chain(
+                uncategorized
+                    .as_ref()
+                    .map(|members| (UNCATEGORIZED_GROUP, members)),
+            )
+        {
+            content.push_str(&format!("### {}\n\n{}\n", name, members.concat()));
+        }
+    }
+
+    if !options.dry_run {
+        if let Some(parent) = summary_path.parent() {
+            fs::create_dir_all(parent).map_err(io_at(parent))?;
+        }
+        write_atomic(&summary_path, content)?;
+    }
+    let verb = if options.dry_run {
+        "Would summarize"
+    } else {
+        "Summarized"
+    };
+    let details = candidates
+        .iter()
+        .map(|candidate| format!("{} {}", verb, candidate.rel))
+        .collect();
+    Ok(LifecycleReport {
+        mode: LifecycleMode::Consolidate,
+        processed: notes.len(),
+        touched: candidates.len(),
+        details,
+        summary_path: Some(summary_path.display().to_string()),
+        dry_run: options.dry_run,
+    })
+}
+
+/// First `limit` body lines worth quoting: frontmatter, headings, lifecycle
+/// markers, and fenced `yaml` relation blocks are skipped.
+fn note_excerpt(content: &str, limit: usize) -> Vec<&str> {
+    let (_, body) = split_frontmatter(content);
+    let mut lines = Vec::new();
+    let mut in_yaml = false;
+    for line in body.lines() {
+        let trimmed = line.trim();
+        if in_yaml {
+            in_yaml = !trimmed.starts_with("```");
+            continue;
+        }
+        if trimmed.starts_with("```yaml") {
+            in_yaml = true;
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(METADATA_PREFIX) {
+            continue;
+        }
+        lines.push(trimmed);
+        if lines.len() == limit {
+            break;
+        }
+    }
+    lines
+}
+
+const UNCATEGORIZED_GROUP: &str = "Uncategorized";
+
+/// Section a consolidated note is listed under. Consolidation only reads the
+/// inbox, so folder grouping uses the first folder below it; notes sitting
+/// directly in the inbox, or without tags, have no key.
+fn consolidate_group_key(
+    note: &Path,
+    notes_root: &Path,
+    group_by: ConsolidateGroup,
+) -> Option<String> {
+    match group_by {
+        ConsolidateGroup::None => None,
+        ConsolidateGroup::Folder => {
+            let rel = note.strip_prefix(notes_root.join(INBOX_DIR)).ok()?;
+            let mut parts = rel.iter();
+            let first = parts.next()?;
+            parts
+                .next()
+                .is_some()
+                .then(|| first.to_string_lossy().to_string())
+        }
+        ConsolidateGroup::Tag => {
+            let content = fs::read_to_string(note).ok()?;
+            let (frontmatter, _) = split_frontmatter(&content);
+            parse_frontmatter(frontmatter?).tags.into_iter().next()
+        }
+    }
+}
+
+fn run_archive(notes_root: &Path, options: &LifecycleOptions) -> ExomResult<LifecycleReport> {
+    let notes = gather_inbox_notes(notes_root, &options.extensions)?;
+    let mut details = Vec::new();
+    let mut moved = 0;
+    let now = SystemTime::now();
+    let lookback_secs = options.older_than_days.saturating_mul(86_400);
+    let cutoff = now
+        .checked_sub(StdDuration::from_secs(lookback_secs))
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let inbox_root = notes_root.join(INBOX_DIR);
+    let archive_root = notes_root.join(ARCHIVE_INBOX_DIR);
+    let run_id = Utc::now().format("%Y%m%dT%H%M%S%.6fZ").to_string();
+
+    for note in &notes {
+        let metadata = fs::metadata(note).map_err(io_at(note))?;
+        let modified = metadata.modified().unwrap_or(now);
+        if modified <= cutoff {
+            let rel = relative_note_id(note, notes_root)?;
+            let relative_inbox = note.strip_prefix(&inbox_root).unwrap_or(note);
+            let target = archive_root.join(relative_inbox);
+            if target.exists() {
+                details.push(format!("Skipped exists {}", rel));
+                continue;
+            }
+            let target_rel = relative_note_id(&target, notes_root)
+                .unwrap_or_else(|_| target.display().to_string());
+            moved += 1;
+            if options.dry_run {
+                details.push(format!("Would move {} -> {}", rel, target_rel));
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(io_at(parent))?;
+            }
+            fs::rename(note, &target).map_err(io_at(note))?;
+            details.push(format!("Moved {} -> {}", rel, target_rel));
+            // Logged per move, so a later failure still leaves this one undoable.
+            append_archive_log(
+                notes_root,
+                &[ArchiveMove {
+                    run_id: run_id.clone(),
+                    from: rel,
+                    to: target_rel,
+                    timestamp: Utc::now().to_rfc3339(),
+                }],
+            )?;
+        }
+    }
+
+    Ok(LifecycleReport {
+        mode: LifecycleMode::Archive,
+        processed: notes.len(),
+        touched: moved,
+        details,
+        summary_path: None,
+        dry_run: options.dry_run,
+    })
+}
+
+/// Moves notes whose decay marker has reached `prune_threshold` and whose
+/// last review is older than `older_than_days`. Moves go to the archive log,
+/// so `unarchive` can bring a pruned batch back.
+fn run_prune(notes_root: &Path, options: &LifecycleOptions) -> ExomResult<LifecycleReport> {
+    let cutoff = Utc::now().date_naive() - Duration::days(options.older_than_days as i64);
+    let pruned_root = notes_root.join(PRUNED_DIR);
+    let run_id = Utc::now().format("%Y%m%dT%H%M%S%.6fZ").to_string();
+    let mut details = Vec::new();
+    let mut processed = 0;
+    let mut moved = 0;
+
+    for source_dir in [INBOX_DIR, ARCHIVE_INBOX_DIR] {
+        let source_root = notes_root.join(source_dir);
+        for note in gather_notes_under(&source_root, &options.extensions)? {
+            processed += 1;
+            let content = fs::read_to_string(&note).map_err(io_at(&note))?;
+            let Some((last_reviewed, decay_score)) = parse_decay_metadata(&content) else {
+                continue;
+            };
+            if decay_score < options.prune_threshold || last_reviewed > cutoff {
+                continue;
+            }
+            let rel = relative_note_id(&note, notes_root)?;
+            let target = pruned_root.join(note.strip_prefix(&source_root).unwrap_or(&note));
+            if target.exists() {
+                details.push(format!("Skipped exists {}", rel));
+                continue;
+            }
+            let target_rel = relative_note_id(&target, notes_root)
+                .unwrap_or_else(|_| target.display().to_string());
+            moved += 1;
+            if options.dry_run {
+                details.push(format!("Would prune {} -> {}", rel, target_rel));
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(io_at(parent))?;
+            }
+            fs::rename(&note, &target).map_err(io_at(&note))?;
+            details.push(format!("Pruned {} -> {}", rel, target_rel));
+            append_archive_log(
+                notes_root,
+                &[ArchiveMove {
+                    run_id: run_id.clone(),
+                    from: rel,
+                    to: target_rel,
+                    timestamp: Utc::now().to_rfc3339(),
+                }],
+            )?;
+        }
+    }
+
+    Ok(LifecycleReport {
+        mode: LifecycleMode::Prune,
+        processed,
+        touched: moved,
+        details,
+        summary_path: None,
+        dry_run: options.dry_run,
+    })
+}
+
+/// One archive move; moves from the same run share a `run_id`.
+#[derive(Serialize, Deserialize)]
+struct ArchiveMove {
+    run_id: String,
+    from: String,
+    to: String,
+    timestamp: String,
+}
+
+fn append_archive_log(notes_root: &Path, moves: &[ArchiveMove]) -> ExomResult<()> {
+    let path = notes_root.join(ARCHIVE_LOG_FILE);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_at(parent))?;
+    }
+    (|| -> std::io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        for entry in moves {
+            serde_json::to_writer(&mut file, entry)?;
+            file.write_all(b"\n")?;
+        }
+        Ok(())
+    })()
+    .map_err(io_at(&path))
+}
+
+fn read_archive_log(notes_root: &Path) -> ExomResult<Vec<ArchiveMove>> {
+    let path = notes_root.join(ARCHIVE_LOG_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).map_err(io_at(&path))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            serde_json::from_str(line).map_err(|source| ExomError::Parse {
+                path: path.clone(),
+                line: Some(idx + 1),
+                source,
+            })
+        })
+        .collect()
+}
+
+/// Moves the last archive batch back to the inbox and drops it from the log,
+/// so repeated runs walk back through earlier batches. Entries skipped for a
+/// conflict stay in the log so a later run can retry.
+fn run_unarchive(notes_root: &Path, options: &LifecycleOptions) -> ExomResult<LifecycleReport> {
+    let mut log = read_archive_log(notes_root)?;
+    let last_run = log.last().map(|entry| entry.run_id.clone());
+    let batch: Vec<ArchiveMove> = match &last_run {
+        Some(run_id) => {
+            let split = log
+                .iter()
+                .position(|entry| &entry.run_id == run_id)
+                .unwrap_or(log.len());
+            log.split_off(split)
+        }
+        None => Vec::new(),
+    };
+    let processed = batch.len();
+    let mut details = Vec::new();
+    let mut restored = 0;
+    for entry in batch {
+        if restore_move(notes_root, &entry, options.dry_run, &mut details)? {
+            restored += 1;
+        } else {
+            log.push(entry);
+        }
+    }
+    if !options.dry_run && restored > 0 {
+        rewrite_archive_log(notes_root, &log)?;
+    }
+
+    Ok(LifecycleReport {
+        mode: LifecycleMode::Unarchive,
+        processed,
+        touched: restored,
+        details,
+        summary_path: None,
+        dry_run: options.dry_run,
+    })
+}
+
+/// Undoes logged moves whose UTC day falls in `restore_from..=restore_to`.
+/// Entries skipped for a conflict stay in the log so a later run can retry.
+fn run_restore(notes_root: &Path, options: &LifecycleOptions) -> ExomResult<LifecycleReport> {
+    let in_range = |entry: &ArchiveMove| {
+        DateTime::parse_from_rfc3339(&entry.timestamp)
+            .map(|moved| moved.with_timezone(&Utc).date_naive())
+            .is_ok_and(|day| {
+                options.restore_from.is_none_or(|from| day >= from)
+                    && options.restore_to.is_none_or(|to| day <= to)
+            })
+    };
+    let mut details = Vec::new();
+    let mut remaining = Vec::new();
+    let mut processed = 0;
+    let mut restored = 0;
+    for entry in read_archive_log(notes_root)? {
+        if !in_range(&entry) {
+            remaining.push(entry);
+            continue;
+        }
+        processed += 1;
+        if restore_move(notes_root, &entry, options.dry_run, &mut details)? {
+            restored += 1;
+        } else {
+            remaining.push(entry);
+        }
+    }
+    if !options.dry_run && restored > 0 {
+        rewrite_archive_log(notes_root, &remaining)?;
+    }
+
+    Ok(LifecycleReport {
+        mode: LifecycleMode::Restore,
+        processed,
+        touched: restored,
+        details,
+        summary_path: None,
+        dry_run: options.dry_run,
+    })
+}
+
+/// Moves one logged note from `to` back to `from`, unless the original spot
+/// is taken or the archived file is gone. Returns whether it was (or, on a dry
+/// run, would be) restored.
+fn restore_move(
+    notes_root: &Path,
+    entry: &ArchiveMove,
+    dry_run: bool,
+    details: &mut Vec<String>,
+) -> ExomResult<bool> {
+    let source = notes_root.join(&entry.to);
+    let target = notes_root.join(&entry.from);
+    if target.exists() {
+        details.push(format!("Skipped exists {}", entry.from));
+        return Ok(false);
+    }
+    if !source.exists() {
+        details.push(format!("Skipped missing {}", entry.to));
+        return Ok(false);
+    }
+    if dry_run {
+        details.push(format!("Would move {} -> {}", entry.to, entry.from));
+        return Ok(true);
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(io_at(parent))?;
+    }
+    fs::rename(&source, &target).map_err(io_at(&source))?;
+    details.push(format!("Restored {} -> {}", entry.to, entry.from));
+    Ok(true)
+}
+
+fn rewrite_archive_log(notes_root: &Path, moves: &[ArchiveMove]) -> ExomResult<()> {
+    let mut buf = Vec::new();
+    write_jsonl(&mut buf, moves)?;
+    write_atomic(&notes_root.join(ARCHIVE_LOG_FILE), buf)
+}
+
+#[derive(Serialize)]
+pub struct MergeReport {
+    pub from: String,
+    pub to: String,
+    pub archived_to: String,
+    /// Wikilinks retargeted from `from` to `to`, across every note.
+    pub links: usize,
+    pub details: Vec<String>,
+    pub dry_run: bool,
+}
+
+/// Finds a note by its id under the notes root (with or without its
+/// extension) or, failing that, by a case-insensitive stem that names exactly
+/// one note.
+fn resolve_note_file(
+    notes_root: &Path,
+    notes: &[PathBuf],
+    raw: &str,
+    extensions: &NoteExtensions,
+) -> ExomResult<PathBuf> {
+    let wanted = extensions.strip(raw.trim());
+    for note in notes {
+        let id = relative_note_id(note, notes_root)?;
+        if extensions.strip(&id) == wanted {
+            return Ok(note.clone());
+        }
+    }
+    let matches: Vec<&PathBuf> = notes
+        .iter()
+        .filter(|note| {
+            note.file_stem()
+                .is_some_and(|stem| stem.to_string_lossy().eq_ignore_ascii_case(wanted))
+        })
+        .collect();
+    match matches.as_slice() {
+        [note] => Ok((*note).clone()),
+        [] => Err(ExomError::invalid(format!(
+            "no note named {:?} under {}",
+            raw,
+            notes_root.display()
+        ))),
+        _ => Err(ExomError::invalid(format!(
+            "{:?} names {} notes; pass a path under the notes root instead",
+            raw,
+            matches.len()
+        ))),
+    }
+}
+
+/// Which `[[...]]` targets resolve to a note being merged or renamed, and
+/// what they point at instead.
+struct LinkRetarget {
+    /// Lowercased id, matched with or without its extension by path-style links.
+    id: String,
+    id_stem: String,
+    /// Lowercased stems and aliases that name this note and no other.
+    names: HashSet<String>,
+    /// Replacement for bare links.
+    to_stem: String,
+    /// Replacement for path-style links, the new id without its extension.
+    to_path: String,
+}
+
+impl LinkRetarget {
+    fn matches(&self, target: &str) -> bool {
+        let key = target.to_lowercase();
+        if key.contains('/') {
+            key == self.id || key == self.id_stem
+        } else {
+            self.names.contains(&key)
+        }
+    }
+}
+
+/// Lowercased stem and frontmatter aliases of each note, mapped to the notes
+/// they name; a bare `[[name]]` only resolves unambiguously with one owner.
+fn name_owners(texts: &[(PathBuf, String)]) -> HashMap<String, Vec<&Path>> {
+    let mut owners: HashMap<String, Vec<&Path>> = HashMap::new();
+    for (note, text) in texts {
+        let mut names: HashSet<String> = note
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+            .into_iter()
+            .collect();
+        if let (Some(raw), _) = split_frontmatter(text) {
+            names.extend(
+                parse_frontmatter(raw)
+                    .aliases
+                    .iter()
+                    .map(|alias| alias.to_lowercase()),
+            );
+        }
+        for name in names {
+            owners.entry(name).or_default().push(note);
+        }
+    }
+    owners
+}
+
+/// Id without its final extension, as a path-style link would write it.
+fn id_without_extension(id: &str) -> &str {
+    match id.rsplit_once('.') {
+        Some((stem, _)) if !stem.ends_with('/') && !stem.is_empty() => stem,
+        _ => id,
+    }
+}
+
+/// Points `[[...]]` links that resolve to the retargeted note at its
+/// replacement, bare links by stem and path-style ones by path, keeping any
+/// `#heading` and `|alias` part as written. Returns the new text and the
+/// number of links changed.
+fn retarget_wikilinks(content: &str, retarget: &LinkRetarget) -> (String, usize) {
+    let mut changed = 0;
+    let text = WIKILINK_REGEX.replace_all(content, |caps: &regex::Captures| {
+        let raw = caps[1].trim();
+        if !retarget.matches(raw) {
+            return caps[0].to_string();
+        }
+        changed += 1;
+        let to = if raw.contains('/') {
+            &retarget.to_path
+        } else {
+            &retarget.to_stem
+        };
+        let mut link = format!("[[{}", to);
+        if let Some(heading) = caps.get(2) {
+            link.push('#');
+            link.push_str(heading.as_str());
+        }
+        if let Some(alias) = caps.get(3) {
+            link.push('|');
+            link.push_str(alias.as_str());
+        }
+        link.push_str("]]");
+        link
+    });
+    (text.into_owned(), changed)
+}
+
+/// Appends `from`'s body (without frontmatter) to `to` below a separator,
+/// moves `from` under `MERGED_DIR`, and retargets links to it across the
+/// vault. Only links that resolve to `from` change: its path, or a stem or
+/// alias no other note shares. With `dry_run` only the report is produced.
+pub fn run_merge(
+    notes_root: &Path,
+    from: &str,
+    to: &str,
+    exclude: &GlobSet,
+    extensions: &NoteExtensions,
+    dry_run: bool,
+) -> ExomResult<MergeReport> {
+    let notes = collect_notes(notes_root, exclude, None, extensions)?.notes;
+    let source = resolve_note_file(notes_root, &notes, from, extensions)?;
+    let target = resolve_note_file(notes_root, &notes, to, extensions)?;
+    if source == target {
+        return Err(ExomError::invalid("--from and --to name the same note"));
+    }
+    let source_id = relative_note_id(&source, notes_root)?;
+    let target_id = relative_note_id(&target, notes_root)?;
+    let archived = notes_root.join(MERGED_DIR).join(&source_id);
+    if archived.exists() {
+        return Err(ExomError::AlreadyExists { path: archived });
+    }
+    let archived_id = relative_note_id(&archived, notes_root)?;
+
+    let texts = notes
+        .iter()
+        .map(|note| Ok((note.clone(), fs::read_to_string(note).map_err(io_at(note))?)))
+        .collect::<ExomResult<Vec<_>>>()?;
+    let owners = name_owners(&texts);
+    let text_of = |path: &Path| {
+        texts
+            .iter()
+            .find(|(note, _)| note == path)
+            .map(|(_, text)| text.as_str())
+            .unwrap_or_default()
+    };
+    let (_, source_body) = split_frontmatter(text_of(&source));
+    let target_stem = target
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let source_lower = source_id.to_lowercase();
+    let retarget = LinkRetarget {
+        id_stem: id_without_extension(&source_lower).to_string(),
+        id: source_lower,
+        names: owners
+            .iter()
+            .filter(|(_, notes)| notes.as_slice() == [source.as_path()])
+            .map(|(name, _)| name.clone())
+            .collect(),
+        to_stem: target_stem,
+        to_path: id_without_extension(&target_id).to_string(),
+    };
+
+    let verb = if dry_run { "Would rewrite" } else { "Rewrote" };
+    let mut details = Vec::new();
+    let mut links = 0;
+    let mut writes: Vec<(PathBuf, String)> = Vec::new();
+    for (note, content) in texts
+        .iter()
+        .filter(|(note, _)| *note != source && *note != target)
+    {
+        let (updated, changed) = retarget_wikilinks(content, &retarget);
+        if changed > 0 {
+            links += changed;
+            details.push(format!(
+                "{} {} link(s) in {}",
+                verb,
+                changed,
+                relative_note_id(note, notes_root)?
+            ));
+            writes.push((note.clone(), updated));
+        }
+    }
+
+    let mut merged = text_of(&target).trim_end().to_string();
+    merged.push_str(&format!(
+        "\n\n---\n<!-- merged from {} -->\n\n{}\n",
+        source_id,
+        source_body.trim()
+    ));
+    let (merged, changed) = retarget_wikilinks(&merged, &retarget);
+    links += changed;
+    details.push(format!(
+        "{} {} into {}",
+        if dry_run { "Would append" } else { "Appended" },
+        source_id,
+        target_id
+    ));
+    writes.push((target.clone(), merged));
+    details.push(format!(
+        "{} {} -> {}",
+        if dry_run { "Would move" } else { "Moved" },
+        source_id,
+        archived_id
+    ));
+
+    // Move first: a failed move leaves every note untouched.
+    if !dry_run {
+        if let Some(parent) = archived.parent() {
+            fs::create_dir_all(parent).map_err(io_at(parent))?;
+        }
+        fs::rename(&source, &archived).map_err(io_at(&source))?;
+        for (path, content) in &writes {
+            write_atomic(path, content)?;
+        }
+    }
+    Ok(MergeReport {
+        from: source_id,
+        to: target_id,
+        archived_to: archived_id,
+        links,
+        details,
+        dry_run,
+    })
+}
+
+#[derive(Serialize)]
+pub struct RenameReport {
+    pub from: String,
+    pub to: String,
+    /// Wikilinks pointed at the new stem, across every note.
+    pub links: usize,
+    pub details: Vec<String>,
+    pub dry_run: bool,
+}
+
+/// Moves `from` to `to` plus its extension in the same folder, then retargets
+/// links to it across the vault: its path, keeping the folder, or its old stem
+/// when no other note shares it. Refuses a stem that another note already
+/// uses, since links resolve by stem. With `dry_run` only the report is
+/// produced.
+pub fn run_rename(
+    notes_root: &Path,
+    from: &str,
+    to: &str,
+    exclude: &GlobSet,
+    extensions: &NoteExtensions,
+    dry_run: bool,
+) -> ExomResult<RenameReport> {
+    let new_stem = to.trim();
+    // Anything a `[[...]]` target can't hold would leave the links unresolvable.
+    if new_stem.is_empty() || new_stem.contains(['/', '\\', '#', '|', '[', ']']) {
+        return Err(ExomError::invalid(format!(
+            "--to must be a bare file stem, got {:?}",
+            to
+        )));
+    }
+    let notes = collect_notes(notes_root, exclude, None, extensions)?.notes;
+    let source = resolve_note_file(notes_root, &notes, from, extensions)?;
+    let old_stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if old_stem == new_stem {
+        return Err(ExomError::invalid(format!(
+            "{} is already named {:?}",
+            source.display(),
+            new_stem
+        )));
+    }
+    if let Some(existing) = notes.iter().find(|note| {
+        **note != source
+            && note
+                .file_stem()
+                .is_some_and(|stem| stem.to_string_lossy().eq_ignore_ascii_case(new_stem))
+    }) {
+        return Err(ExomError::invalid(format!(
+            "{:?} is already used by {}",
+            new_stem,
+            relative_note_id(existing, notes_root)?
+        )));
+    }
+    let mut file_name = new_stem.to_string();
+    if let Some(ext) = source.extension() {
+        file_name.push('.');
+        file_name.push_str(&ext.to_string_lossy());
+    }
+    let renamed = source.with_file_name(file_name);
+    if renamed.exists() && !old_stem.eq_ignore_ascii_case(new_stem) {
+        return Err(ExomError::AlreadyExists { path: renamed });
+    }
+    let source_id = relative_note_id(&source, notes_root)?;
+    let renamed_id = relative_note_id(&renamed, notes_root)?;
+
+    let texts = notes
+        .iter()
+        .map(|note| Ok((note.clone(), fs::read_to_string(note).map_err(io_at(note))?)))
+        .collect::<ExomResult<Vec<_>>>()?;
+    let old_name = old_stem.to_lowercase();
+    let unambiguous = name_owners(&texts)
+        .get(&old_name)
+        .is_some_and(|owners| owners.as_slice() == [source.as_path()]);
+    let source_lower = source_id.to_lowercase();
+    let retarget = LinkRetarget {
+        id_stem: id_without_extension(&source_lower).to_string(),
+        id: source_lower,
+        names: unambiguous.then_some(old_name).into_iter().collect(),
+        to_stem: new_stem.to_string(),
+        to_path: id_without_extension(&renamed_id).to_string(),
+    };
+    let verb = if dry_run { "Would rewrite" } else { "Rewrote" };
+    let mut details = Vec::new();
+    let mut links = 0;
+    let mut writes: Vec<(PathBuf, String)> = Vec::new();
+    for (note, content) in &texts {
+        let (updated, changed) = retarget_wikilinks(content, &retarget);
+        if changed > 0 {
+            links += changed;
+            details.push(format!(
+                "{} {} link(s) in {}",
+                verb,
+                changed,
+                relative_note_id(note, notes_root)?
+            ));
+            // Self-links are written to the note at its new name.
+            let path = if *note == source {
+                renamed.clone()
+            } else {
+                note.clone()
+            };
+            writes.push((path, updated));
+        }
+    }
+    details.push(format!(
+        "{} {} -> {}",
+        if dry_run { "Would move" } else { "Moved" },
+        source_id,
+        renamed_id
+    ));
+
+    // Move first: a failed move leaves every note untouched.
+    if !dry_run {
+        fs::rename(&source, &renamed).map_err(io_at(&source))?;
+        for (path, content) in &writes {
+            write_atomic(path, content)?;
+        }
+    }
+    Ok(RenameReport {
+        from: source_id,
+        to: renamed_id,
+        links,
+        details,
+        dry_run,
+    })
+}
+
+fn duration_since_days(now: SystemTime, earlier: SystemTime) -> f64 {
+    now.duration_since(earlier)
+        .unwrap_or(StdDuration::ZERO)
+        .as_secs_f64()
+        / 86_400.0
+}
+
+/// Decay in [0, 1] for a note `days` old. The sigmoid's slope puts it at
+/// about 0.02 when new and 0.98 at twice `scale`.
+fn compute_decay_score(days: f64, curve: DecayFn, scale: f64) -> f64 {
+    let ratio = days / scale;
+    match curve {
+        DecayFn::Linear => ratio.min(1.0),
+        DecayFn::Exponential => 1.0 - (-ratio).exp(),
+        DecayFn::Sigmoid => 1.0 / (1.0 + (-4.0 * (ratio - 1.0)).exp()),
+    }
+}
+
+fn apply_decay_metadata(
+    note: &Path,
+    last_reviewed: NaiveDate,
+    decay_score: f64,
+) -> ExomResult<bool> {
+    let content = fs::read_to_string(note).map_err(io_at(note))?;
+    match render_decay_metadata(&content, last_reviewed, decay_score) {
+        Some(rebuilt) => {
+            write_atomic(note, rebuilt)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Returns the note content with a refreshed lifecycle marker, or `None` when
+/// the existing marker already matches.
+fn render_decay_metadata(
+    content: &str,
+    last_reviewed: NaiveDate,
+    decay_score: f64,
+) -> Option<String> {
+    let new_line = format!(
+        "<!-- lifecycle last_reviewed={} decay_score={:.3} -->",
+        last_reviewed, decay_score
+    );
+    if lifecycle_marker(content) == Some(new_line.as_str()) {
+        return None;
+    }
+
+    let filtered: Vec<&str> = content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with(METADATA_PREFIX))
+        .collect();
+    let mut rebuilt = filtered.join("\n");
+    if !rebuilt.is_empty() {
+        rebuilt.push('\n');
+    }
+    rebuilt.push_str(&new_line);
+    rebuilt.push('\n');
+    Some(rebuilt)
+}
+
+/// The last lifecycle marker line in a note, trimmed.
+fn lifecycle_marker(content: &str) -> Option<&str> {
+    content
+        .lines()
+        .rev()
+        .find(|line| line.trim_start().starts_with(METADATA_PREFIX))
+        .map(|line| line.trim())
+}
+
+/// Reads `last_reviewed` and `decay_score` back out of the lifecycle marker.
+fn parse_decay_metadata(content: &str) -> Option<(NaiveDate, f64)> {
+    let marker = lifecycle_marker(content)?;
+    let mut last_reviewed = None;
+    let mut decay_score = None;
+    for field in marker.split_whitespace() {
+        if let Some(value) = field.strip_prefix("last_reviewed=") {
+            last_reviewed = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok();
+        } else if let Some(value) = field.strip_prefix("decay_score=") {
+            decay_score = value.parse().ok();
+        }
+    }
+    Some((last_reviewed?, decay_score?))
+}
+
+#[derive(Serialize)]
+pub struct ReviewItem {
+    pub path: String,
+    pub decay_score: f64,
+    pub last_reviewed: NaiveDate,
+}
+
+/// Notes carrying a lifecycle marker, most decayed first; ties go to the
+/// longest-unreviewed note. `all` scans the whole root, skipping
+/// `99_Archives` unless `include_archives` is set. `topk == 0` keeps them all.
+pub fn review_queue(
+    notes_root: &Path,
+    all: bool,
+    include_archives: bool,
+    topk: usize,
+    extensions: &NoteExtensions,
+) -> ExomResult<Vec<ReviewItem>> {
+    let notes = if all {
+        let archives = notes_root.join(ARCHIVES_DIR);
+        gather_notes_under(notes_root, extensions)?
+            .into_iter()
+            .filter(|note| include_archives || !note.starts_with(&archives))
+            .collect()
+    } else {
+        gather_inbox_notes(notes_root, extensions)?
+    };
+    let mut items = Vec::new();
+    for note in notes {
+        let content = fs::read_to_string(&note).map_err(io_at(&note))?;
+        if let Some((last_reviewed, decay_score)) = parse_decay_metadata(&content) {
+            items.push(ReviewItem {
+                path: relative_note_id(&note, notes_root)?,
+                decay_score,
+                last_reviewed,
+            });
+        }
+    }
+    items.sort_by(|a, b| {
+        b.decay_score
+            .partial_cmp(&a.decay_score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.last_reviewed.cmp(&b.last_reviewed))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    if topk > 0 {
+        items.truncate(topk);
+    }
+    Ok(items)
+}
+
+/// A note changed between a git ref and HEAD. `current` is its text at HEAD;
+/// `previous` is its text at the ref, `None` when the note was added since.
+pub struct NoteChange {
+    path: PathBuf,
+    current: String,
+    previous: Option<String>,
+}
+
+/// Notes under `notes_root` that `git diff <since>..HEAD` reports as added or
+/// modified, with both versions read from git so uncommitted edits are left
+/// out. Deleted notes are left out too.
+pub fn git_note_changes(
+    notes_root: &Path,
+    since: &str,
+    extensions: &NoteExtensions,
+) -> ExomResult<Vec<NoteChange>> {
+    let git_error = |detail: String| ExomError::Command {
+        program: "git".to_string(),
+        detail,
+    };
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .arg("-C")
+            .arg(notes_root)
+            .args(args)
+            .output()
+            .map_err(|err| git_error(format!("failed to run git; is it installed? ({})", err)))
+    };
+    let toplevel = git(&["rev-parse", "--show-toplevel"])?;
+    if !toplevel.status.success() {
+        return Err(git_error(format!(
+            "{} is not inside a git repository; `exom digest` reads changes from git history.",
+            notes_root.display()
+        )));
+    }
+    let top = PathBuf::from(String::from_utf8_lossy(&toplevel.stdout).trim());
+    let root = notes_root.canonicalize().map_err(io_at(notes_root))?;
+    let range = format!("{}..HEAD", since);
+    // `-z` keeps paths raw; without it git quotes and escapes non-ASCII names.
+    let diff = git(&["diff", "-z", "--name-status", "--no-renames", &range])?;
+    if !diff.status.success() {
+        return Err(git_error(format!(
+            "git diff {} failed: {}",
+            range,
+            String::from_utf8_lossy(&diff.stderr).trim()
+        )));
+    }
+    let show = |rev: &str, rel: &str| -> ExomResult<Option<String>> {
+        let out = git(&["show", &format!("{}:{}", rev, rel)])?;
+        Ok(out
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&out.stdout).into_owned()))
+    };
+    let listing = String::from_utf8_lossy(&diff.stdout);
+    let mut fields = listing.split('\0');
+    let mut changes = Vec::new();
+    while let (Some(status), Some(rel)) = (fields.next(), fields.next()) {
+        let path = top.join(rel);
+        if status.starts_with('D') || !path.starts_with(&root) || !extensions.matches(&path) {
+            continue;
+        }
+        let Some(current) = show("HEAD", rel)? else {
+            continue;
+        };
+        let previous = if status.starts_with('A') {
+            None
+        } else {
+            show(since, rel)?
+        };
+        changes.push(NoteChange {
+            path: path
+                .strip_prefix(&root)
+                .map(|rel| notes_root.join(rel))
+                .unwrap_or(path),
+            current,
+            previous,
+        });
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+#[derive(Serialize)]
+pub struct DigestReport {
+    pub since: String,
+    pub notes: Vec<DigestNote>,
+}
+
+#[derive(Serialize)]
+pub struct DigestNote {
+    pub path: String,
+    pub title: String,
+    pub added: bool,
+    /// Relations in the note now that it did not have at the ref.
+    pub new_relations: Vec<TypedRelation>,
+}
+
+/// Reads each changed note and diffs its relations against the ref's version.
+pub fn digest_notes(
+    notes_root: &Path,
+    since: &str,
+    changes: &[NoteChange],
+) -> ExomResult<DigestReport> {
+    let relations_of =
+        |content: &str| parse_relations(split_frontmatter(content).1, &RELATION_REGEX);
+    let mut notes = Vec::new();
+    for change in changes {
+        let content = &change.current;
+        let before = change
+            .previous
+            .as_deref()
+            .map(relations_of)
+            .unwrap_or_default();
+        let mut new_relations: Vec<TypedRelation> = relations_of(content)
+            .into_iter()
+            .filter(|relation| !before.contains(relation))
+            .collect();
+        new_relations.dedup();
+        notes.push(DigestNote {
+            path: relative_note_id(&change.path, notes_root)?,
+            title: title_from_text(content, &change.path),
+            added: change.previous.is_none(),
+            new_relations,
+        });
+    }
+    Ok(DigestReport {
+        since: since.to_string(),
+        notes,
+    })
+}
+
+fn gather_inbox_notes(notes_root: &Path, extensions: &NoteExtensions) -> ExomResult<Vec<PathBuf>> {
+    gather_notes_under(&notes_root.join(INBOX_DIR), extensions)
+}
+
+fn gather_notes_under(dir: &Path, extensions: &NoteExtensions) -> ExomResult<Vec<PathBuf>> {
+    let mut notes = Vec::new();
+    if !dir.exists() {
+        return Ok(notes);
+    }
+    for entry in WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        if extensions.matches(entry.path()) {
+            notes.push(entry.into_path());
+        }
+    }
+    notes.sort();
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_split() {
+        let result = tokens("Hello, WORLD-42_test!", &TokenOptions::default());
+        assert!(result.contains("hello"));
+        assert!(result.contains("world-42_test"));
+    }
+
+    #[test]
+    fn unicode_tokens_keep_accented_words() {
+        let ascii = token_sequence("Café phở", &TokenOptions::default());
+        assert_eq!(ascii, vec!["caf", "ph"]);
+        let options = TokenOptions {
+            unicode: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            token_sequence("Café phở, 東京", &options),
+            vec!["café", "phở", "東", "京"]
+        );
+        assert_ne!(options.fingerprint(), TokenOptions::default().fingerprint());
+    }
+
+    #[test]
+    fn token_filters_drop_short_and_numeric_tokens() {
+        let root = temp_root("token-filters");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "10_Projects/a.md",
+            "# Plan 2024\nShip v2 on page 42 of a b report\n",
+        );
+        let options = IndexOptions {
+            tokens: TokenOptions {
+                min_len: 2,
+                drop_numeric: true,
+                ..Default::default()
+            },
+            ..IndexOptions::default()
+        };
+        let result = index_graph_data(&root, &out, &options).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let node = require_node(&graph, "a").unwrap();
+        for dropped in ["2024", "42", "a", "b"] {
+            assert!(!node.term_counts.contains_key(dropped), "{} kept", dropped);
+        }
+        for kept in ["plan", "v2", "page", "report"] {
+            assert!(node.term_counts.contains_key(kept), "{} dropped", kept);
+        }
+        assert!(graph.drop_numeric && graph.min_token_len == 2);
+
+        let query = tokens("2024 plan", &TokenOptions::from_graph(&graph));
+        assert_eq!(query.into_iter().collect::<Vec<_>>(), vec!["plan"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn token_counts_tracks_multiples() {
+        let counts = token_counts("Rust rust RUST!!!", &TokenOptions::default());
+        assert_eq!(counts.get("rust"), Some(&3));
+    }
+
+    #[test]
+    fn stemming_collapses_inflections() {
+        let options = TokenOptions {
+            stem: true,
+            ..Default::default()
+        };
+        let counts = token_counts("running runs", &options);
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts.get("run"), Some(&2));
+        assert_eq!(
+            token_counts("running runs", &TokenOptions::default()).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn stopwords_are_dropped_from_index_and_queries() {
+        let root = temp_root("stopwords");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "10_Projects/a.md",
+            "# A\nThe engine and the parser\n",
+        );
+        let list = root.join("stop.txt");
+        fs::write(&list, "# project noise\nengine\n").unwrap();
+        let options = IndexOptions {
+            tokens: TokenOptions {
+                stopwords: load_stopwords(Some(&list), true).unwrap(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = index_graph_data(&root, &out, &options).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let node = graph.nodes.iter().find(|n| n.id.ends_with("a.md")).unwrap();
+        assert!(node.semantic.contains_key("parser"));
+        for word in ["the", "and", "engine"] {
+            assert!(!node.semantic.contains_key(word), "{word} kept");
+        }
+        let query = tokens("the parser", &TokenOptions::from_graph(&graph));
+        assert_eq!(query, HashSet::from(["parser".to_string()]));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn stemming_mismatch_is_reported() {
+        let root = temp_root("stem-doctor");
+        let out = root.join(".neural");
+        write_note(&root, "10_Projects/a.md", "# A\ntesting tests\n");
+        let options = IndexOptions {
+            tokens: TokenOptions {
+                stem: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = index_graph_data(&root, &out, &options).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        assert!(graph.stemmed);
+        assert!(ensure_stemming_matches(&graph, false).is_err());
+        assert!(ensure_stemming_matches(&graph, true).is_ok());
+
+        let report = doctor_report(&root, &result.graph_path, &DoctorOptions::default());
+        let check = report
+            .checks
+            .iter()
+            .find(|c| c.name == "graph_stemming")
+            .unwrap();
+        assert!(!check.ok);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn lexical_overlap_zero_when_no_shared_tokens() {
+        let query = tokens("alpha beta", &TokenOptions::default());
+        let score = lexical_overlap_score(&query, "gamma delta", &TokenOptions::default());
+        assert_eq!(score, 0.0);
+    }
+
+    #[test]
+    fn set_similarity_modes_favor_fully_covered_short_notes() {
+        let long_counts: Vec<(String, usize)> = ["kafka", "lag"]
+            .into_iter()
+            .map(str::to_string)
+            .chain((0..30).map(|i| format!("filler{}", i)))
+            .map(|token| (token, 1))
+            .collect();
+        let long_refs: Vec<(&str, usize)> =
+            long_counts.iter().map(|(t, c)| (t.as_str(), *c)).collect();
+        let graph = graph_of(
+            vec![
+                counted_node("short.md", "Short", &[("kafka", 1), ("lag", 1)]),
+                counted_node("long.md", "Long", &long_refs),
+            ],
+            vec![],
+        );
+        let weights = RecallProfile::Lexical.weights();
+        for mode in [LexicalMode::Jaccard, LexicalMode::Dice] {
+            let options = RecallOptions {
+                lexical_mode: mode,
+                ..RecallOptions::default()
+            };
+            let rows = recall_from_graph(&graph, "kafka lag", 5, &weights, &options);
+            assert_eq!(rows[0].path.as_deref(), Some("short.md"), "{}", mode);
+            assert!(rows[0].score > rows[1].score);
+        }
+
+        let query = tokens("kafka lag", &TokenOptions::default());
+        let short = &graph.nodes[0].term_counts;
+        let long = &graph.nodes[1].term_counts;
+        assert_eq!(
+            vocabulary_similarity(&query, short, LexicalMode::Jaccard),
+            1.0
+        );
+        assert!(
+            (vocabulary_similarity(&query, long, LexicalMode::Jaccard) - 2.0 / 32.0).abs() < 1e-12
+        );
+        assert!(
+            (vocabulary_similarity(&query, long, LexicalMode::Dice) - 4.0 / 34.0).abs() < 1e-12
+        );
+    }
+
+    #[test]
+    fn pagerank_favors_notes_cited_by_hubs() {
+        let graph = graph_of(
+            vec![
+                counted_node("a.md", "a", &[]),
+                counted_node("b.md", "b", &[]),
+                counted_node("c.md", "c", &[]),
+                counted_node("hub.md", "hub", &[]),
+                counted_node("orphan.md", "orphan", &[]),
+                counted_node("cited.md", "cited", &[]),
+                counted_node("stray.md", "stray", &[]),
+            ],
+            vec![
+                edge("a.md", "hub.md", "WIKILINK"),
+                edge("b.md", "hub.md", "WIKILINK"),
+                edge("c.md", "hub.md", "WIKILINK"),
+                edge("hub.md", "cited.md", "WIKILINK"),
+                edge("orphan.md", "stray.md", "WIKILINK"),
+            ],
+        );
+        let ranks = pagerank(&graph, &EdgeWeights::default(), false);
+        assert!((ranks.values().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(ranks["cited.md"] > ranks["stray.md"]);
+
+        let weights = RecallWeights {
+            lexical: 0.0,
+            graph: 1.0,
+            semantic: 0.0,
+        };
+        let options = RecallOptions {
+            graph_mode: GraphMode::Pagerank,
+            ..Default::default()
+        };
+        let rows = recall_from_graph(&graph, "anything", 10, &weights, &options);
+        let position = |title: &str| rows.iter().position(|row| row.title == title).unwrap();
+        assert!(position("cited") < position("stray"));
+    }
+
+    #[test]
+    fn fuzzy_recall_tolerates_title_typos() {
+        let graph = graph_of(
+            vec![
+                counted_node(
+                    "db.md",
+                    "Database design",
+                    &[("database", 1), ("design", 1)],
+                ),
+                counted_node("ops.md", "Oncall rotation", &[("oncall", 1)]),
+            ],
+            Vec::new(),
+        );
+        let weights = RecallProfile::Lexical.weights();
+        let strict = recall_from_graph(&graph, "databse", 5, &weights, &RecallOptions::default());
+        assert!(strict.is_empty());
+
+        let options = RecallOptions {
+            fuzzy: true,
+            ..Default::default()
+        };
+        let rows = recall_from_graph(&graph, "databse", 5, &weights, &options);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].title, "Database design");
+    }
+
+    #[test]
+    fn synonyms_expand_query_terms() {
+        let root = temp_root("synonyms");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "10_Projects/cluster.md",
+            "# Cluster upgrade\nbump k8s nodes\n",
+        );
+        write_note(
+            &root,
+            "10_Projects/garden.md",
+            "# Garden\ntomatoes and basil\n",
+        );
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let weights = RecallProfile::Balanced.weights();
+
+        let plain = recall_from_graph(&graph, "kubernetes", 5, &weights, &RecallOptions::default());
+        assert!(plain.is_empty());
+
+        let map = BTreeMap::from([("kubernetes".to_string(), vec!["k8s".to_string()])]);
+        let options = RecallOptions {
+            synonyms: Some(Synonyms::from_map(map, 0.5)),
+            ..Default::default()
+        };
+        let rows = recall_from_graph(&graph, "kubernetes", 5, &weights, &options);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].title, "Cluster upgrade");
+        let reverse = recall_from_graph(&graph, "k8s", 5, &weights, &options);
+        assert_eq!(reverse[0].title, "Cluster upgrade");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn snippets_highlight_matching_line() {
+        let root = temp_root("snippets");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "10_Projects/deploy.md",
+            "# Deploy\nGeneral notes.\nThe canary rollout stalled at 10%.\n",
+        );
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let weights = RecallProfile::Balanced.weights();
+
+        let plain = recall_from_graph(&graph, "rollout", 5, &weights, &RecallOptions::default());
+        assert!(plain[0].snippet.is_none());
+
+        let options = RecallOptions {
+            snippets: true,
+            ..Default::default()
+        };
+        let rows = recall_from_graph(&graph, "canary rollout", 5, &weights, &options);
+        assert_eq!(
+            rows[0].snippet.as_deref(),
+            Some("The **canary** **rollout** stalled at 10%.")
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn open_resolves_hits_against_notes_root() {
+        let mut ghost = counted_node("ghost/Rollout", "Rollout checklist", &[]);
+        ghost.path = None;
+        let mut graph = graph_of(
+            vec![
+                counted_node("10_Projects/rollout.md", "Rollout plan", &[]),
+                ghost,
+            ],
+            Vec::new(),
+        );
+        graph.notes_root = vec!["/vault".to_string()];
+
+        let first = resolve_recall_hit(&graph, "rollout plan", 1).unwrap();
+        assert_eq!(first, Path::new("/vault/10_Projects/rollout.md"));
+        let ghost_hit = resolve_recall_hit(&graph, "rollout plan", 2).unwrap_err();
+        assert!(ghost_hit.to_string().contains("ghost"));
+        let beyond = resolve_recall_hit(&graph, "rollout plan", 5).unwrap_err();
+        assert!(beyond.to_string().contains("out of range"));
+    }
+
+    #[test]
+    fn graph_influence_caps_at_ten() {
+        assert_eq!(graph_influence(5.0), 0.5);
+        assert_eq!(graph_influence(20.0), 1.0);
+    }
+
+    #[test]
+    fn semantic_score_considers_weights() {
+        let mut vector = BTreeMap::new();
+        vector.insert("foo".to_string(), 2.5);
+        vector.insert("bar".to_string(), 1.0);
+        let mut query_vector = BTreeMap::new();
+        query_vector.insert("foo".to_string(), 2.0);
+        query_vector.insert("baz".to_string(), 1.0);
+        let score = semantic_score(&query_vector, &vector);
+        assert!((score - 5.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn query_idf_favors_rare_tokens() {
+        let mut graph = graph_of(
+            vec![
+                semantic_node("rare.md", &[("zeppelin", 1.0)]),
+                semantic_node("common.md", &[("notes", 1.0)]),
+            ],
+            Vec::new(),
+        );
+        graph.total_docs = 10;
+        graph.doc_freq.insert("zeppelin".to_string(), 1);
+        graph.doc_freq.insert("notes".to_string(), 10);
+        let weights = RecallWeights {
+            lexical: 0.0,
+            graph: 0.0,
+            semantic: 1.0,
+        };
+        let rows = recall_from_graph(
+            &graph,
+            "notes zeppelin",
+            10,
+            &weights,
+            &RecallOptions::default(),
+        );
+        assert_eq!(rows[0].path.as_deref(), Some("rare.md"));
+        assert!(rows[0].score > rows[1].score);
+    }
+
+    fn counted_node(id: &str, title: &str, counts: &[(&str, usize)]) -> Node {
+        Node {
+            id: id.to_string(),
+            path: Some(id.to_string()),
+            title: title.to_string(),
+            stem: id.trim_end_matches(".md").to_string(),
+            term_counts: counts
+                .iter()
+                .map(|(token, count)| (token.to_string(), *count))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn graph_of(nodes: Vec<Node>, edges: Vec<Edge>) -> GraphData {
+        let stats = Stats {
+            notes: nodes.len(),
+            nodes: nodes.len(),
+            edges: edges.len(),
+        };
+        GraphData {
+            notes_root: vec![".".to_string()],
+            nodes,
+            edges,
+            stats,
+            ..Default::default()
+        }
+    }
+
+    fn semantic_node(id: &str, weights: &[(&str, f64)]) -> Node {
+        let semantic: BTreeMap<String, f64> = weights
+            .iter()
+            .map(|(token, weight)| (token.to_string(), *weight))
+            .collect();
+        Node {
+            id: id.to_string(),
+            path: Some(id.to_string()),
+            title: id.to_string(),
+            stem: id.trim_end_matches(".md").to_string(),
+            semantic_norm: vector_norm(&semantic),
+            semantic,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cosine_ignores_document_length() {
+        let mut graph = graph_of(
+            vec![
+                semantic_node("short.md", &[("rust", 1.0), ("graph", 1.0)]),
+                semantic_node("long.md", &[("rust", 2.0), ("graph", 2.0)]),
+            ],
+            Vec::new(),
+        );
+        graph.total_docs = 2;
+        graph.doc_freq.insert("rust".to_string(), 2);
+        graph.doc_freq.insert("graph".to_string(), 2);
+        let weights = RecallWeights {
+            lexical: 0.0,
+            graph: 0.0,
+            semantic: 1.0,
+        };
+
+        let dot = recall_from_graph(
+            &graph,
+            "rust graph",
+            10,
+            &weights,
+            &RecallOptions::default(),
+        );
+        assert_eq!(dot[0].path.as_deref(), Some("long.md"));
+        assert!(dot[0].score > dot[1].score);
+
+        let options = RecallOptions {
+            semantic_mode: SemanticMode::Cosine,
+            ..Default::default()
+        };
+        let cosine = recall_from_graph(&graph, "rust graph", 10, &weights, &options);
+        assert_eq!(cosine.len(), 2);
+        assert!((cosine[0].score - cosine[1].score).abs() < 1e-9);
+        assert!((cosine[0].score - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bm25_prefers_dense_short_notes_over_overlap_tie() {
+        let graph = graph_of(
+            vec![
+                counted_node(
+                    "long.md",
+                    "Rust survey",
+                    &[("rust", 1), ("survey", 1), ("misc", 40), ("filler", 40)],
+                ),
+                counted_node("short.md", "Rust tips", &[("rust", 3), ("tips", 1)]),
+                counted_node("other.md", "Gardening", &[("soil", 5)]),
+            ],
+            Vec::new(),
+        );
+        let weights = RecallWeights {
+            lexical: 1.0,
+            graph: 0.0,
+            semantic: 0.0,
+        };
+        let overlap = recall_from_graph(&graph, "rust", 10, &weights, &RecallOptions::default());
+        assert_eq!(overlap.len(), 2);
+        assert_eq!(overlap[0].score, overlap[1].score);
+
+        let options = RecallOptions {
+            lexical_mode: LexicalMode::Bm25,
+            ..Default::default()
+        };
+        let bm25 = recall_from_graph(&graph, "rust", 10, &weights, &options);
+        assert_eq!(bm25.len(), 2);
+        assert_eq!(bm25[0].path.as_deref(), Some("short.md"));
+        assert!(bm25[0].score > bm25[1].score);
+    }
+
+    fn temp_root(label: &str) -> PathBuf {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let unique = COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let root = env::temp_dir().join(format!(
+            "exom-test-{}-{}-{}",
+            label,
+            std::process::id(),
+            unique
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn write_note(root: &Path, rel: &str, body: &str) -> PathBuf {
+        let path = root.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, body).unwrap();
+        path
+    }
+
+    #[test]
+    fn incremental_index_reuses_unchanged_notes() {
+        let root = temp_root("incremental");
+        let out = root.join(".neural");
+        write_note(&root, "10_Projects/alpha.md", "# Alpha\nrust [[beta]]\n");
+        write_note(&root, "10_Projects/beta.md", "# Beta\nrust graph\n");
+        write_note(&root, "10_Projects/gamma.md", "# Gamma\nsoil [[beta]]\n");
+
+        let first = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        assert_eq!((first.reused, first.rebuilt), (0, 3));
+
+        let second = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        assert_eq!((second.reused, second.rebuilt), (3, 0));
+
+        fs::remove_file(root.join("10_Projects/gamma.md")).unwrap();
+        write_note(
+            &root,
+            "10_Projects/alpha.md",
+            "# Alpha\nrust rust [[beta]]\n",
+        );
+        let third = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        assert_eq!((third.reused, third.rebuilt), (1, 1));
+
+        let graph = load_graph(&third.graph_path).unwrap();
+        assert!(graph.nodes.iter().all(|n| n.id != "10_Projects/gamma.md"));
+        assert!(graph.edges.iter().all(|e| e.src != "10_Projects/gamma.md"));
+        assert_eq!(graph.total_docs, 2);
+        assert_eq!(graph.doc_freq.get("rust"), Some(&2));
+        assert_eq!(graph.doc_freq.get("soil"), None);
+
+        let forced = index_graph_data(
+            &root,
+            &out,
+            &IndexOptions {
+                force: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!((forced.reused, forced.rebuilt), (0, 2));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn strip_code_blocks_keeps_prose_and_yaml() {
+        let root = temp_root("strip-code");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "30_Resources/snippet.md",
+            "# Parser\nprose about tokenizers and `inlinevar` names\n\n```rust\nfn main() { let zzvariable = 1; }\n```\n\n```yaml\nrelations: keptyaml\n```\n",
+        );
+        let semantic = |strip_code: Option<CodeStripping>| {
+            let options = IndexOptions {
+                force: true,
+                strip_code,
+                ..Default::default()
+            };
+            let result = index_graph_data(&root, &out, &options).unwrap();
+            let graph = load_graph(&result.graph_path).unwrap();
+            graph.nodes[0].semantic.clone()
+        };
+        assert!(semantic(None).contains_key("zzvariable"));
+
+        let stripped = semantic(Some(CodeStripping {
+            keep_fences: BTreeSet::from(["yaml".to_string()]),
+            inline: true,
+        }));
+        for gone in ["zzvariable", "fn", "inlinevar"] {
+            assert!(!stripped.contains_key(gone), "{} should be stripped", gone);
+        }
+        for kept in ["prose", "tokenizers", "keptyaml"] {
+            assert!(stripped.contains_key(kept), "{} should remain", kept);
+        }
+        let text = fs::read_to_string(root.join("30_Resources/snippet.md")).unwrap();
+        assert!(text.contains("zzvariable"));
+    }
+
+    #[test]
+    fn word_count_is_stored_and_summed_in_stats() {
+        let root = temp_root("word-count");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "10_Projects/a.md",
+            "---\ntags: [ignored words here]\n---\n# Release plan\nShip the new parser on Friday.\n",
+        );
+        write_note(&root, "10_Projects/b.md", "# B\none two three\n");
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let words = |id: &str| graph.nodes.iter().find(|n| n.id == id).unwrap().word_count;
+        assert_eq!(words("10_Projects/a.md"), 8);
+        assert_eq!(words("10_Projects/b.md"), 4);
+
+        let report = graph_stats(&graph, 5);
+        assert_eq!(report.total_words, 12);
+        assert!((report.avg_words - 6.0).abs() < 1e-9);
+
+        let weights = RecallWeights {
+            lexical: 1.0,
+            graph: 0.0,
+            semantic: 0.0,
+        };
+        let options = RecallOptions {
+            reading_time: true,
+            ..RecallOptions::default()
+        };
+        let rows = recall_from_graph(&graph, "release", 1, &weights, &options);
+        assert_eq!(rows[0].reading_minutes, Some(8.0 / WORDS_PER_MINUTE));
+    }
+
+    #[test]
+    fn index_appends_history_records() {
+        let root = temp_root("history");
+        let out = root.join(".neural");
+        write_note(&root, "10_Projects/a.md", "# A\n[[missing]]\n");
+        index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        write_note(&root, "10_Projects/b.md", "# B\n[[a]]\n");
+        index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+
+        let records = load_history(&out.join(HISTORY_FILE)).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[0].notes, records[1].notes), (1, 2));
+        assert!(records[1].nodes > records[0].nodes);
+        assert!(records[1].edges > records[0].edges);
+        assert_eq!(records[1].ghost_nodes, 1);
+        assert_eq!(sparkline(&[2, 3, 9]), "▁▂█");
+    }
+
+    #[test]
+    fn chunk_by_heading_creates_section_nodes() {
+        let root = temp_root("chunks");
+        let out = root.join(".neural");
+        write_note(&root, "20_Areas/Runbook.md", "# Runbook\nOverview\n## Paging\nEscalate via [[Oncall]]\n### Disk Full!\nPrune logs\n```\n## not a heading\n```\n");
+        write_note(&root, "20_Areas/Oncall.md", "# Oncall\n");
+        let options = IndexOptions {
+            chunk_by_heading: true,
+            ..Default::default()
+        };
+        let result = index_graph_data(&root, &out, &options).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert!(ids.contains(&"20_Areas/Runbook.md"));
+        assert!(ids.contains(&"20_Areas/Runbook.md#paging"));
+        assert!(ids.contains(&"20_Areas/Runbook.md#disk-full"));
+        assert_eq!(result.nodes, 4);
+        let contains: Vec<&Edge> = graph
+            .edges
+            .iter()
+            .filter(|e| e.kind == "CONTAINS")
+            .collect();
+        assert_eq!(contains.len(), 2);
+        assert!(contains.iter().all(|e| e.src == "20_Areas/Runbook.md"));
+        assert!(graph
+            .edges
+            .iter()
+            .any(|e| e.src == "20_Areas/Runbook.md#paging"
+                && e.dst == "20_Areas/Oncall.md"
+                && e.kind == "WIKILINK"));
+        let paging = graph
+            .nodes
+            .iter()
+            .find(|n| n.id == "20_Areas/Runbook.md#paging")
+            .unwrap();
+        assert_eq!(paging.title, "Paging");
+        assert_eq!(paging.path.as_deref(), Some("20_Areas/Runbook.md"));
+        assert!(paging.semantic.contains_key("escalate"));
+        assert!(!paging.semantic.contains_key("prune"));
+        let runbook = graph
+            .nodes
+            .iter()
+            .find(|n| n.id == "20_Areas/Runbook.md")
+            .unwrap();
+        assert_eq!(runbook.word_count, 13);
+        let report = graph_stats(&graph, 5);
+        assert_eq!(report.total_words, 14);
+        assert!((report.avg_words - 7.0).abs() < 1e-9);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn chunked_notes_reuse_the_manifest() {
+        let root = temp_root("chunk-reuse");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "20_Areas/Runbook.md",
+            "# Runbook\n## Paging\nEscalate via [[Oncall]]\n",
+        );
+        write_note(&root, "20_Areas/Oncall.md", "# Oncall\n");
+        let options = IndexOptions {
+            chunk_by_heading: true,
+            ..Default::default()
+        };
+        index_graph_data(&root, &out, &options).unwrap();
+        let second = index_graph_data(&root, &out, &options).unwrap();
+        assert_eq!((second.reused, second.rebuilt), (2, 0));
+        let graph = load_graph(&second.graph_path).unwrap();
+        assert!(graph
+            .edges
+            .iter()
+            .any(|e| e.src == "20_Areas/Runbook.md#paging" && e.dst == "20_Areas/Oncall.md"));
+        assert_eq!(graph.nodes.len(), 3);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn repeated_links_collapse_into_one_weighted_edge() {
+        let root = temp_root("dedup");
+        let out = root.join(".neural");
+        write_note(&root, "20_Areas/kafka.md", "# Kafka\nBroker notes\n");
+        write_note(
+            &root,
+            "10_Projects/lag.md",
+            "# Lag\nSee [[kafka]] and again [[kafka|the broker]].\n",
+        );
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let links: Vec<&Edge> = graph
+            .edges
+            .iter()
+            .filter(|e| e.kind == "WIKILINK")
+            .collect();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].dst, "20_Areas/kafka.md");
+        assert_eq!(links[0].weight, Some(2));
+        let indegree = weighted_indegree(&graph, &EdgeWeights::default());
+        assert_eq!(indegree["20_Areas/kafka.md"], 2.0);
+        let keys: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| (&e.src, &e.dst, &e.kind))
+            .collect();
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn heading_links_target_section_chunks() {
+        let root = temp_root("anchors");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "20_Areas/Runbook.md",
+            "# Runbook\n## Disk Full\nPrune logs\n",
+        );
+        write_note(
+            &root,
+            "10_Projects/Outage.md",
+            "# Outage\nSee [[Runbook#Disk Full|the disk steps]] and [[Runbook#Missing]].\n",
+        );
+        let link_targets = |options: &IndexOptions| {
+            let result = index_graph_data(&root, &out, options).unwrap();
+            let graph = load_graph(&result.graph_path).unwrap();
+            graph
+                .edges
+                .iter()
+                .filter(|e| e.src == "10_Projects/Outage.md" && e.kind == "WIKILINK")
+                .map(|e| (e.dst.clone(), e.alias.clone()))
+                .collect::<Vec<_>>()
+        };
+        let chunked = IndexOptions {
+            chunk_by_heading: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            link_targets(&chunked),
+            vec![
+                ("20_Areas/Runbook.md".to_string(), None),
+                (
+                    "20_Areas/Runbook.md#disk-full".to_string(),
+                    Some("the disk steps".to_string())
+                ),
+            ]
+        );
+        let whole = link_targets(&IndexOptions::default());
+        assert_eq!(whole.len(), 1);
+        assert_eq!(whole[0].0, "20_Areas/Runbook.md");
+        assert_eq!(whole[0].1.as_deref(), Some("the disk steps"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn parallel_index_matches_serial_output() {
+        let root = temp_root("parallel");
+        for idx in 0..24 {
+            write_note(
+                &root,
+                &format!("10_Projects/note-{:02}.md", idx),
+                &format!(
+                    "# Note {idx}\nshared topic {idx} [[note-{next:02}]] [[Missing {idx}]]\nREL:DEPENDS_ON(Note {idx}->Note {next})[0.8]\n",
+                    idx = idx,
+                    next = (idx + 1) % 24
+                ),
+            );
+        }
+        let build = |jobs: usize, out: &str| {
+            let options = IndexOptions {
+                force: true,
+                jobs: Some(jobs),
+                ..Default::default()
+            };
+            let result = index_graph_data(&root, &root.join(out), &options).unwrap();
+            fs::read(&result.graph_path).unwrap()
+        };
+        let serial = build(1, "serial");
+        let parallel = build(4, "parallel");
+        let graph: GraphData = serde_json::from_slice(&serial).unwrap();
+        let relations = graph
+            .edges
+            .iter()
+            .filter(|e| e.kind == "DEPENDS_ON")
+            .count();
+        assert_eq!(relations, 24);
+        assert!(
+            serial == parallel,
+            "parallel graph.json differs from serial"
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn max_depth_limits_collection_per_note_folder() {
+        let root = temp_root("depth");
+        let out = root.join(".neural");
+        write_note(&root, "30_Resources/top.md", "# Top\n");
+        write_note(&root, "30_Resources/vendor/nested.md", "# Nested\n");
+        write_note(&root, "30_Resources/vendor/api/deep.md", "# Deep\n");
+        write_note(&root, "10_Projects/plan.md", "# Plan\n");
+
+        let options = IndexOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let result = index_graph_data(&root, &out, &options).unwrap();
+        assert_eq!(result.notes, 2);
+        let graph = load_graph(&result.graph_path).unwrap();
+        let mut ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["10_Projects/plan.md", "30_Resources/top.md"]);
+
+        let unlimited = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        assert_eq!(unlimited.notes, 4);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn exclude_globs_skip_matching_notes() {
+        let root = temp_root("exclude");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "30_Resources/templates/daily.md",
+            "# Daily template\n",
+        );
+        write_note(&root, "30_Resources/guide.md", "# Guide\n");
+
+        let all = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        assert_eq!((all.notes, all.excluded), (2, 0));
+
+        let options = IndexOptions {
+            exclude: build_exclude_set(&["**/templates/**".to_string()]).unwrap(),
+            ..Default::default()
+        };
+        let filtered = index_graph_data(&root, &out, &options).unwrap();
+        assert_eq!((filtered.notes, filtered.excluded), (1, 1));
+        let graph = load_graph(&filtered.graph_path).unwrap();
+        assert!(graph.nodes.iter().all(|n| !n.id.contains("templates")));
+        assert!(build_exclude_set(&["[".to_string()]).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn infer_related_links_similar_notes_only() {
+        let root = temp_root("related");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "10_Projects/kafka.md",
+            "# Kafka\nkafka consumer lag partition rebalance\n",
+        );
+        write_note(
+            &root,
+            "10_Projects/lag.md",
+            "# Lag\nconsumer lag partition rebalance alerts\n",
+        );
+        write_note(
+            &root,
+            "20_Areas/garden.md",
+            "# Garden\ntomato seedlings compost watering\n",
+        );
+        write_note(
+            &root,
+            "20_Areas/recipes.md",
+            "# Recipes\nsourdough starter hydration oven\n",
+        );
+        let options = IndexOptions {
+            related: Some(RelatedOptions {
+                threshold: 0.3,
+                top_n: 5,
+            }),
+            ..Default::default()
+        };
+        let result = index_graph_data(&root, &out, &options).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let inferred: Vec<&Edge> = graph
+            .edges
+            .iter()
+            .filter(|e| e.kind == "INFERRED_RELATED")
+            .collect();
+        assert!(inferred
+            .iter()
+            .any(|e| e.src == "10_Projects/kafka.md" && e.dst == "10_Projects/lag.md"));
+        assert!(inferred.iter().all(|e| e.confidence.unwrap() >= 0.3));
+        assert!(!inferred
+            .iter()
+            .any(|e| e.src == "20_Areas/garden.md" && e.dst == "20_Areas/recipes.md"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn infer_related_links_every_near_duplicate() {
+        let node_map: BTreeMap<String, Node> = [
+            semantic_node("a.md", &[("kafka", 2.0), ("lag", 1.0)]),
+            semantic_node("b.md", &[("kafka", 2.0), ("lag", 1.2)]),
+            semantic_node("c.md", &[("kafka", 2.2), ("lag", 1.0)]),
+            semantic_node("d.md", &[("sourdough", 3.0)]),
+        ]
+        .into_iter()
+        .map(|node| (node.id.clone(), node))
+        .collect();
+        let options = RelatedOptions {
+            threshold: 0.9,
+            top_n: 5,
+        };
+        let mut pairs: Vec<(String, String)> = inferred_related_edges(&node_map, &options)
+            .into_iter()
+            .map(|e| (e.src, e.dst))
+            .collect();
+        pairs.sort();
+        let expected: Vec<(String, String)> = [
+            ("a.md", "b.md"),
+            ("a.md", "c.md"),
+            ("b.md", "a.md"),
+            ("b.md", "c.md"),
+            ("c.md", "a.md"),
+            ("c.md", "b.md"),
+        ]
+        .iter()
+        .map(|(src, dst)| (src.to_string(), dst.to_string()))
+        .collect();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn inspect_lists_heaviest_tokens_first() {
+        let root = temp_root("inspect");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "10_Projects/lang.md",
+            "# Notes\nrust rust rust rust borrow checker and cargo\n",
+        );
+        write_note(
+            &root,
+            "10_Projects/other.md",
+            "# Other\ncargo and gardening\n",
+        );
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let node = require_node(&graph, "lang").unwrap();
+        let tokens = top_tokens_of(node, 2);
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token, "rust");
+        assert!(tokens[0].weight > tokens[1].weight);
+        assert_eq!(top_tokens_of(node, 0).len(), node.semantic.len());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn ghost_modes_shape_unresolved_links() {
+        let root = temp_root("ghost-modes");
+        write_note(
+            &root,
+            "10_Projects/a.md",
+            "# Alpha\nSee [[b]], [[missing]] and [[gone]].\n",
+        );
+        write_note(&root, "10_Projects/b.md", "# Beta\n");
+        let counts = |ghosts: GhostMode| {
+            let out = root.join(format!(".neural-{}", ghosts));
+            let options = IndexOptions {
+                ghosts,
+                ..IndexOptions::default()
+            };
+            let result = index_graph_data(&root, &out, &options).unwrap();
+            let graph = load_graph(&result.graph_path).unwrap();
+            let unresolved = graph
+                .edges
+                .iter()
+                .filter(|e| e.kind == "UNRESOLVED_LINK")
+                .count();
+            (graph, unresolved)
+        };
+
+        let (graph, unresolved) = counts(GhostMode::Keep);
+        assert_eq!(
+            (graph.nodes.len(), graph.edges.len(), unresolved),
+            (4, 3, 2)
+        );
+
+        let (graph, unresolved) = counts(GhostMode::Drop);
+        assert_eq!(
+            (graph.nodes.len(), graph.edges.len(), unresolved),
+            (2, 1, 0)
+        );
+
+        let (graph, unresolved) = counts(GhostMode::EdgesOnly);
+        assert_eq!(
+            (graph.nodes.len(), graph.edges.len(), unresolved),
+            (2, 3, 2)
+        );
+        assert!(graph.edges.iter().any(|e| e.dst == "ghost/missing"));
+        let report = graph_stats(&graph, 5);
+        assert_eq!(
+            (report.nodes, report.ghost_nodes, report.orphans),
+            (2, 0, 0)
+        );
+        let loaded = LoadedGraph::new(graph);
+        let rows = loaded.recall(
+            "alpha",
+            5,
+            &RecallProfile::Balanced.weights(),
+            &RecallOptions::default(),
+        );
+        assert_eq!(rows[0].path.as_deref(), Some("10_Projects/a.md"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn index_merges_vaults_with_prefixes() {
+        let base = temp_root("vaults");
+        let work = base.join("work");
+        let personal = base.join("personal");
+        write_note(&work, "10_Projects/plan.md", "# Plan\nSee [[journal]]\n");
+        write_note(
+            &personal,
+            "20_Areas/journal.md",
+            "# Journal\nSee [[plan]]\n",
+        );
+        let out = base.join(".neural");
+        let roots = vec![work.clone(), personal.clone()];
+
+        let result = index_vaults(&roots, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert!(ids.contains(&"work/10_Projects/plan.md"));
+        assert!(ids.contains(&"personal/20_Areas/journal.md"));
+        assert_eq!(graph.notes_root.len(), 2);
+        assert_eq!(
+            graph.note_file("personal/20_Areas/journal.md"),
+            personal.join("20_Areas/journal.md")
+        );
+        assert!(graph.edges.iter().all(|e| e.kind == "UNRESOLVED_LINK"));
+
+        let options = IndexOptions {
+            cross_link: true,
+            ..Default::default()
+        };
+        let result = index_vaults(&roots, &out, &options).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        assert!(graph
+            .edges
+            .iter()
+            .any(|e| e.src == "work/10_Projects/plan.md"
+                && e.dst == "personal/20_Areas/journal.md"
+                && e.kind == "WIKILINK"));
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn legacy_single_notes_root_still_loads() {
+        let graph: GraphData = serde_json::from_str(
+            r#"{"notes_root": "/vault", "nodes": [], "edges": [], "stats": {"notes": 0, "nodes": 0, "edges": 0}}"#,
+        )
+        .unwrap();
+        assert_eq!(graph.notes_root, vec!["/vault"]);
+    }
+
+    #[test]
+    fn tags_are_counted_case_insensitively() {
+        let root = temp_root("tags");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "10_Projects/a.md",
+            "---\ntags: [Rust, cli]\n---\n# A\n",
+        );
+        write_note(&root, "10_Projects/b.md", "---\ntags: [rust]\n---\n# B\n");
+        write_note(&root, "20_Areas/c.md", "# C\nuntagged\n");
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+
+        let counts = tag_counts(&graph);
+        assert_eq!(counts.get("rust"), Some(&2));
+        assert_eq!(counts.get("cli"), Some(&1));
+        assert_eq!(counts.len(), 2);
+        let mut notes = notes_with_tag(&graph, "RUST");
+        notes.sort();
+        assert_eq!(notes, vec!["10_Projects/a.md", "10_Projects/b.md"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn frontmatter_tags_land_on_node_not_semantic() {
+        let root = temp_root("frontmatter");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "20_Areas/ops.md",
+            "---\ntags: [reliability, \"#oncall\"]\naliases:\n  - Runbook\nstatus: draft\n---\n# Ops\nPaging policy\n",
+        );
+        write_note(&root, "10_Projects/plan.md", "# Plan\nSee [[runbook]]\n");
+
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let ops = graph
+            .nodes
+            .iter()
+            .find(|n| n.id == "20_Areas/ops.md")
+            .unwrap();
+        assert_eq!(ops.tags, vec!["reliability", "oncall"]);
+        assert_eq!(ops.aliases, vec!["Runbook"]);
+        assert_eq!(ops.status.as_deref(), Some("draft"));
+        for token in ["tags", "reliability", "status", "draft", "aliases"] {
+            assert!(!ops.semantic.contains_key(token), "{} leaked", token);
+        }
+        assert!(ops.semantic.contains_key("paging"));
+        assert!(graph
+            .edges
+            .iter()
+            .any(|e| e.src == "10_Projects/plan.md" && e.dst == "20_Areas/ops.md"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn search_matches_substring_and_regex() {
+        let root = temp_root("search");
+        write_note(
+            &root,
+            "30_Resources/db.md",
+            "# DB\nGraph Database notes\nplain line\nsee graph.db\n",
+        );
+        write_note(&root, "10_Projects/x.md", "# X\nnothing here\n");
+
+        let literal = build_search_regex("graph.db", false, false).unwrap();
+        let hits = search_notes(
+            &root,
+            &literal,
+            1,
+            None,
+            &GlobSet::empty(),
+            &NoteExtensions::default(),
+        )
+        .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "30_Resources/db.md");
+        assert_eq!(hits[0].line, 4);
+        assert_eq!(hits[0].before, vec![(3, "plain line".to_string())]);
+
+        let case_sensitive = build_search_regex("graph database", false, false).unwrap();
+        assert!(search_notes(
+            &root,
+            &case_sensitive,
+            0,
+            None,
+            &GlobSet::empty(),
+            &NoteExtensions::default()
+        )
+        .unwrap()
+        .is_empty());
+        let relaxed = build_search_regex("graph database", false, true).unwrap();
+        assert_eq!(
+            search_notes(
+                &root,
+                &relaxed,
+                0,
+                None,
+                &GlobSet::empty(),
+                &NoteExtensions::default()
+            )
+            .unwrap()
+            .len(),
+            1
+        );
+
+        let pattern = build_search_regex(r"^#\s\w+$", true, false).unwrap();
+        assert_eq!(
+            search_notes(
+                &root,
+                &pattern,
+                0,
+                None,
+                &GlobSet::empty(),
+                &NoteExtensions::default()
+            )
+            .unwrap()
+            .len(),
+            2
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn edge(src: &str, dst: &str, kind: &str) -> Edge {
+        Edge {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            kind: kind.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn captured_relations_become_typed_edges() {
+        let root = temp_root("relations");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "20_Areas/Latency Spike.md",
+            "# Latency Spike\np99 jumped\n",
+        );
+        let target = root.join(INBOX_DIR).join("capture.md");
+        run_capture(
+            &root,
+            &target,
+            "REL:CAUSED_BY(API Timeout->Latency Spike)[0.82] seen in prod",
+            &CaptureOptions::default(),
+        )
+        .unwrap();
+
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let typed: Vec<&Edge> = graph
+            .edges
+            .iter()
+            .filter(|e| e.kind == "CAUSED_BY")
+            .collect();
+        assert_eq!(typed.len(), 1);
+        assert_eq!(typed[0].src, "ghost/API Timeout");
+        assert_eq!(typed[0].dst, "20_Areas/Latency Spike.md");
+        assert_eq!(typed[0].confidence, Some(0.82));
+
+        let boosted = weighted_indegree(&graph, &EdgeWeights::parse(3.0, &[]).unwrap());
+        assert_eq!(boosted.get("20_Areas/Latency Spike.md"), Some(&3.0));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn relation_weight_leaves_inferred_edges_alone() {
+        let graph = graph_of(
+            vec![
+                counted_node("a.md", "A", &[]),
+                counted_node("b.md", "B", &[]),
+                counted_node("c.md", "C", &[]),
+            ],
+            vec![
+                edge("a.md", "b.md", "INFERRED_RELATED"),
+                edge("a.md", "c.md", "CAUSED_BY"),
+            ],
+        );
+        let boosted = weighted_indegree(&graph, &EdgeWeights::parse(3.0, &[]).unwrap());
+        assert_eq!(boosted.get("b.md"), Some(&1.0));
+        assert_eq!(boosted.get("c.md"), Some(&3.0));
+    }
+
+    #[test]
+    fn alternate_grammar_captures_reach_the_index() {
+        let root = temp_root("relations-arrow");
+        let out = root.join(".neural");
+        write_note(&root, "20_Areas/Outage.md", "# Outage\n");
+        write_note(&root, "20_Areas/Deploy.md", "# Deploy\n");
+        let target = root.join(INBOX_DIR).join("capture.md");
+        let options = CaptureOptions {
+            relation_regex: Some(relation_regex(None, RelationGrammar::Arrow).unwrap()),
+            ..Default::default()
+        };
+        run_capture(
+            &root,
+            &target,
+            "Postmortem notes\n- Deploy -> Outage (CAUSED_BY, 0.7)\n",
+            &options,
+        )
+        .unwrap();
+        let written = fs::read_to_string(&target).unwrap();
+        assert!(written.contains("Postmortem notes\nREL:CAUSED_BY(Deploy -> Outage)[0.7]\n"));
+
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let typed: Vec<(&str, &str, Option<f64>)> = graph
+            .edges
+            .iter()
+            .filter(|e| e.kind == "CAUSED_BY")
+            .map(|e| (e.src.as_str(), e.dst.as_str(), e.confidence))
+            .collect();
+        assert_eq!(
+            typed,
+            vec![("20_Areas/Deploy.md", "20_Areas/Outage.md", Some(0.7))]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn spread_lifts_neighbours_of_strong_matches() {
+        let mut link = edge("a.md", "b.md", "WIKILINK");
+        link.confidence = Some(0.8);
+        let graph = graph_of(
+            vec![
+                counted_node("a.md", "kafka lag", &[]),
+                counted_node("b.md", "consumer", &[]),
+                counted_node("c.md", "unrelated", &[]),
+            ],
+            vec![link, edge("b.md", "c.md", "WIKILINK")],
+        );
+        let weights = RecallWeights {
+            lexical: 1.0,
+            graph: 0.0,
+            semantic: 0.0,
+        };
+        let base = recall_from_graph(&graph, "kafka", 10, &weights, &RecallOptions::default());
+        assert_eq!(base.len(), 1);
+
+        let options = RecallOptions {
+            spread: 1,
+            ..Default::default()
+        };
+        let rows = recall_from_graph(&graph, "kafka", 10, &weights, &options);
+        let scores: Vec<(&str, f64)> = rows.iter().map(|r| (r.title.as_str(), r.score)).collect();
+        assert_eq!(scores.len(), 2);
+        assert_eq!(scores[1].0, "consumer");
+        assert!((scores[1].1 - 2.0 * 0.5 * 0.8).abs() < 1e-9);
+
+        let two_hops = RecallOptions {
+            spread: 2,
+            ..Default::default()
+        };
+        let rows = recall_from_graph(&graph, "kafka", 10, &weights, &two_hops);
+        assert!(rows.iter().any(|row| row.title == "unrelated"));
+    }
+
+    #[test]
+    fn edge_weights_can_silence_ghost_links() {
+        let graph = graph_of(
+            vec![
+                counted_node("a.md", "A", &[]),
+                counted_node("b.md", "B", &[]),
+                counted_node("linked.md", "Linked", &[]),
+                counted_node("ghosted.md", "Ghosted", &[]),
+            ],
+            vec![
+                edge("a.md", "linked.md", "WIKILINK"),
+                edge("a.md", "ghosted.md", "UNRESOLVED_LINK"),
+                edge("b.md", "ghosted.md", "UNRESOLVED_LINK"),
+            ],
+        );
+        let weights = RecallWeights {
+            lexical: 0.0,
+            graph: 1.0,
+            semantic: 0.0,
+        };
+        let rows = recall_from_graph(&graph, "anything", 10, &weights, &RecallOptions::default());
+        assert_eq!(rows[0].title, "Ghosted");
+
+        let options = RecallOptions {
+            edge_weights: EdgeWeights::parse(1.0, &["UNRESOLVED_LINK=0".to_string()]).unwrap(),
+            ..Default::default()
+        };
+        let rows = recall_from_graph(&graph, "anything", 10, &weights, &options);
+        let titles: Vec<&str> = rows.iter().map(|row| row.title.as_str()).collect();
+        assert_eq!(titles, vec!["Linked"]);
+        assert!(EdgeWeights::parse(1.0, &["WIKILINK".to_string()]).is_err());
+    }
+
+    #[test]
+    fn related_prefers_linked_similar_notes() {
+        let graph = graph_of(
+            vec![
+                semantic_node("kafka.md", &[("kafka", 2.0), ("lag", 1.0)]),
+                semantic_node("consumer.md", &[("kafka", 1.0), ("consumer", 1.0)]),
+                semantic_node("bridge.md", &[("misc", 1.0)]),
+                semantic_node("garden.md", &[("tomato", 1.0)]),
+                semantic_node("loner.md", &[("kafka", 0.1), ("soil", 3.0)]),
+            ],
+            vec![
+                edge("kafka.md", "consumer.md", "WIKILINK"),
+                edge("consumer.md", "bridge.md", "WIKILINK"),
+                edge("garden.md", "bridge.md", "WIKILINK"),
+            ],
+        );
+        let target = require_node(&graph, "kafka").unwrap();
+        let weights = RecallWeights {
+            lexical: 0.0,
+            graph: 1.0,
+            semantic: 1.0,
+        };
+        let rows = related_notes(&graph, target, 10, &weights);
+        let order: Vec<&str> = rows.iter().filter_map(|r| r.path.as_deref()).collect();
+        assert_eq!(order[0], "consumer.md");
+        let garden = order.iter().position(|p| *p == "garden.md").unwrap();
+        assert!(garden > 0);
+        assert!(!order.contains(&"kafka.md"));
+        assert_eq!(hop_distances(&graph, "kafka.md")["garden.md"], 3);
+    }
+
+    #[test]
+    fn missing_graph_and_note_are_typed_errors() {
+        let root = temp_root("typed-errors");
+        let path = root.join(".neural/graph.json");
+        let Err(err) = require_graph(&path) else {
+            panic!("expected a missing graph");
+        };
+        assert!(matches!(&err, ExomError::GraphNotFound { path: missing } if *missing == path));
+        assert!(err.to_string().contains("Run `exom index` first"));
+
+        // Typed errors survive the trip through `anyhow` callers.
+        let wrapped: anyhow::Error = err.into();
+        assert!(matches!(
+            wrapped.downcast_ref::<ExomError>(),
+            Some(ExomError::GraphNotFound { .. })
+        ));
+
+        let graph = graph_of(vec![counted_node("a.md", "A", &[])], vec![]);
+        assert!(matches!(
+            require_node(&graph, "missing"),
+            Err(ExomError::NoteNotFound { key }) if key == "missing"
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn index_and_lifecycle_failures_are_typed_errors() {
+        let root = temp_root("typed-run-errors");
+        write_note(&root, "10_Projects/a.md", "# A\n");
+        // A file where the output folder should go makes the write fail.
+        let out = write_note(&root, "out", "");
+        let Err(err) = index_graph_data(&root, &out, &IndexOptions::default()) else {
+            panic!("expected an index failure");
+        };
+        assert!(matches!(&err, ExomError::Io { path, .. } if *path == out));
+        assert!(std::error::Error::source(&err).is_some());
+
+        write_note(&root, ARCHIVE_LOG_FILE, "not json\n");
+        let Err(err) = run_lifecycle(
+            &root,
+            LifecycleMode::Unarchive,
+            &LifecycleOptions::default(),
+        ) else {
+            panic!("expected a lifecycle failure");
+        };
+        assert!(matches!(
+            &err,
+            ExomError::Parse { path, line: Some(1), .. } if *path == root.join(ARCHIVE_LOG_FILE)
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn unversioned_graph_asks_for_rebuild() {
+        let root = temp_root("schema");
+        let path = root.join("graph.json");
+        fs::write(
+            &path,
+            r#"{"notes_root": ".", "nodes": [{"id": "a.md", "title": "A", "stem": "a"}], "edges": []}"#,
+        )
+        .unwrap();
+        let err = load_graph(&path).err().unwrap();
+        assert!(matches!(err, ExomError::Schema { found: 0, .. }));
+        let message = err.to_string();
+        assert!(message.contains("schema v0"), "{}", message);
+        assert!(message.contains("run `exom index` to rebuild"));
+
+        fs::write(
+            &path,
+            r#"{"version": 1, "notes_root": ".", "nodes": "oops"}"#,
+        )
+        .unwrap();
+        let message = format!("{:#}", load_graph(&path).err().unwrap());
+        assert!(message.contains("does not match schema v1"), "{}", message);
+
+        let out = root.join(".neural");
+        write_note(&root, "10_Projects/a.md", "# A\n");
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        assert_eq!(
+            load_graph(&result.graph_path).unwrap().version,
+            GRAPH_SCHEMA_VERSION
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn backlinks_sorted_by_referrer_indegree() {
+        let graph = graph_of(
+            vec![
+                counted_node("hub.md", "Hub", &[]),
+                counted_node("a.md", "A", &[]),
+                counted_node("b.md", "B", &[]),
+                counted_node("c.md", "C", &[]),
+            ],
+            vec![
+                edge("a.md", "hub.md", "WIKILINK"),
+                edge("b.md", "hub.md", "CAUSED_BY"),
+                edge("c.md", "b.md", "WIKILINK"),
+                edge("hub.md", "b.md", "WIKILINK"),
+                edge("c.md", "a.md", "WIKILINK"),
+            ],
+        );
+        let target = require_node(&graph, "hub").unwrap();
+        let links = backlinks_for(&graph, &target.id);
+        let order: Vec<(&str, &str)> = links
+            .iter()
+            .map(|l| (l.src.as_str(), l.kind.as_str()))
+            .collect();
+        assert_eq!(order, vec![("b.md", "CAUSED_BY"), ("a.md", "WIKILINK")]);
+        assert!(require_node(&graph, "missing").is_err());
+    }
+
+    #[test]
+    fn siblings_share_a_linking_hub() {
+        let graph = graph_of(
+            vec![
+                counted_node("hub.md", "Hub", &[]),
+                counted_node("index.md", "Index", &[]),
+                counted_node("a.md", "A", &[]),
+                counted_node("b.md", "B", &[]),
+                counted_node("c.md", "C", &[]),
+                counted_node("d.md", "D", &[]),
+                counted_node("e.md", "E", &[]),
+                counted_node("a.md#one", "One", &[]),
+                counted_node("a.md#two", "Two", &[]),
+            ],
+            vec![
+                edge("hub.md", "a.md", "WIKILINK"),
+                edge("hub.md", "b.md", "WIKILINK"),
+                edge("hub.md", "c.md", "WIKILINK"),
+                edge("hub.md", "c.md", "RELATED_TO"),
+                edge("a.md", "d.md", "WIKILINK"),
+                edge("e.md", "a.md", "INFERRED_RELATED"),
+                edge("e.md", "d.md", "INFERRED_RELATED"),
+                edge("a.md", "a.md#one", "CONTAINS"),
+                edge("a.md", "a.md#two", "CONTAINS"),
+            ],
+        );
+        let siblings = sibling_notes(&graph, "a.md", 0);
+        let found: Vec<(&str, usize)> = siblings
+            .iter()
+            .map(|s| (s.id.as_str(), s.shared_parents))
+            .collect();
+        assert_eq!(found, vec![("b.md", 1), ("c.md", 1)]);
+        assert!(sibling_notes(&graph, "a.md#one", 0).is_empty());
+
+        let mut graph = graph;
+        graph.edges.push(edge("index.md", "c.md", "WIKILINK"));
+        graph.edges.push(edge("index.md", "a.md", "WIKILINK"));
+        let siblings = sibling_notes(&graph, "a.md", 1);
+        assert_eq!(siblings.len(), 1);
+        assert_eq!(
+            (siblings[0].id.as_str(), siblings[0].shared_parents),
+            ("c.md", 2)
+        );
+    }
+
+    #[test]
+    fn link_alias_is_stored_and_shown_in_backlinks() {
+        let root = temp_root("alias");
+        let out = root.join(".neural");
+        write_note(&root, "10_Projects/hub.md", "# Hub\n");
+        write_note(
+            &root,
+            "10_Projects/a.md",
+            "# A\nSee [[hub|Friendly Name]].\n",
+        );
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let link = graph
+            .edges
+            .iter()
+            .find(|e| e.src == "10_Projects/a.md" && e.dst == "10_Projects/hub.md")
+            .unwrap();
+        assert_eq!(link.alias.as_deref(), Some("Friendly Name"));
+
+        let target = require_node(&graph, "hub").unwrap();
+        let links = backlinks_for(&graph, &target.id);
+        assert_eq!(links[0].alias.as_deref(), Some("Friendly Name"));
+        let mut text = Vec::new();
+        write_backlinks(&mut text, target, &links).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(
+            text.contains("- 10_Projects/a.md as \"Friendly Name\" (title \"Hub\")"),
+            "{}",
+            text
+        );
+
+        let weights = RecallWeights {
+            lexical: 0.0,
+            graph: 1.0,
+            semantic: 0.0,
+        };
+        let related = related_notes(&graph, target, 5, &weights);
+        assert_eq!(related[0].alias.as_deref(), Some("Friendly Name"));
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn compact_graph_round_trips_smaller() {
+        let root = temp_root("compact");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "10_Projects/a.md",
+            "# Alpha\nparser release notes [[b]]\n",
+        );
+        write_note(
+            &root,
+            "10_Projects/b.md",
+            "# Beta\nrelease checklist for the parser\n",
+        );
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let pretty = fs::metadata(&result.graph_path).unwrap().len();
+
+        let mut graph = load_graph(&result.graph_path).unwrap();
+        compact_graph(&mut graph, false, true);
+        let quantized = out.join("quantized.json");
+        write_atomic(&quantized, serde_json::to_string(&graph).unwrap()).unwrap();
+        assert!(fs::metadata(&quantized).unwrap().len() < pretty);
+        let reloaded = load_graph(&quantized).unwrap();
+        assert_eq!(reloaded.nodes.len(), graph.nodes.len());
+        assert!(reloaded.nodes.iter().any(|n| !n.semantic.is_empty()));
+
+        let mut graph = load_graph(&result.graph_path).unwrap();
+        compact_graph(&mut graph, true, false);
+        let bare = out.join("bare.json");
+        write_atomic(&bare, serde_json::to_string(&graph).unwrap()).unwrap();
+        assert!(fs::metadata(&bare).unwrap().len() < fs::metadata(&quantized).unwrap().len());
+        let reloaded = load_graph(&bare).unwrap();
+        assert!(reloaded.nodes.iter().all(|n| n.semantic.is_empty()));
+        assert_eq!(reloaded.edges.len(), graph.edges.len());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    fn age_file(path: &Path, days: u64) {
+        let when = SystemTime::now() - StdDuration::from_secs(days * 86_400);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(when)
+            .unwrap();
+    }
+
+    #[test]
+    fn since_filters_out_stale_notes() {
+        let root = temp_root("since");
+        let out = root.join(".neural");
+        write_note(&root, "10_Projects/old.md", "# Old\nrollout plan\n");
+        write_note(&root, "10_Projects/new.md", "# New\nrollout plan\n");
+        age_file(&root.join("10_Projects/old.md"), 40);
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+
+        let cutoff = Utc::now().date_naive() - Duration::days(10);
+        let options = RecallOptions {
+            since: Some(cutoff),
+            ..Default::default()
+        };
+        let weights = RecallProfile::Balanced.weights();
+        let rows = recall_from_graph(&graph, "rollout", 5, &weights, &options);
+        let titles: Vec<&str> = rows.iter().map(|row| row.title.as_str()).collect();
+        assert_eq!(titles, vec!["New"]);
+
+        let matcher = build_search_regex("rollout", false, false).unwrap();
+        let hits = search_notes(
+            &root,
+            &matcher,
+            0,
+            Some(cutoff),
+            &GlobSet::empty(),
+            &NoteExtensions::default(),
+        )
+        .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "10_Projects/new.md");
+        assert!(parse_since("2026-13-01").is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn import_validates_and_canonicalizes_graphs() {
+        let root = temp_root("import");
+        write_note(
+            &root,
+            "10_Projects/kafka.md",
+            "# Kafka\nbroker partition notes\n",
+        );
+        let input = root.join("external.json");
+        fs::write(
+            &input,
+            r#"{
+                "nodes": [
+                    {"id": "10_Projects/kafka.md", "path": "10_Projects/kafka.md", "name": "Kafka"},
+                    {"id": "20_Areas/ops.md", "path": "20_Areas/ops.md", "tfidf": {"ops": 1.0}}
+                ],
+                "links": [
+                    {"source": "20_Areas/ops.md", "target": "10_Projects/kafka.md"},
+                    {"source": "20_Areas/ops.md", "target": "ghost/Runbook", "kind": "UNRESOLVED_LINK"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let (graph, report) = import_graph(&input, Some(&root)).unwrap();
+        assert_eq!(graph.version, GRAPH_SCHEMA_VERSION);
+        assert_eq!((report.nodes, report.edges), (3, 2));
+        assert_eq!((report.ghosts_added, report.recomputed), (1, 1));
+        let kafka = graph
+            .nodes
+            .iter()
+            .find(|n| n.id == "10_Projects/kafka.md")
+            .unwrap();
+        assert_eq!(kafka.stem, "kafka");
+        assert!(kafka.semantic.contains_key("broker"));
+        assert!(graph.edges.iter().any(|e| e.kind == "WIKILINK"));
+
+        let out = root.join(".neural/graph.json");
+        write_graph_export(&graph, GraphFormat::Json, &out).unwrap();
+        assert_eq!(load_graph(&out).unwrap().nodes.len(), 3);
+
+        fs::write(
+            &input,
+            r#"{"nodes": [{"id": "a"}, {"id": "a"}], "edges": [{"src": "a", "dst": "missing"}]}"#,
+        )
+        .unwrap();
+        let err = format!("{:#}", import_graph(&input, None).err().unwrap());
+        assert!(err.contains(r#"duplicate node id "a""#), "{}", err);
+        assert!(err.contains(r#"unknown endpoint "missing""#), "{}", err);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn recall_json_goes_to_output_file() {
+        let root = temp_root("output");
+        write_note(&root, "10_Projects/kafka.md", "# Kafka\nbroker notes\n");
+        let options = IndexOptions {
+            graph_file: Some(root.join("graphs/main/kafka.json")),
+            ..Default::default()
+        };
+        let result = index_graph_data(&root, &root.join(".neural"), &options).unwrap();
+        assert_eq!(result.graph_path, root.join("graphs/main/kafka.json"));
+        assert!(!root.join(".neural/graph.json").exists());
+
+        let graph = load_graph(&result.graph_path).unwrap();
+        let rows = recall_from_graph(
+            &graph,
+            "kafka",
+            5,
+            &RecallProfile::Balanced.weights(),
+            &RecallOptions::default(),
+        );
+        let target = root.join("reports/nested/recall.json");
+        let mut out = output_writer(Some(&target)).unwrap();
+        write_json(
+            &mut out,
+            &RecallResponse {
+                query: "kafka".to_string(),
+                top_k: 5,
+                results: rows,
+            },
+        )
+        .unwrap();
+        out.flush().unwrap();
+        drop(out);
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&target).unwrap()).unwrap();
+        assert_eq!(parsed["query"], "kafka");
+        assert_eq!(parsed["results"][0]["path"], "10_Projects/kafka.md");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn prf_recalls_related_notes_missing_the_query_term() {
+        let root = temp_root("prf");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "10_Projects/kafka.md",
+            "# Kafka\nkafka broker partition offsets\n",
+        );
+        write_note(&root, "10_Projects/lag.md", "# Lag\nkafka consumer lag\n");
+        write_note(
+            &root,
+            "20_Areas/offsets.md",
+            "# Offsets\nbroker partition replication\n",
+        );
+        write_note(
+            &root,
+            "20_Areas/garden.md",
+            "# Garden\ntomato soil compost\n",
+        );
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let weights = RecallProfile::Balanced.weights();
+        let paths = |options: &RecallOptions| {
+            recall_from_graph(&graph, "kafka", 10, &weights, options)
+                .into_iter()
+                .filter_map(|row| row.path)
+                .collect::<Vec<_>>()
+        };
+
+        let plain = paths(&RecallOptions::default());
+        assert!(!plain.contains(&"20_Areas/offsets.md".to_string()));
+
+        let options = RecallOptions {
+            prf: Some(PrfOptions {
+                docs: 2,
+                terms: 5,
+                weight: 0.3,
+            }),
+            ..Default::default()
+        };
+        let expanded = paths(&options);
+        assert_eq!(expanded[..2], plain[..2]);
+        assert!(expanded.contains(&"20_Areas/offsets.md".to_string()));
+        assert!(!expanded.contains(&"20_Areas/garden.md".to_string()));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn title_boost_lifts_title_matches_over_body_matches() {
+        let root = temp_root("title-boost");
+        let out = root.join(".neural");
+        write_note(&root, "10_Projects/a.md", "# Kafka\nretention settings\n");
+        write_note(
+            &root,
+            "10_Projects/b.md",
+            "# Notes\nkafka retention kafka settings kafka\n",
+        );
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let semantic_only = RecallWeights {
+            lexical: 0.0,
+            graph: 0.0,
+            semantic: 1.0,
+        };
+        let top = |boost: f64| {
+            let options = RecallOptions {
+                title_boost: boost,
+                ..Default::default()
+            };
+            recall_from_graph(&graph, "kafka", 2, &semantic_only, &options)[0]
+                .path
+                .clone()
+                .unwrap()
+        };
+        assert_eq!(top(1.0), "10_Projects/b.md");
+        assert_eq!(top(3.0), "10_Projects/a.md");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn recency_halflife_prefers_fresh_notes() {
+        let root = temp_root("recency");
+        let out = root.join(".neural");
+        write_note(&root, "10_Projects/a.md", "# Rollout\nrollout plan\n");
+        write_note(&root, "10_Projects/b.md", "# Rollout\nrollout plan\n");
+        age_file(&root.join("10_Projects/a.md"), 30);
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let weights = RecallProfile::Balanced.weights();
+
+        let flat = recall_from_graph(&graph, "rollout", 5, &weights, &RecallOptions::default());
+        assert!((flat[0].score - flat[1].score).abs() < 1e-9);
+
+        let options = RecallOptions {
+            recency_halflife_days: Some(30.0),
+            ..Default::default()
+        };
+        let rows = recall_from_graph(&graph, "rollout", 5, &weights, &options);
+        assert_eq!(rows[0].path.as_deref(), Some("10_Projects/b.md"));
+        let ratio = rows[1].score / rows[0].score;
+        assert!((ratio - 0.5).abs() < 0.01, "ratio {}", ratio);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn ghosts_are_decayed_like_a_half_life_old_note() {
+        let now = Utc::now();
+        let mut ghost = counted_node("ghost/x", "x", &[]);
+        ghost.path = None;
+        assert_eq!(recency_factor(&ghost, 30.0, now), GHOST_RECENCY_FACTOR);
+        let mut fresh = counted_node("a.md", "a", &[]);
+        fresh.modified = Some(now.to_rfc3339());
+        assert_eq!(recency_factor(&fresh, 30.0, now), 1.0);
+        assert_eq!(recency_factor(&ghost, 0.0, now), 1.0);
+    }
+
+    #[test]
+    fn merge_folds_note_and_retargets_links() {
+        let root = temp_root("merge");
+        let out = root.join(".neural");
+        let dup = write_note(
+            &root,
+            "00_Inbox/kafka-lag.md",
+            "# Kafka lag\nConsumer lag notes\n",
+        );
+        let keep = write_note(&root, "10_Projects/kafka.md", "# Kafka\nBroker notes\n");
+        let other = write_note(
+            &root,
+            "20_Areas/ops.md",
+            "# Ops\nSee [[kafka-lag#Alerts|lag alerts]] and [[Kafka-Lag]].\n",
+        );
+
+        let preview = run_merge(
+            &root,
+            "kafka-lag",
+            "10_Projects/kafka.md",
+            &GlobSet::empty(),
+            &NoteExtensions::default(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(preview.links, 2);
+        assert!(dup.exists());
+        assert!(fs::read_to_string(&other)
+            .unwrap()
+            .contains("[[kafka-lag#Alerts"));
+
+        let report = run_merge(
+            &root,
+            "kafka-lag",
+            "kafka",
+            &GlobSet::empty(),
+            &NoteExtensions::default(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            report.archived_to,
+            "99_Archives/Merged/00_Inbox/kafka-lag.md"
+        );
+        assert!(!dup.exists());
+        assert!(root.join(&report.archived_to).exists());
+        assert_eq!(
+            fs::read_to_string(&other).unwrap(),
+            "# Ops\nSee [[kafka#Alerts|lag alerts]] and [[kafka]].\n"
+        );
+        let merged = fs::read_to_string(&keep).unwrap();
+        assert!(merged.starts_with("# Kafka\nBroker notes\n\n---\n"));
+        assert!(merged.contains("Consumer lag notes"));
+
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        assert!(graph
+            .edges
+            .iter()
+            .any(|e| e.src == "20_Areas/ops.md" && e.dst == "10_Projects/kafka.md"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn merge_leaves_links_to_same_stem_notes_alone() {
+        let root = temp_root("merge-stems");
+        write_note(&root, "00_Inbox/lag.md", "# Lag\nInbox lag\n");
+        write_note(&root, "30_Resources/lag.md", "# Lag\nReference lag\n");
+        write_note(&root, "10_Projects/kafka.md", "# Kafka\n");
+        let other = write_note(
+            &root,
+            "20_Areas/ops.md",
+            "[[30_Resources/lag]] [[00_Inbox/lag.md|inbox]] [[lag]]\n",
+        );
+        let report = run_merge(
+            &root,
+            "00_Inbox/lag.md",
+            "kafka",
+            &GlobSet::empty(),
+            &NoteExtensions::default(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(report.links, 1);
+        assert_eq!(
+            fs::read_to_string(&other).unwrap(),
+            "[[30_Resources/lag]] [[10_Projects/kafka|inbox]] [[lag]]\n"
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn stored_index_options_repeat_the_last_build() {
+        let root = temp_root("stored-options");
+        let out = root.join(".neural");
+        write_note(&root, "10_Projects/a.md", "# A\n## Part\n[[missing]]\n");
+        let options = IndexOptions {
+            chunk_by_heading: true,
+            ghosts: GhostMode::Drop,
+            tokens: TokenOptions {
+                stem: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        index_graph_data(&root, &out, &options).unwrap();
+        let stored = stored_index_options(&out, GlobSet::empty(), NoteExtensions::default());
+        assert!(stored.chunk_by_heading);
+        assert!(stored.tokens.stem);
+        assert!(stored.ghosts == GhostMode::Drop);
+        assert_eq!(stored.cache_key(), options.cache_key());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn rename_leaves_links_to_same_stem_notes_alone() {
+        let root = temp_root("rename-stems");
+        write_note(&root, "10_Projects/lag.md", "# Lag\n");
+        write_note(&root, "30_Resources/lag.md", "# Lag reference\n");
+        let other = write_note(
+            &root,
+            "20_Areas/ops.md",
+            "[[30_Resources/lag]] [[10_Projects/lag#Alerts|alerts]] [[lag]]\n",
+        );
+        let report = run_rename(
+            &root,
+            "10_Projects/lag.md",
+            "consumer-lag",
+            &GlobSet::empty(),
+            &NoteExtensions::default(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(report.links, 1);
+        assert_eq!(
+            fs::read_to_string(&other).unwrap(),
+            "[[30_Resources/lag]] [[10_Projects/consumer-lag#Alerts|alerts]] [[lag]]\n"
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn rename_moves_note_and_updates_links() {
+        let root = temp_root("rename");
+        let old = write_note(&root, "10_Projects/kafka.md", "# Kafka\nSee [[kafka]].\n");
+        write_note(&root, "20_Areas/streams.md", "# Streams\n");
+        let referrer = write_note(
+            &root,
+            "20_Areas/ops.md",
+            "# Ops\nSee [[kafka#Lag|lag]] and [[Kafka]].\n",
+        );
+        let rename = |to: &str, dry_run| {
+            run_rename(
+                &root,
+                "kafka",
+                to,
+                &GlobSet::empty(),
+                &NoteExtensions::default(),
+                dry_run,
+            )
+        };
+
+        assert!(rename("Streams", false).is_err());
+        let preview = rename("kafka-brokers", true).unwrap();
+        assert_eq!(preview.links, 3);
+        assert!(old.exists());
+
+        let report = rename("kafka-brokers", false).unwrap();
+        assert_eq!(report.to, "10_Projects/kafka-brokers.md");
+        assert!(!old.exists());
+        assert_eq!(
+            fs::read_to_string(root.join(&report.to)).unwrap(),
+            "# Kafka\nSee [[kafka-brokers]].\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&referrer).unwrap(),
+            "# Ops\nSee [[kafka-brokers#Lag|lag]] and [[kafka-brokers]].\n"
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn consolidate_groups_summary_by_folder_and_tag() {
+        let root = temp_root("consolidate-groups");
+        let notes = [
+            write_note(
+                &root,
+                "00_Inbox/work/standup.md",
+                "---\ntags: [meetings]\n---\n# Standup\n",
+            ),
+            write_note(&root, "00_Inbox/home/plants.md", "# Plants\n"),
+            write_note(
+                &root,
+                "00_Inbox/loose.md",
+                "---\ntags: [ideas]\n---\n# Loose\n",
+            ),
+        ];
+        for note in &notes {
+            age_file(note, 10);
+        }
+        let summary = |group_by: ConsolidateGroup| {
+            let options = LifecycleOptions {
+                group_by,
+                ..Default::default()
+            };
+            let report = run_consolidate(&root, &options).unwrap();
+            fs::read_to_string(report.summary_path.unwrap()).unwrap()
+        };
+
+        let by_folder = summary(ConsolidateGroup::Folder);
+        let home = by_folder.find("### home").unwrap();
+        let work = by_folder.find("### work").unwrap();
+        let other = by_folder.find("### Uncategorized").unwrap();
+        assert!(home < work && work < other);
+        assert!(by_folder[other..].contains("00_Inbox/loose.md"));
+
+        let by_tag = summary(ConsolidateGroup::Tag);
+        assert!(by_tag.contains("### ideas\n\n- 00_Inbox/loose.md"));
+        assert!(by_tag.contains("### meetings\n\n- 00_Inbox/work/standup.md"));
+        assert!(by_tag.contains("### Uncategorized\n\n- 00_Inbox/home/plants.md"));
+
+        assert!(!summary(ConsolidateGroup::None).contains("###"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn consolidate_excerpts_skip_metadata() {
+        let root = temp_root("consolidate-excerpt");
+        let note = write_note(
+            &root,
+            "00_Inbox/capture.md",
+            "---\ntags: [ideas]\n---\n## Capture @2026-01-01\n\nFirst thought.\n\n```yaml\nrelations:\n  - type: causes\n```\n\nSecond thought.\nThird thought.\n<!-- lifecycle last_reviewed=2026-01-01 decay_score=0.100 -->\n",
+        );
+        age_file(&note, 10);
+        let options = LifecycleOptions {
+            excerpt_lines: 2,
+            ..Default::default()
+        };
+        let report = run_consolidate(&root, &options).unwrap();
+        let summary = fs::read_to_string(report.summary_path.unwrap()).unwrap();
+        assert!(
+            summary.contains("\n  > First thought.\n  > Second thought.\n"),
+            "{}",
+            summary
+        );
+        for hidden in ["Third", "relations:", "lifecycle", "tags:"] {
+            assert!(!summary.contains(hidden), "{} leaked: {}", hidden, summary);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn archive_dry_run_leaves_files_in_place() {
+        let root = temp_root("archive-dry");
+        let old = write_note(&root, "00_Inbox/old.md", "# Old\n");
+        let fresh = write_note(&root, "00_Inbox/fresh.md", "# Fresh\n");
+        age_file(&old, 45);
+
+        let options = LifecycleOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let report = run_lifecycle(&root, LifecycleMode::Archive, &options).unwrap();
+        assert_eq!(report.touched, 1);
+        assert_eq!(
+            report.details,
+            vec!["Would move 00_Inbox/old.md -> 99_Archives/Inbox/old.md"]
+        );
+        assert!(old.exists());
+        assert!(fresh.exists());
+        assert!(!root.join(ARCHIVE_INBOX_DIR).exists());
+
+        let decay = run_lifecycle(&root, LifecycleMode::Decay, &options).unwrap();
+        assert_eq!(decay.touched, 1);
+        assert_eq!(fs::read_to_string(&old).unwrap(), "# Old\n");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn archive_logs_moves_made_before_a_failure() {
+        let root = temp_root("archive-partial");
+        for rel in ["00_Inbox/a.md", "00_Inbox/sub/b.md"] {
+            let note = write_note(&root, rel, "# Note\n");
+            age_file(&note, 40);
+        }
+        // A file where `sub/` must be created makes the second move fail.
+        write_note(&root, "99_Archives/Inbox/sub", "");
+        assert!(
+            run_lifecycle(&root, LifecycleMode::Archive, &LifecycleOptions::default()).is_err()
+        );
+        let log = read_archive_log(&root).unwrap();
+        let moves: Vec<(&str, &str)> = log
+            .iter()
+            .map(|m| (m.from.as_str(), m.to.as_str()))
+            .collect();
+        assert_eq!(moves, vec![("00_Inbox/a.md", "99_Archives/Inbox/a.md")]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn unarchive_keeps_skipped_entries_in_log() {
+        let root = temp_root("unarchive-skip");
+        let kept = write_note(&root, "00_Inbox/kept.md", "# Kept\n");
+        let taken = write_note(&root, "00_Inbox/taken.md", "# Taken\n");
+        age_file(&kept, 45);
+        age_file(&taken, 45);
+        let options = LifecycleOptions::default();
+        let archived = run_lifecycle(&root, LifecycleMode::Archive, &options).unwrap();
+        assert_eq!(archived.touched, 2);
+        write_note(&root, "00_Inbox/taken.md", "# New taken\n");
+
+        let restored = run_lifecycle(&root, LifecycleMode::Unarchive, &options).unwrap();
+        assert_eq!((restored.processed, restored.touched), (2, 1));
+        assert!(kept.exists());
+        let log = read_archive_log(&root).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].from, "00_Inbox/taken.md");
+        assert!(root.join(&log[0].to).exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn unarchive_restores_last_archive_run() {
+        let root = temp_root("unarchive");
+        let old = write_note(&root, "00_Inbox/old.md", "# Old\n");
+        age_file(&old, 45);
+        let options = LifecycleOptions::default();
+
+        let archived = run_lifecycle(&root, LifecycleMode::Archive, &options).unwrap();
+        assert_eq!(archived.touched, 1);
+        assert!(!old.exists());
+        assert_eq!(read_archive_log(&root).unwrap().len(), 1);
+
+        let restored = run_lifecycle(&root, LifecycleMode::Unarchive, &options).unwrap();
+        assert_eq!(restored.touched, 1);
+        assert!(old.exists());
+        assert!(!root.join(ARCHIVE_INBOX_DIR).join("old.md").exists());
+        assert!(read_archive_log(&root).unwrap().is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn restore_brings_back_moves_in_date_range() {
+        let root = temp_root("restore");
+        let mut moves = Vec::new();
+        for (name, day) in [("early", 1), ("mid", 10), ("late", 20), ("blocked", 12)] {
+            write_note(
+                &root,
+                &format!("99_Archives/Inbox/{}.md", name),
+                "# Archived\n",
+            );
+            moves.push(ArchiveMove {
+                run_id: format!("run-{}", day),
+                from: format!("00_Inbox/{}.md", name),
+                to: format!("99_Archives/Inbox/{}.md", name),
+                timestamp: format!("2026-03-{:02}T08:00:00+00:00", day),
+            });
+        }
+        write_note(&root, "00_Inbox/blocked.md", "# Newer note in the way\n");
+        append_archive_log(&root, &moves).unwrap();
+
+        let options = LifecycleOptions {
+            restore_from: NaiveDate::from_ymd_opt(2026, 3, 5),
+            restore_to: NaiveDate::from_ymd_opt(2026, 3, 15),
+            ..Default::default()
+        };
+        let report = run_lifecycle(&root, LifecycleMode::Restore, &options).unwrap();
+        assert_eq!((report.processed, report.touched), (2, 1));
+        assert!(root.join("00_Inbox/mid.md").exists());
+        assert!(!root.join("00_Inbox/early.md").exists());
+        assert!(!root.join("00_Inbox/late.md").exists());
+        assert!(report
+            .details
+            .contains(&"Skipped exists 00_Inbox/blocked.md".to_string()));
+
+        let left: Vec<String> = read_archive_log(&root)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.from)
+            .collect();
+        assert_eq!(
+            left,
+            vec![
+                "00_Inbox/early.md",
+                "00_Inbox/late.md",
+                "00_Inbox/blocked.md"
+            ]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn review_queue_orders_by_decay() {
+        let root = temp_root("review");
+        let marked = |title: &str, days: i64, score: f64| {
+            format!(
+                "# {}\n<!-- lifecycle last_reviewed={} decay_score={:.3} -->\n",
+                title,
+                Utc::now().date_naive() - Duration::days(days),
+                score
+            )
+        };
+        write_note(&root, "00_Inbox/mid.md", &marked("Mid", 40, 0.5));
+        write_note(&root, "00_Inbox/high.md", &marked("High", 80, 0.9));
+        write_note(&root, "00_Inbox/tie-old.md", &marked("Tie old", 60, 0.5));
+        write_note(&root, "00_Inbox/unmarked.md", "# Unmarked\n");
+        write_note(&root, "10_Projects/deep.md", &marked("Deep", 200, 1.0));
+        write_note(
+            &root,
+            "99_Archives/Inbox/gone.md",
+            &marked("Gone", 300, 1.0),
+        );
+        let md = NoteExtensions::default();
+
+        let inbox = review_queue(&root, false, false, 0, &md).unwrap();
+        let order: Vec<&str> = inbox.iter().map(|item| item.path.as_str()).collect();
+        assert_eq!(
+            order,
+            vec!["00_Inbox/high.md", "00_Inbox/tie-old.md", "00_Inbox/mid.md"]
+        );
+
+        let everywhere = review_queue(&root, true, false, 2, &md).unwrap();
+        assert_eq!(everywhere.len(), 2);
+        assert_eq!(everywhere[0].path, "10_Projects/deep.md");
+
+        let archived = review_queue(&root, true, true, 1, &md).unwrap();
+        assert_eq!(archived[0].path, "99_Archives/Inbox/gone.md");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn digest_reports_only_changed_notes_and_new_relations() {
+        let root = temp_root("digest");
+        let changes = vec![
+            NoteChange {
+                path: root.join("00_Inbox/new.md"),
+                current: "# New idea\nREL:causes(Cache miss -> Latency)[0.8]\n".to_string(),
+                previous: None,
+            },
+            NoteChange {
+                path: root.join("10_Projects/tuning.md"),
+                current:
+                    "# Tuning\nREL:causes(GC -> Pauses)[0.9]\nREL:fixes(Pooling -> Pauses)[0.7]\n"
+                        .to_string(),
+                previous: Some("# Tuning\nREL:causes(GC -> Pauses)[0.9]\n".to_string()),
+            },
+        ];
+        let report = digest_notes(&root, "HEAD~1", &changes).unwrap();
+        let paths: Vec<&str> = report.notes.iter().map(|n| n.path.as_str()).collect();
+        assert_eq!(paths, vec!["00_Inbox/new.md", "10_Projects/tuning.md"]);
+        assert!(report.notes[0].added && !report.notes[1].added);
+        assert_eq!(report.notes[0].new_relations[0].to, "Latency");
+        let fresh: Vec<&str> = report.notes[1]
+            .new_relations
+            .iter()
+            .map(|r| r.rel_type.as_str())
+            .collect();
+        assert_eq!(fresh, vec!["fixes"]);
+
+        let Err(err) = git_note_changes(&root, "HEAD~1", &NoteExtensions::default()) else {
+            panic!("expected a non-git error");
+        };
+        assert!(err.to_string().contains("git"), "{}", err);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn git_changes_read_committed_text_of_non_ascii_paths() {
+        let root = temp_root("digest-git");
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(&root)
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        git(&["init", "-q"]);
+        write_note(&root, "10_Projects/tuning.md", "# Tuning\n");
+        git(&["add", "."]);
+        git(&["commit", "-qm", "first"]);
+        write_note(
+            &root,
+            "00_Inbox/café.md",
+            "# Café\nREL:causes(Beans -> Coffee)[0.8]\n",
+        );
+        write_note(
+            &root,
+            "10_Projects/tuning.md",
+            "# Tuning\nREL:fixes(Pooling -> Pauses)[0.7]\n",
+        );
+        git(&["add", "."]);
+        git(&["commit", "-qm", "second"]);
+        write_note(&root, "10_Projects/tuning.md", "# Uncommitted\n");
+
+        let changes = git_note_changes(&root, "HEAD~1", &NoteExtensions::default()).unwrap();
+        let report = digest_notes(&root, "HEAD~1", &changes).unwrap();
+        let notes: Vec<(&str, &str, bool)> = report
+            .notes
+            .iter()
+            .map(|n| (n.path.as_str(), n.title.as_str(), n.added))
+            .collect();
+        assert_eq!(
+            notes,
+            vec![
+                ("00_Inbox/café.md", "Café", true),
+                ("10_Projects/tuning.md", "Tuning", false),
+            ]
+        );
+        assert_eq!(report.notes[1].new_relations[0].rel_type, "fixes");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn prune_moves_only_fully_decayed_notes() {
+        let root = temp_root("prune");
+        let stale = Utc::now().date_naive() - Duration::days(200);
+        let decayed = write_note(
+            &root,
+            "99_Archives/Inbox/old.md",
+            &format!(
+                "# Old\n<!-- lifecycle last_reviewed={} decay_score=1.000 -->\n",
+                stale
+            ),
+        );
+        let fading = write_note(
+            &root,
+            "00_Inbox/fading.md",
+            &format!(
+                "# Fading\n<!-- lifecycle last_reviewed={} decay_score=0.400 -->\n",
+                stale
+            ),
+        );
+        let fresh = write_note(&root, "00_Inbox/fresh.md", "# Fresh\n");
+        assert_eq!(
+            parse_decay_metadata(&fs::read_to_string(&decayed).unwrap()),
+            Some((stale, 1.0))
+        );
+
+        let report =
+            run_lifecycle(&root, LifecycleMode::Prune, &LifecycleOptions::default()).unwrap();
+        assert_eq!((report.processed, report.touched), (3, 1));
+        assert!(!decayed.exists());
+        assert!(root.join(PRUNED_DIR).join("old.md").exists());
+        assert!(fading.exists());
+        assert!(fresh.exists());
+        assert_eq!(
+            read_archive_log(&root).unwrap()[0].from,
+            "99_Archives/Inbox/old.md"
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn benchmark_reports_mrr_and_ndcg() {
+        let graph = LoadedGraph::new(graph_of(
+            vec![
+                counted_node("first.md", "alpha beta", &[]),
+                counted_node("second.md", "alpha", &[]),
+            ],
+            Vec::new(),
+        ));
+        let dataset = vec![BenchmarkQuery {
+            query: "alpha beta".to_string(),
+            expected: vec!["second.md".to_string()],
+            relevance: HashMap::new(),
+        }];
+        let report = run_benchmark(&graph, &dataset, 5, None).unwrap();
+        assert_eq!(report.queries[0].hit_rank, Some(2));
+        assert!((report.summary.mrr - 0.5).abs() < 1e-9);
+        let expected_ndcg = 1.0 / 3f64.log2();
+        assert!((report.summary.ndcg - expected_ndcg).abs() < 1e-9);
+
+        let graded = vec![BenchmarkQuery {
+            query: "alpha beta".to_string(),
+            expected: vec!["second.md".to_string()],
+            relevance: HashMap::from([("first.md".to_string(), 3.0)]),
+        }];
+        let report = run_benchmark(&graph, &graded, 5, None).unwrap();
+        assert!((report.summary.ndcg - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn link_check_reports_and_stubs_missing_targets() {
+        let root = temp_root("link-check");
+        let out = root.join(".neural");
+        write_note(
+            &root,
+            "10_Projects/a.md",
+            "# A\nSee [[Runbook]] and [[b]].\n",
+        );
+        write_note(
+            &root,
+            "10_Projects/b.md",
+            "# B\nAlso [[Runbook]] and [[Glossary]].\n",
+        );
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+
+        let links = unresolved_links(&graph);
+        assert_eq!(links.len(), 3);
+        let groups = group_unresolved(&links);
+        assert_eq!(
+            groups[0],
+            ("Runbook", vec!["10_Projects/a.md", "10_Projects/b.md"])
+        );
+        assert_eq!(groups[1], ("Glossary", vec!["10_Projects/b.md"]));
+
+        let stubs = create_link_stubs(&root, &links).unwrap();
+        assert_eq!(stubs, vec!["00_Inbox/Runbook.md", "00_Inbox/Glossary.md"]);
+        assert_eq!(
+            fs::read_to_string(root.join("00_Inbox/Runbook.md")).unwrap(),
+            "# Runbook\n"
+        );
+        assert!(create_link_stubs(&root, &links).unwrap().is_empty());
+
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        assert!(unresolved_links(&load_graph(&result.graph_path).unwrap()).is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn diff_reports_node_edge_and_title_changes() {
+        let old = graph_of(
+            vec![
+                counted_node("a.md", "Alpha", &[]),
+                counted_node("b.md", "Beta", &[]),
+            ],
+            vec![edge("a.md", "b.md", "WIKILINK")],
+        );
+        let new = graph_of(
+            vec![
+                counted_node("a.md", "Alpha v2", &[]),
+                counted_node("b.md", "Beta", &[]),
+                counted_node("c.md", "Gamma", &[]),
+            ],
+            vec![
+                edge("a.md", "b.md", "WIKILINK"),
+                edge("c.md", "a.md", "WIKILINK"),
+            ],
+        );
+        let diff = diff_graphs(&old, &new);
+        assert_eq!(diff.added_nodes, vec!["c.md".to_string()]);
+        assert!(diff.removed_nodes.is_empty());
+        assert_eq!(diff.added_edges.len(), 1);
+        assert_eq!(diff.added_edges[0].src, "c.md");
+        assert!(diff.removed_edges.is_empty());
+        assert_eq!(diff.retitled.len(), 1);
+        assert_eq!(diff.retitled[0].new, "Alpha v2");
+
+        let reverse = diff_graphs(&new, &old);
+        assert_eq!(reverse.removed_nodes, vec!["c.md".to_string()]);
+        assert_eq!(reverse.removed_edges.len(), 1);
+    }
+
+    #[test]
+    fn benchmark_progress_ticks_once_per_query() {
+        let graph = LoadedGraph::new(graph_of(
+            vec![counted_node("a.md", "alpha", &[])],
+            Vec::new(),
+        ));
+        let query = |text: &str| BenchmarkQuery {
+            query: text.to_string(),
+            expected: vec!["a.md".to_string()],
+            relevance: HashMap::new(),
+        };
+        let dataset = vec![query("alpha"), query("beta"), query("gamma")];
+        let seen = Mutex::new(Vec::new());
+        let progress = |done: usize, total: usize| seen.lock().unwrap().push((done, total));
+        run_benchmark(&graph, &dataset, 3, Some(&progress)).unwrap();
+        assert_eq!(seen.into_inner().unwrap(), vec![(1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn benchmark_markdown_report_has_summary_and_rows() {
+        let root = temp_root("bench-md");
+        let graph = LoadedGraph::new(graph_of(
+            vec![counted_node("a.md", "alpha", &[])],
+            Vec::new(),
+        ));
+        let dataset = vec![BenchmarkQuery {
+            query: "alpha".to_string(),
+            expected: vec!["a.md".to_string()],
+            relevance: HashMap::new(),
+        }];
+        let report = run_benchmark(&graph, &dataset, 3, None).unwrap();
+        let path = root.join("report.md");
+        fs::write(
+            &path,
+            render_benchmark_markdown(&report, Path::new(".neural/graph.json"), Utc::now()),
+        )
+        .unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.contains("| hit@1 | hit@3 | hit@5 | MRR | NDCG | avg latency (ms) |"));
+        assert!(written.contains("- Top-k: 3"));
+        assert!(written.contains("| 1 | alpha | hit | 1 | a.md |"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn bench_compare_reports_rank_deltas_regressions_first() {
+        let report = |ranks: &[(&str, Option<usize>)]| {
+            let mrr = ranks
+                .iter()
+                .map(|(_, rank)| rank.map_or(0.0, |r| 1.0 / r as f64))
+                .sum::<f64>()
+                / ranks.len() as f64;
+            BenchmarkReport {
+                summary: BenchmarkSummary {
+                    hit_at_1: 0.0,
+                    hit_at_3: 0.0,
+                    hit_at_5: 0.0,
+                    mrr,
+                    ndcg: 0.0,
+                    precision_at_k: 0.0,
+                    recall_at_k: 0.0,
+                    top_k: 5,
+                    avg_latency_ms: 1.0,
+                },
+                queries: ranks
+                    .iter()
+                    .map(|(query, rank)| QuerySummary {
+                        query: query.to_string(),
+                        hit_rank: *rank,
+                        hit_path: None,
+                        reciprocal_rank: rank.map_or(0.0, |r| 1.0 / r as f64),
+                        ndcg: 0.0,
+                        precision: 0.0,
+                        recall: 0.0,
+                        latency_ms: 1.0,
+                    })
+                    .collect(),
+            }
+        };
+        let root = temp_root("bench-compare");
+        let old_path = root.join("old.json");
+        let old = report(&[("alpha", Some(1)), ("beta", Some(2)), ("gamma", None)]);
+        write_atomic(&old_path, serde_json::to_string_pretty(&old).unwrap()).unwrap();
+        let old = load_benchmark_report(&old_path).unwrap();
+        let new = report(&[("alpha", Some(1)), ("beta", Some(4)), ("delta", Some(1))]);
+
+        let comparison = compare_benchmarks(&old, &new);
+        let changes: Vec<(&str, RankChange)> = comparison
+            .queries
+            .iter()
+            .map(|q| (q.query.as_str(), q.change))
+            .collect();
+        assert!(
+            changes
+                == vec![
+                    ("beta", RankChange::Regressed),
+                    ("alpha", RankChange::Unchanged)
+                ]
+        );
+        assert_eq!(
+            (
+                comparison.queries[0].old_rank,
+                comparison.queries[0].new_rank
+            ),
+            (Some(2), Some(4))
+        );
+        assert_eq!(comparison.only_old, vec!["gamma"]);
+        assert_eq!(comparison.only_new, vec!["delta"]);
+        let mrr = comparison.metrics.iter().find(|m| m.name == "mrr").unwrap();
+        assert!((mrr.delta - (new.summary.mrr - old.summary.mrr)).abs() < 1e-12);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn benchmark_reports_precision_and_recall_at_k() {
+        let graph = LoadedGraph::new(graph_of(
+            vec![
+                counted_node("a.md", "alpha one", &[]),
+                counted_node("b.md", "alpha two", &[]),
+                counted_node("c.md", "gamma", &[]),
+            ],
+            Vec::new(),
+        ));
+        let dataset = vec![BenchmarkQuery {
+            query: "alpha".to_string(),
+            expected: vec!["a.md".to_string(), "b.md".to_string(), "c.md".to_string()],
+            relevance: HashMap::new(),
+        }];
+        let report = run_benchmark(&graph, &dataset, 5, None).unwrap();
+        assert!((report.summary.recall_at_k - 2.0 / 3.0).abs() < 1e-3);
+        assert!((report.summary.precision_at_k - 0.4).abs() < 1e-9);
+        assert_eq!(report.summary.hit_at_1, 1.0);
+    }
+
+    #[test]
+    fn benchmark_counts_a_note_listed_by_path_and_title_once() {
+        let mut a = counted_node("a", "alpha one", &[]);
+        a.path = Some("notes/a.md".to_string());
+        let graph = LoadedGraph::new(graph_of(
+            vec![a, counted_node("b", "gamma", &[])],
+            Vec::new(),
+        ));
+        let dataset = vec![BenchmarkQuery {
+            query: "alpha".to_string(),
+            expected: vec!["notes/a.md".to_string(), "alpha one".to_string()],
+            relevance: HashMap::new(),
+        }];
+        let report = run_benchmark(&graph, &dataset, 5, None).unwrap();
+        assert_eq!(report.summary.recall_at_k, 1.0);
+        assert!((report.summary.precision_at_k - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn jsonl_lines_parse_independently() {
+        let graph = LoadedGraph::new(graph_of(
+            vec![
+                counted_node("first.md", "alpha beta", &[]),
+                counted_node("second.md", "alpha", &[]),
+            ],
+            Vec::new(),
+        ));
+        let rows = recall_from_graph(
+            &graph,
+            "alpha",
+            5,
+            &RecallProfile::Balanced.weights(),
+            &RecallOptions::default(),
+        );
+        let mut out = Vec::new();
+        write_jsonl(&mut out, &rows).unwrap();
+
+        let dataset = vec![BenchmarkQuery {
+            query: "alpha".to_string(),
+            expected: vec!["second.md".to_string()],
+            relevance: HashMap::new(),
+        }];
+        let summary = stream_benchmark(&graph, &dataset, 5, |query| {
+            write_jsonl_line(&mut out, &query)
+        })
+        .unwrap();
+        write_jsonl_line(&mut out, &summary).unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), rows.len() + 2);
+        for line in &lines {
+            serde_json::from_str::<serde_json::Value>(line).unwrap();
+        }
+        let last: serde_json::Value = serde_json::from_str(lines[lines.len() - 1]).unwrap();
+        assert!(last.get("mrr").is_some());
+    }
+
+    #[test]
+    fn dot_export_styles_ghosts_and_labels_edges() {
+        let mut ghost = counted_node("ghost/Missing", "Missing", &[]);
+        ghost.path = None;
+        let graph = graph_of(
+            vec![counted_node("a.md", "Say \"hi\"", &[]), ghost],
+            vec![edge("a.md", "ghost/Missing", "UNRESOLVED_LINK")],
+        );
+        let root = temp_root("dot");
+        let target = root.join("graph.dot");
+        write_graph_export(&graph, GraphFormat::Dot, &target).unwrap();
+        let dot = fs::read_to_string(&target).unwrap();
+        assert!(dot.starts_with("digraph exomind {\n"));
+        assert!(dot.contains("\"a.md\" [label=\"Say \\\"hi\\\"\", stem=\"a\"];"));
+        assert!(dot.contains(
+            "\"ghost/Missing\" [label=\"Missing\", stem=\"ghost/Missing\", style=dashed];"
+        ));
+        assert!(dot.contains("\"a.md\" -> \"ghost/Missing\" [label=\"UNRESOLVED_LINK\"];"));
+        assert!(dot.trim_end().ends_with('}'));
+
+        let graphml = render_graphml(&graph);
+        assert!(graphml.contains("<edge source=\"a.md\" target=\"ghost/Missing\">"));
+        assert!(graphml.contains("Say &quot;hi&quot;"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn only_kinds_keeps_typed_relations_and_their_endpoints() {
+        let mut caused = edge("a.md", "b.md", "CAUSED_BY");
+        caused.confidence = Some(0.9);
+        let mut graph = graph_of(
+            vec![
+                counted_node("a.md", "A", &[]),
+                counted_node("b.md", "B", &[]),
+                counted_node("c.md", "C", &[]),
+                counted_node("d.md", "D", &[]),
+            ],
+            vec![
+                caused,
+                edge("c.md", "b.md", "related_to"),
+                edge("a.md", "d.md", "WIKILINK"),
+            ],
+        );
+        retain_edge_kinds(
+            &mut graph,
+            &["CAUSED_BY".to_string(), "RELATED_TO".to_string()],
+        );
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["a.md", "b.md", "c.md"]);
+        assert_eq!((graph.stats.nodes, graph.stats.edges), (3, 2));
+
+        let dot = render_dot(&graph);
+        assert!(dot.contains("\"a.md\" -> \"b.md\" [label=\"CAUSED_BY (0.90)\"];"));
+        assert!(dot.contains("\"c.md\" -> \"b.md\" [label=\"related_to\"];"));
+        assert!(!dot.contains("WIKILINK") && !dot.contains("d.md"));
+        let graphml = render_graphml(&graph);
+        assert!(graphml.contains("<data key=\"confidence\">0.9</data>"));
+    }
+
+    #[test]
+    fn csv_export_quotes_fields_and_counts_rows() {
+        let mut typed = edge("a.md", "b.md", "CAUSED_BY");
+        typed.confidence = Some(0.5);
+        let graph = graph_of(
+            vec![
+                counted_node("a.md", "Alpha, \"quoted\"", &[]),
+                counted_node("b.md", "Beta", &[]),
+            ],
+            vec![edge("a.md", "b.md", "WIKILINK"), typed],
+        );
+        let root = temp_root("csv");
+        write_nodes_csv(&graph, &root.join("nodes.csv")).unwrap();
+        write_edges_csv(&graph, &root.join("edges.csv")).unwrap();
+
+        let nodes = fs::read_to_string(root.join("nodes.csv")).unwrap();
+        let lines: Vec<&str> = nodes.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "id,title,stem,path,indegree");
+        assert_eq!(lines[1], "a.md,\"Alpha, \"\"quoted\"\"\",a,a.md,0");
+        assert_eq!(lines[2], "b.md,Beta,b,b.md,2");
+
+        let mut reader = csv::Reader::from_path(root.join("edges.csv")).unwrap();
+        let headers: Vec<String> = reader.headers().unwrap().iter().map(String::from).collect();
+        assert_eq!(headers, vec!["src", "dst", "kind", "confidence"]);
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((&rows[0][3], &rows[1][3]), ("", "0.5"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn stats_counts_orphans_and_top_indegree() {
+        let mut ghost = counted_node("ghost/x", "x", &[]);
+        ghost.path = None;
+        let graph = graph_of(
+            vec![
+                semantic_node("a.md", &[("one", 1.0)]),
+                semantic_node("b.md", &[("one", 1.0), ("two", 1.0), ("three", 1.0)]),
+                semantic_node("c.md", &[("one", 1.0), ("two", 1.0)]),
+                semantic_node("lonely.md", &[]),
+                ghost,
+            ],
+            vec![
+                edge("a.md", "b.md", "WIKILINK"),
+                edge("c.md", "b.md", "WIKILINK"),
+                edge("c.md", "ghost/x", "UNRESOLVED_LINK"),
+            ],
+        );
+        let report = graph_stats(&graph, 1);
+        assert_eq!(report.real_nodes, 4);
+        assert_eq!(report.ghost_nodes, 1);
+        assert_eq!(report.orphans, 1);
+        assert_eq!(report.edges_by_kind.get("WIKILINK"), Some(&2));
+        assert_eq!(report.top_linked.len(), 1);
+        assert_eq!(report.top_linked[0].id, "b.md");
+        assert_eq!(report.top_linked[0].indegree, 2);
+        assert!((report.avg_vector_size - 1.5).abs() < 1e-9);
+        assert!((report.median_vector_size - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stats_indegree_matches_the_recall_graph_signal() {
+        let graph = graph_of(
+            vec![
+                counted_node("a.md", "a", &[]),
+                counted_node("b.md", "b", &[]),
+                counted_node("c.md", "c", &[]),
+            ],
+            vec![
+                Edge {
+                    weight: Some(2),
+                    ..edge("a.md", "b.md", "WIKILINK")
+                },
+                edge("c.md", "b.md", "CONTAINS"),
+                edge("c.md", "a.md", "WIKILINK"),
+            ],
+        );
+        let report = graph_stats(&graph, 3);
+        let recall = weighted_indegree(&graph, &EdgeWeights::default());
+        let counts: Vec<(&str, usize)> = report
+            .top_linked
+            .iter()
+            .map(|n| (n.id.as_str(), n.indegree))
+            .collect();
+        assert_eq!(counts, vec![("b.md", 2), ("a.md", 1)]);
+        for (id, count) in counts {
+            assert_eq!(recall[id], count as f64);
+        }
+    }
+
+    #[test]
+    fn dedupe_reports_identical_bodies_only() {
+        let graph = graph_of(
+            vec![
+                semantic_node("a.md", &[("kafka", 2.0), ("offsets", 1.5)]),
+                semantic_node("b.md", &[("kafka", 2.0), ("offsets", 1.5)]),
+                semantic_node("c.md", &[("sourdough", 3.0), ("starter", 1.0)]),
+            ],
+            Vec::new(),
+        );
+        let pairs = find_duplicates(&graph, 0.9);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].a.as_str(), pairs[0].b.as_str()), ("a.md", "b.md"));
+        assert!((pairs[0].similarity - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn dedupe_pairs_every_copy_of_a_common_note() {
+        let copy = || [("kafka", 2.0), ("offsets", 1.5)];
+        let graph = graph_of(
+            vec![
+                semantic_node("a.md", &copy()),
+                semantic_node("b.md", &copy()),
+                semantic_node("c.md", &copy()),
+            ],
+            Vec::new(),
+        );
+        let pairs = find_duplicates(&graph, 0.9);
+        let found: Vec<(&str, &str)> = pairs.iter().map(|p| (p.a.as_str(), p.b.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![("a.md", "b.md"), ("a.md", "c.md"), ("b.md", "c.md")]
+        );
+    }
+
+    #[test]
+    fn repl_applies_commands_between_queries() {
+        let graph = LoadedGraph::new(graph_of(
+            vec![
+                counted_node("a.md", "alpha beta", &[]),
+                counted_node("b.md", "alpha", &[]),
+            ],
+            Vec::new(),
+        ));
+        let input = "alpha\n\n:topk 1\n:weights 0 0 0\nalpha\n:weights 1 0 0\nalpha\n";
+        let mut out = Vec::new();
+        run_repl(&graph, std::io::Cursor::new(input), &mut out, 10, false).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "01. score=2.00 | alpha beta | a.md",
+                "02. score=2.00 | alpha | b.md",
+                "topk=1",
+                "weights lexical=0 graph=0 semantic=0",
+                "(no results)",
+                "weights lexical=1 graph=0 semantic=0",
+                "01. score=2.00 | alpha beta | a.md",
+            ]
+        );
+    }
+
+    #[test]
+    fn serve_handler_answers_recall_and_health() {
+        let graph = LoadedGraph::new(graph_of(
+            vec![
+                counted_node("a.md", "alpha beta", &[]),
+                counted_node("b.md", "gamma", &[]),
+            ],
+            Vec::new(),
+        ));
+        let (status, body) =
+            handle_api_request(&graph, "/recall?q=alpha%20beta&topk=1&graph_weight=0");
+        assert_eq!(status, 200);
+        let value: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(value["query"], "alpha beta");
+        assert_eq!(value["top_k"], 1);
+        assert_eq!(value["results"][0]["path"], "a.md");
+        assert_eq!(value["results"].as_array().unwrap().len(), 1);
+
+        let (status, body) = handle_api_request(&graph, "/healthz");
+        assert_eq!((status, body.contains("\"nodes\":2")), (200, true));
+        assert_eq!(handle_api_request(&graph, "/recall?topk=3").0, 400);
+        assert_eq!(handle_api_request(&graph, "/recall?q=a&topk=x").0, 400);
+        assert_eq!(handle_api_request(&graph, "/nope").0, 404);
+    }
+
+    #[test]
+    fn loaded_graph_caches_idf_indegree_and_pagerank() {
+        let build = || {
+            graph_of(
+                vec![
+                    counted_node("a.md", "alpha beta", &[]),
+                    counted_node("b.md", "alpha", &[]),
+                    counted_node("c.md", "gamma", &[]),
+                ],
+                vec![edge("a.md", "b.md", "wikilink")],
+            )
+        };
+        let graph = build();
+        let loaded = LoadedGraph::new(build());
+        assert_eq!(loaded.indexes.idf.len(), graph.doc_freq.len());
+        for (token, df) in &graph.doc_freq {
+            let direct = tfidf_idf(graph.total_docs, *df);
+            assert!((loaded.indexes.idf(token) - direct).abs() < 1e-12);
+        }
+        assert_eq!(loaded.indexes.idf("unseen"), tfidf_idf(graph.total_docs, 0));
+        assert!(loaded.indexes.indegree["b.md"] > 0.0);
+
+        let weights = RecallWeights {
+            lexical: 1.0,
+            graph: 1.0,
+            semantic: 1.0,
+        };
+        let options = RecallOptions::default();
+        let cached: Vec<Option<String>> = loaded
+            .recall("alpha", 3, &weights, &options)
+            .into_iter()
+            .map(|row| row.path)
+            .collect();
+        let direct: Vec<Option<String>> = recall_from_graph(&graph, "alpha", 3, &weights, &options)
+            .into_iter()
+            .map(|row| row.path)
+            .collect();
+        assert_eq!(cached, direct);
+
+        let ranked = RecallOptions {
+            graph_mode: GraphMode::Pagerank,
+            ..Default::default()
+        };
+        let first = loaded.recall("alpha", 3, &weights, &ranked);
+        let again = loaded.recall("beta", 3, &weights, &ranked);
+        assert_eq!(loaded.indexes.pagerank.lock().unwrap().len(), 1);
+        let direct = recall_from_graph(&graph, "alpha", 3, &weights, &ranked);
+        let scores = |rows: &[RecallRow]| rows.iter().map(|r| r.score).collect::<Vec<_>>();
+        assert_eq!(scores(&first), scores(&direct));
+        assert!(!again.is_empty());
+    }
+
+    #[test]
+    fn topk_zero_returns_every_positive_score() {
+        let graph = graph_of(
+            vec![
+                counted_node("a.md", "alpha", &[]),
+                counted_node("b.md", "alpha beta", &[]),
+                counted_node("c.md", "alpha gamma", &[]),
+                counted_node("d.md", "delta", &[]),
+            ],
+            Vec::new(),
+        );
+        let weights = RecallWeights {
+            lexical: 1.0,
+            graph: 0.0,
+            semantic: 0.0,
+        };
+        let options = RecallOptions::default();
+        assert_eq!(
+            recall_from_graph(&graph, "alpha", 1, &weights, &options).len(),
+            1
+        );
+        let rows = recall_from_graph(&graph, "alpha", 0, &weights, &options);
+        assert_eq!(rows.len(), 3);
+        let ranks: Vec<usize> = rows.iter().map(|row| row.rank).collect();
+        assert_eq!(ranks, vec![1, 2, 3]);
+        assert!(rows.iter().all(|row| row.score > 0.0));
+    }
+
+    #[test]
+    fn explain_parts_sum_to_score() {
+        let mut a = counted_node("a.md", "alpha beta", &[("alpha", 1), ("beta", 1)]);
+        a.semantic = BTreeMap::from([("alpha".to_string(), 0.5)]);
+        let mut graph = graph_of(
+            vec![a, counted_node("b.md", "alpha", &[("alpha", 1)])],
+            vec![edge("a.md", "b.md", "wikilink")],
+        );
+        graph.total_docs = 2;
+        graph.doc_freq = BTreeMap::from([("alpha".to_string(), 2), ("beta".to_string(), 1)]);
+        let weights = RecallWeights {
+            lexical: 1.0,
+            graph: 0.5,
+            semantic: 2.0,
+        };
+        let options = RecallOptions {
+            explain: true,
+            spread: 1,
+            ..RecallOptions::default()
+        };
+        let rows = recall_from_graph(&graph, "alpha beta", 5, &weights, &options);
+        assert_eq!(rows.len(), 2);
+        for row in &rows {
+            let parts = row.explain.as_ref().unwrap();
+            let total = parts.lexical + parts.graph + parts.semantic + parts.spread;
+            assert!((total - row.score).abs() < 1e-9);
+            assert!((parts.lexical - parts.lexical_raw).abs() < 1e-12);
+            assert!((parts.semantic - 2.0 * parts.semantic_raw).abs() < 1e-12);
+        }
+        let a = rows
+            .iter()
+            .find(|row| row.path.as_deref() == Some("a.md"))
+            .unwrap();
+        let b = rows
+            .iter()
+            .find(|row| row.path.as_deref() == Some("b.md"))
+            .unwrap();
+        assert!(a.explain.as_ref().unwrap().semantic > 0.0);
+        assert!(b.explain.as_ref().unwrap().graph > 0.0);
+        assert!(b.explain.as_ref().unwrap().spread > 0.0);
+
+        let plain = recall_from_graph(&graph, "alpha beta", 5, &weights, &RecallOptions::default());
+        assert!(plain.iter().all(|row| row.explain.is_none()));
+    }
+
+    #[test]
+    fn init_with_templates_seeds_para_folders_once() {
+        let root = temp_root("init-templates");
+        init_workflow(&root).unwrap();
+        let written = write_seed_notes(&root).unwrap();
+        assert_eq!(written.len(), SEED_NOTES.len());
+        for rel in [
+            "10_Projects/Example-Project.md",
+            "20_Areas/Example-Area.md",
+            "30_Resources/Example-Resource.md",
+        ] {
+            let text = fs::read_to_string(root.join(rel)).unwrap();
+            assert!(text.starts_with("---\n"));
+            assert!(text.contains("\n# Example"));
+            assert!(text.contains("[["));
+        }
+
+        let edited = root.join("20_Areas/Example-Area.md");
+        fs::write(&edited, "# Mine\n").unwrap();
+        assert!(write_seed_notes(&root).unwrap().is_empty());
+        assert_eq!(fs::read_to_string(&edited).unwrap(), "# Mine\n");
+
+        fs::remove_file(&edited).unwrap();
+        write_seed_notes(&root).unwrap();
+        let out = root.join(".neural");
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        assert!(graph
+            .edges
+            .iter()
+            .any(|e| e.src.ends_with("Example-Project.md") && e.dst.ends_with("Example-Area.md")));
+    }
+
+    #[test]
+    fn workspace_hint_fires_only_without_note_dirs() {
+        let root = temp_root("workspace-hint");
+        let hint = uninitialized_workspace_hint(&root).unwrap();
+        assert!(hint.contains("exom init"));
+        assert!(uninitialized_workspace_hint(&root.join("missing")).is_none());
+
+        let result =
+            index_graph_data(&root, &root.join(".neural"), &IndexOptions::default()).unwrap();
+        assert_eq!(result.notes, 0);
+
+        init_workflow(&root).unwrap();
+        assert!(uninitialized_workspace_hint(&root).is_none());
+    }
+
+    #[test]
+    fn minmax_normalization_puts_components_on_one_scale() {
+        let mut semantic = counted_node("sem.md", "other", &[]);
+        semantic.semantic = BTreeMap::from([("kafka".to_string(), 40.0)]);
+        let mut graph = graph_of(
+            vec![
+                counted_node("lex.md", "kafka", &[("kafka", 1)]),
+                semantic,
+                counted_node("none.md", "unrelated", &[]),
+            ],
+            Vec::new(),
+        );
+        graph.total_docs = 3;
+        graph.doc_freq = BTreeMap::from([("kafka".to_string(), 1)]);
+        let weights = RecallWeights {
+            lexical: 1.0,
+            graph: 0.0,
+            semantic: 1.0,
+        };
+        let contribution = |normalize: ScoreNormalization, id: &str| {
+            let options = RecallOptions {
+                normalize,
+                explain: true,
+                ..RecallOptions::default()
+            };
+            let rows = recall_from_graph(&graph, "kafka", 5, &weights, &options);
+            let row = rows
+                .iter()
+                .find(|row| row.path.as_deref() == Some(id))
+                .unwrap();
+            let parts = row.explain.as_ref().unwrap();
+            parts.lexical.max(parts.semantic)
+        };
+        let raw_ratio = contribution(ScoreNormalization::None, "sem.md")
+            / contribution(ScoreNormalization::None, "lex.md");
+        assert!(raw_ratio > 10.0);
+        let lexical = contribution(ScoreNormalization::Minmax, "lex.md");
+        let semantic = contribution(ScoreNormalization::Minmax, "sem.md");
+        assert!((lexical - 1.0).abs() < 1e-9);
+        assert!((semantic - 1.0).abs() < 1e-9);
+
+        let mut column = vec![1.0, 3.0, 5.0];
+        ScoreNormalization::Zscore.apply(&mut column);
+        let std = (8.0f64 / 3.0).sqrt();
+        for (value, expected) in column.iter().zip([0.0, 2.0 / std, 4.0 / std]) {
+            assert!((value - expected).abs() < 1e-9);
+        }
+        let mut flat = vec![2.0, 2.0];
+        ScoreNormalization::Minmax.apply(&mut flat);
+        assert_eq!(flat, vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn zscore_keeps_below_mean_matches() {
+        let graph = graph_of(
+            vec![
+                counted_node("a.md", "kafka streams", &[]),
+                counted_node("b.md", "kafka streams guide", &[]),
+                counted_node("c.md", "kafka", &[]),
+                counted_node("d.md", "other", &[]),
+            ],
+            Vec::new(),
+        );
+        let weights = RecallWeights {
+            lexical: 1.0,
+            graph: 0.0,
+            semantic: 0.0,
+        };
+        let options = RecallOptions {
+            normalize: ScoreNormalization::Zscore,
+            ..RecallOptions::default()
+        };
+        let mut paths: Vec<String> =
+            recall_from_graph(&graph, "kafka streams", 0, &weights, &options)
+                .into_iter()
+                .filter_map(|row| row.path)
+                .collect();
+        paths.sort();
+        assert_eq!(paths, vec!["a.md", "b.md", "c.md"]);
+    }
+
+    #[test]
+    fn extra_extensions_are_indexed_only_when_enabled() {
+        let root = temp_root("extensions");
+        let out = root.join(".neural");
+        write_note(&root, "10_Projects/plain.md", "# Plain\n");
+        write_note(&root, "10_Projects/long.MARKDOWN", "# Long\n[[plain]]\n");
+        write_note(&root, "10_Projects/component.mdx", "# Component\n");
+
+        let ids = |options: &IndexOptions| {
+            let result = index_graph_data(&root, &out, options).unwrap();
+            let graph = load_graph(&result.graph_path).unwrap();
+            graph
+                .nodes
+                .into_iter()
+                .filter(|n| n.path.is_some())
+                .map(|n| n.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&IndexOptions::default()), vec!["10_Projects/plain.md"]);
+
+        let options = IndexOptions {
+            force: true,
+            extensions: NoteExtensions::new(&["md".to_string(), ".markdown".to_string()]),
+            ..Default::default()
+        };
+        let mut found = ids(&options);
+        found.sort();
+        assert_eq!(
+            found,
+            vec!["10_Projects/long.MARKDOWN", "10_Projects/plain.md"]
+        );
+        assert_eq!(
+            options.extensions.strip("notes/long.Markdown"),
+            "notes/long"
+        );
+        assert_eq!(options.extensions.strip("notes/page.mdx"), "notes/page.mdx");
+    }
+
+    #[test]
+    fn query_embedding_outranks_lexical_match() {
+        let root = temp_root("embeddings");
+        let out = root.join(".neural");
+        write_note(&root, "30_Resources/lex.md", "# Latency\nlatency budget\n");
+        write_note(
+            &root,
+            "30_Resources/para.md",
+            "# Slow responses\nrequests take long\n",
+        );
+        let vectors = root.join("vectors.jsonl");
+        fs::write(
+            &vectors,
+            "{\"id\": \"30_Resources/lex.md\", \"vector\": [0.0, 1.0, 0.0]}\n\
+             {\"id\": \"30_Resources/para.md\", \"embedding\": [0.9, 0.1, 0.0]}\n",
+        )
+        .unwrap();
+        let options = IndexOptions {
+            embeddings: Some(load_embeddings(&vectors).unwrap()),
+            ..Default::default()
+        };
+        let result = index_graph_data(&root, &out, &options).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+
+        let query = root.join("query.json");
+        fs::write(&query, "[1.0, 0.0, 0.0]").unwrap();
+        let query = load_query_embedding(&query).unwrap();
+        check_embedding_dimension(&graph, &query).unwrap();
+        let weights = RecallWeights {
+            lexical: 0.1,
+            graph: 0.0,
+            semantic: 0.0,
+        };
+        let plain = recall_from_graph(&graph, "latency", 5, &weights, &RecallOptions::default());
+        assert_eq!(plain[0].path.as_deref(), Some("30_Resources/lex.md"));
+        let options = RecallOptions {
+            query_embedding: Some(query),
+            ..RecallOptions::default()
+        };
+        let rows = recall_from_graph(&graph, "latency", 5, &weights, &options);
+        assert_eq!(rows[0].path.as_deref(), Some("30_Resources/para.md"));
+
+        let err = check_embedding_dimension(&graph, &[1.0, 0.0]).unwrap_err();
+        assert!(err.to_string().contains("2 dimensions"));
+        fs::write(
+            &vectors,
+            "{\"id\": \"a\", \"vector\": [1.0]}\n{\"id\": \"b\", \"vector\": [1.0, 2.0]}\n",
+        )
+        .unwrap();
+        assert!(load_embeddings(&vectors).is_err());
+    }
+
+    #[test]
+    fn write_atomic_replaces_whole_file_without_leftovers() {
+        let root = temp_root("atomic");
+        let target = root.join("graph.json");
+        fs::write(&target, "old").unwrap();
+        write_atomic(&target, "{\"complete\": true}").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "{\"complete\": true}");
+        let leftovers: Vec<_> = fs::read_dir(&root)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .filter(|n| n != "graph.json")
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+
+        let missing_dir = root.join("nope").join("graph.json");
+        assert!(write_atomic(&missing_dir, "new").is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "{\"complete\": true}");
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn min_score_drops_rows_before_topk() {
+        let graph = graph_of(
+            vec![
+                counted_node("rust-async.md", "rust async", &[]),
+                counted_node("rust.md", "rust", &[]),
+                counted_node("cooking.md", "cooking", &[]),
+            ],
+            vec![],
+        );
+        let weights = RecallWeights {
+            lexical: 1.0,
+            graph: 0.0,
+            semantic: 0.0,
+        };
+        let all = recall_from_graph(&graph, "rust async", 0, &weights, &RecallOptions::default());
+        assert_eq!(all.len(), 2);
+        assert!(all[0].score > all[1].score);
+
+        let options = RecallOptions {
+            min_score: (all[0].score + all[1].score) / 2.0,
+            ..RecallOptions::default()
+        };
+        let kept = recall_from_graph(&graph, "rust async", 5, &weights, &options);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].path.as_deref(), Some("rust-async.md"));
+        assert!(kept.iter().all(|row| row.score >= options.min_score));
+    }
+
+    #[test]
+    fn offset_pages_recall_with_absolute_ranks() {
+        let graph = graph_of(
+            vec![
+                counted_node("a.md", "rust async graph tokio", &[]),
+                counted_node("b.md", "rust async graph", &[]),
+                counted_node("c.md", "rust async", &[]),
+                counted_node("d.md", "rust", &[]),
+                counted_node("e.md", "cooking", &[]),
+            ],
+            vec![],
+        );
+        let weights = RecallProfile::Lexical.weights();
+        let query = "rust async graph tokio";
+        let all = recall_from_graph(&graph, query, 0, &weights, &RecallOptions::default());
+        assert_eq!(all.len(), 4);
+        let options = RecallOptions {
+            offset: 2,
+            ..RecallOptions::default()
+        };
+        let page = recall_from_graph(&graph, query, 2, &weights, &options);
+        let ranks: Vec<usize> = page.iter().map(|row| row.rank).collect();
+        assert_eq!(ranks, vec![3, 4]);
+        assert_eq!(page[0].path, all[2].path);
+        assert_eq!(page[1].path, all[3].path);
+    }
+
+    #[test]
+    fn graph_schema_describes_nodes_and_edges() {
+        let schema = serde_json::to_value(graph_json_schema()).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        for field in ["version", "nodes", "edges", "stats", "doc_freq"] {
+            assert!(properties.contains_key(field), "missing {}", field);
+        }
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+        assert!(required.contains(&"nodes") && required.contains(&"edges"));
+        let definitions = &schema["definitions"];
+        assert!(definitions["Node"]["properties"]["semantic"].is_object());
+        assert!(definitions["Edge"]["properties"]["type"].is_object());
+        assert!(definitions["Stats"]["properties"]["notes"].is_object());
+    }
+
+    #[test]
+    fn exact_lookup_matches_title_or_stem_only() {
+        let node = |id: &str, stem: &str, title: &str| Node {
+            stem: stem.to_string(),
+            ..counted_node(id, title, &[("release", 1)])
+        };
+        let graph = graph_of(
+            vec![
+                node("b/Release.md", "Release", "Release"),
+                node("a/plan.md", "plan", "Release plan"),
+                node("c/notes.md", "notes", "release"),
+                node("d/rel-notes.md", "rel-notes", "Notes on releases"),
+            ],
+            vec![],
+        );
+        let ids =
+            |rows: Vec<RecallRow>| -> Vec<String> { rows.into_iter().map(|row| row.id).collect() };
+        assert_eq!(
+            ids(exact_title_matches(&graph, "Release", false, 10)),
+            vec!["b/Release.md"]
+        );
+        assert_eq!(
+            ids(exact_title_matches(&graph, "Release", true, 10)),
+            vec!["b/Release.md", "c/notes.md"]
+        );
+        assert_eq!(
+            ids(exact_title_matches(&graph, "rel-notes", false, 10)),
+            vec!["d/rel-notes.md"]
+        );
+        assert!(exact_title_matches(&graph, "Releas", true, 10).is_empty());
+    }
+
+    #[test]
+    fn components_split_disjoint_pairs() {
+        let mut ghost = counted_node("ghost/x", "x", &[]);
+        ghost.path = None;
+        let graph = graph_of(
+            vec![
+                counted_node("a.md", "a", &[]),
+                counted_node("b.md", "b", &[]),
+                counted_node("c.md", "c", &[]),
+                counted_node("d.md", "d", &[]),
+                counted_node("lonely.md", "lonely", &[]),
+                ghost,
+            ],
+            vec![
+                edge("a.md", "b.md", "WIKILINK"),
+                edge("d.md", "c.md", "WIKILINK"),
+                edge("lonely.md", "ghost/x", "UNRESOLVED_LINK"),
+            ],
+        );
+        let report = connected_components(&graph, false);
+        assert_eq!(report.components, 3);
+        assert_eq!(report.sizes, vec![2, 2, 1]);
+        assert_eq!(report.largest, 2);
+        assert_eq!(report.orphans, vec!["lonely.md"]);
+
+        let with_ghosts = connected_components(&graph, true);
+        assert_eq!(with_ghosts.sizes, vec![2, 2, 2]);
+        assert!(with_ghosts.orphans.is_empty());
+    }
+
+    #[test]
+    fn recall_columns_print_requested_fields_in_order() {
+        let graph = graph_of(
+            vec![
+                counted_node("notes/rust.md", "rust", &[]),
+                counted_node("notes/intro.md", "intro", &[]),
+            ],
+            vec![edge("notes/intro.md", "notes/rust.md", "WIKILINK")],
+        );
+        let weights = RecallProfile::Lexical.weights();
+        let rows = recall_from_graph(&graph, "rust", 5, &weights, &RecallOptions::default());
+        let render = |columns: &[RecallColumn]| {
+            let mut out = Vec::new();
+            write_recall_columns(&mut out, &graph, &rows, columns).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(
+            render(&[RecallColumn::Path, RecallColumn::Score]),
+            format!("notes/rust.md | {:.2}\n", rows[0].score)
+        );
+        assert_eq!(
+            render(&[
+                RecallColumn::Stem,
+                RecallColumn::Indegree,
+                RecallColumn::Modified
+            ]),
+            "notes/rust | 1 | -\n"
+        );
+    }
+
+    #[test]
+    fn adjacent_phrase_outranks_scattered_tokens() {
+        let root = temp_root("positions");
+        let out = root.join(".neural");
+        write_note(&root, "30_Resources/a.md", "# A\ngraph database design\n");
+        write_note(
+            &root,
+            "30_Resources/b.md",
+            "# B\ngraph theory and database design\n",
+        );
+        let options = IndexOptions {
+            positions: true,
+            ..Default::default()
+        };
+        let result = index_graph_data(&root, &out, &options).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let a = graph
+            .nodes
+            .iter()
+            .find(|n| n.id == "30_Resources/a.md")
+            .unwrap();
+        assert_eq!(a.bigrams.get("graph database"), Some(&1));
+
+        let weights = RecallWeights {
+            lexical: 1.0,
+            graph: 0.0,
+            semantic: 1.0,
+        };
+        let rows = recall_from_graph(
+            &graph,
+            "graph database",
+            10,
+            &weights,
+            &RecallOptions::default(),
+        );
+        assert_eq!(rows[0].path.as_deref(), Some("30_Resources/a.md"));
+        assert!(rows[0].score > rows[1].score);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn watch_only_reacts_to_markdown_changes() {
+        use notify::event::{AccessKind, CreateKind, RemoveKind};
+        use notify::{Event, EventKind};
+
+        let note = PathBuf::from("/vault/00_Inbox/idea.md");
+        let create = Event::new(EventKind::Create(CreateKind::File)).add_path(note.clone());
+        let remove = Event::new(EventKind::Remove(RemoveKind::File)).add_path(note.clone());
+        let access = Event::new(EventKind::Access(AccessKind::Read)).add_path(note);
+        let image = Event::new(EventKind::Create(CreateKind::File))
+            .add_path(PathBuf::from("/vault/00_Inbox/diagram.png"));
+        assert!(is_note_event(&create, &NoteExtensions::default()));
+        assert!(is_note_event(&remove, &NoteExtensions::default()));
+        assert!(!is_note_event(&access, &NoteExtensions::default()));
+        assert!(!is_note_event(&image, &NoteExtensions::default()));
+    }
+
+    #[test]
+    fn capture_reads_piped_stdin() {
+        let piped = std::io::Cursor::new("REL:DEPENDS_ON(Deploy->Migration)[0.9] from pbpaste\n");
+        let text = capture_input_text(None, None, Some(piped)).unwrap();
+        let relations = parse_relations(&text, &RELATION_REGEX);
+        assert_eq!(relations.len(), 1);
+        assert_eq!(relations[0].rel_type, "DEPENDS_ON");
+
+        let explicit = capture_input_text(
+            Some("inline".to_string()),
+            None,
+            Some(std::io::Cursor::new("ignored")),
+        )
+        .unwrap();
+        assert_eq!(explicit, "inline");
+
+        let empty = capture_input_text(None, None, Some(std::io::Cursor::new("  \n")));
+        assert!(empty.is_err());
+        assert!(capture_input_text(None, None, None::<std::io::Empty>).is_err());
+    }
+
+    #[test]
+    fn capture_context_writes_parent_wikilink() {
+        let root = temp_root("capture-context");
+        let target = root.join(INBOX_DIR).join("capture.md");
+        let mut atlas = counted_node("10_Projects/Atlas.md", "Atlas rollout", &[]);
+        atlas.stem = "Atlas".to_string();
+        let graph = graph_of(vec![atlas], Vec::new());
+        let options = CaptureOptions {
+            context: resolve_capture_context(&graph, "atlas rollout"),
+            ..Default::default()
+        };
+        run_capture(&root, &target, "retry budget idea", &options).unwrap();
+        let written = fs::read_to_string(&target).unwrap();
+        assert!(written.contains("Context: [[Atlas]]\n\nretry budget idea"));
+        assert_eq!(resolve_capture_context(&graph, "Atlsa"), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn capture_template_substitutes_placeholders() {
+        let root = temp_root("capture-template");
+        let template_path = root.join("template.md");
+        fs::write(
+            &template_path,
+            "### {{date}} capture\nSource: {{ context }}\n{{body}}\nAction items:\n{{relations_yaml}}",
+        )
+        .unwrap();
+        let options = CaptureOptions {
+            context: Some("Atlas".to_string()),
+            template: Some(load_capture_template(&template_path).unwrap()),
+            ..Default::default()
+        };
+        let target = root.join(INBOX_DIR).join("capture.md");
+        run_capture(
+            &root,
+            &target,
+            "ship it REL:DEPENDS_ON(A->B)[0.9]",
+            &options,
+        )
+        .unwrap();
+        let written = fs::read_to_string(&target).unwrap();
+        let today = Utc::now().format("%Y-%m-%d").to_string();
+        assert!(written.starts_with(&format!(
+            "# Auto capture\n\n### {} capture\nSource: [[Atlas]]\nship it\nREL:DEPENDS_ON(A -> B)[0.9]\nAction items:\n",
+            today
+        )));
+        assert!(written.contains("type: DEPENDS_ON"));
+        assert!(written.ends_with('\n'));
+
+        fs::write(&template_path, "{{timestamp}} only").unwrap();
+        assert!(load_capture_template(&template_path).is_err());
+        fs::write(&template_path, "{{body}} {{author}}").unwrap();
+        assert!(load_capture_template(&template_path).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn capture_filters_and_synthesizes_inverse_relations() {
+        let root = temp_root("capture-inverse");
+        let target = root.join(INBOX_DIR).join("capture.md");
+        let options = CaptureOptions {
+            min_confidence: 0.5,
+            synthesize_inverse: true,
+            ..Default::default()
+        };
+        let report = run_capture(
+            &root,
+            &target,
+            "REL:RELATED_TO(Cache->Queue)[0.7] REL:CAUSED_BY(Load->Outage)[0.2]",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(report.filtered_count, 1);
+        assert_eq!(report.synthesized_count, 1);
+        assert_eq!(report.relation_count, 2);
+        let inverse = &report.relations[1];
+        assert_eq!(
+            (
+                inverse.rel_type.as_str(),
+                inverse.from.as_str(),
+                inverse.to.as_str()
+            ),
+            ("RELATED_TO", "Queue", "Cache")
+        );
+        assert!((inverse.confidence - 0.7).abs() < 1e-9);
+        let written = fs::read_to_string(&target).unwrap();
+        assert!(!written.contains("type: CAUSED_BY"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn min_confidence_drops_relations_from_the_index() {
+        let root = temp_root("capture-min-conf");
+        let out = root.join(".neural");
+        let target = root.join(INBOX_DIR).join("capture.md");
+        let options = CaptureOptions {
+            min_confidence: 0.5,
+            ..Default::default()
+        };
+        run_capture(
+            &root,
+            &target,
+            "REL:CAUSED_BY(Load -> Outage)[0.2]\nREL:DEPENDS_ON(Api -> Db)[0.9]",
+            &options,
+        )
+        .unwrap();
+
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let kinds: Vec<&str> = graph
+            .edges
+            .iter()
+            .filter(|e| e.confidence.is_some())
+            .map(|e| e.kind.as_str())
+            .collect();
+        assert_eq!(kinds, vec!["DEPENDS_ON"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn doctor_inbox_backlog_fails_past_threshold() {
+        let root = temp_root("inbox-backlog");
+        init_workflow(&root).unwrap();
+        for idx in 0..3 {
+            let note = write_note(&root, &format!("00_Inbox/old-{}.md", idx), "# Old\n");
+            age_file(&note, DECAY_THRESHOLD_DAYS + 5);
+        }
+        write_note(&root, "00_Inbox/fresh.md", "# Fresh\n");
+        let backlog = |max_inbox| {
+            let options = DoctorOptions {
+                max_inbox,
+                ..Default::default()
+            };
+            let report = doctor_report(&root, &root.join("missing.json"), &options);
+            let check = report
+                .checks
+                .into_iter()
+                .find(|c| c.name == "inbox_backlog")
+                .unwrap();
+            (check.ok, check.info)
+        };
+        assert_eq!(backlog(3), (true, "aged=3 total=4".to_string()));
+        assert!(!backlog(2).0);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn doctor_flags_case_insensitive_stem_collisions() {
+        let root = temp_root("collisions");
+        write_note(&root, "10_Projects/Index.md", "# Projects\n");
+        write_note(&root, "20_Areas/index.md", "# Areas\n");
+        write_note(
+            &root,
+            "30_Resources/guide.md",
+            "---\naliases: [Manual]\n---\n# Guide\n",
+        );
+        write_note(&root, "30_Resources/manual.md", "# Manual\n");
+
+        let report = doctor_report(&root, &root.join("missing.json"), &DoctorOptions::default());
+        let check = report
+            .checks
+            .iter()
+            .find(|c| c.name == "stem_collisions")
+            .unwrap();
+        assert!(!check.ok);
+        assert!(check
+            .info
+            .contains("index: 10_Projects/Index.md, 20_Areas/index.md"));
+        assert!(check
+            .info
+            .contains("manual: 30_Resources/guide.md, 30_Resources/manual.md"));
+
+        fs::remove_file(root.join("20_Areas/index.md")).unwrap();
+        fs::remove_file(root.join("30_Resources/manual.md")).unwrap();
+        let report = doctor_report(&root, &root.join("missing.json"), &DoctorOptions::default());
+        assert!(report
+            .checks
+            .iter()
+            .any(|c| c.name == "stem_collisions" && c.ok));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn doctor_fix_creates_dirs_and_graph() {
+        let root = temp_root("doctor-fix");
+        let graph_path = root.join(".neural/graph.json");
+        let options = DoctorOptions {
+            fix: true,
+            ..Default::default()
+        };
+        let report = doctor_report(&root, &graph_path, &options);
+        for dir in NOTE_DIRS {
+            assert!(root.join(dir).is_dir(), "{} missing", dir);
+        }
+        let dirs = report
+            .checks
+            .iter()
+            .find(|c| c.name == "note_dirs")
+            .unwrap();
+        assert!(dirs.ok);
+        assert!(dirs.info.starts_with("fixed: created"));
+        assert!(!report.ok, "no notes yet, so the graph cannot be built");
+
+        write_note(&root, "10_Projects/plan.md", "# Plan\n");
+        let report = doctor_report(&root, &graph_path, &options);
+        assert!(graph_path.exists());
+        assert!(report.ok);
+        let graph = report
+            .checks
+            .iter()
+            .find(|c| c.name == "graph_exists")
+            .unwrap();
+        assert!(graph.info.contains("fixed: indexed 1 notes"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn recall_profiles_expand_and_accept_overrides() {
+        let exact = RecallProfile::Exact.weights();
+        assert_eq!(
+            (exact.lexical, exact.graph, exact.semantic),
+            (1.0, 0.0, 2.0)
+        );
+        let tuned = RecallProfile::Exact
+            .weights()
+            .with_overrides(None, Some(0.25), None);
+        assert_eq!(
+            (tuned.lexical, tuned.graph, tuned.semantic),
+            (1.0, 0.25, 2.0)
+        );
+    }
+
+    #[test]
+    fn parse_typed_relations() {
+        let sample =
+            "REL:CAUSED_BY(API Timeout->Latency Spike)[0.82] and REL:RELATED_TO(Demo->Spec)[1]";
+        let relations = parse_relations(sample, &RELATION_REGEX);
+        assert_eq!(relations.len(), 2);
+        assert_eq!(relations[0].rel_type, "CAUSED_BY");
+        assert_eq!(relations[0].from, "API Timeout");
+        assert_eq!(relations[0].to, "Latency Spike");
+        assert!((relations[0].confidence - 0.82).abs() < f64::EPSILON);
+        assert_eq!(relations[1].rel_type, "RELATED_TO");
+        assert_eq!(relations[1].confidence, 1.0);
+    }
+
+    #[test]
+    fn capture_normalizes_relation_types_and_rejects_unknown() {
+        for (raw, folded) in [
+            ("caused-by", "CAUSED_BY"),
+            ("causedBy", "CAUSED_BY"),
+            ("CAUSED_BY", "CAUSED_BY"),
+            ("depends on", "DEPENDS_ON"),
+        ] {
+            assert_eq!(normalize_relation_type(raw), folded);
+        }
+
+        let root = temp_root("relation-types");
+        let allow = root.join("types.txt");
+        fs::write(&allow, "caused-by  # causal\nRELATED_TO\n").unwrap();
+        let target = root.join(INBOX_DIR).join("capture.md");
+        let input =
+            "REL:causedBy(API Timeout -> Latency Spike)[0.9]\nREL:mentions(Cache -> Queue)[0.8]\n";
+
+        let lenient = CaptureOptions {
+            relation_types: Some(RelationTypes::load(&allow, false).unwrap()),
+            ..Default::default()
+        };
+        let report = run_capture(&root, &target, input, &lenient).unwrap();
+        assert_eq!(report.relation_count, 2);
+        assert_eq!(report.relations[0].rel_type, "CAUSED_BY");
+        assert_eq!(report.normalized.len(), 2);
+        assert!(report.rejected.is_empty());
+
+        let strict = CaptureOptions {
+            relation_types: Some(RelationTypes::load(&allow, true).unwrap()),
+            ..Default::default()
+        };
+        let target = root.join(INBOX_DIR).join("strict.md");
+        let report = run_capture(&root, &target, input, &strict).unwrap();
+        assert_eq!(report.relation_count, 1);
+        assert_eq!(report.relations[0].rel_type, "CAUSED_BY");
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.rejected[0].rel_type, "MENTIONS");
+        let written = fs::read_to_string(&target).unwrap();
+        assert!(!written.contains("type: MENTIONS"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn strict_normalized_captures_and_inverses_reach_the_index() {
+        let root = temp_root("relation-types-index");
+        let out = root.join(".neural");
+        let allow = root.join("types.txt");
+        fs::write(&allow, "caused-by\nRELATED_TO\n").unwrap();
+        let options = CaptureOptions {
+            relation_types: Some(RelationTypes::load(&allow, true).unwrap()),
+            synthesize_inverse: true,
+            ..Default::default()
+        };
+        run_capture(
+            &root,
+            &root.join(INBOX_DIR).join("capture.md"),
+            "REL:causedBy(Timeout -> Spike)[0.9]\nREL:mentions(Cache -> Queue)[0.8]\nREL:relatedTo(Cache -> Spike)[0.6]",
+            &options,
+        )
+        .unwrap();
+
+        let result = index_graph_data(&root, &out, &IndexOptions::default()).unwrap();
+        let graph = load_graph(&result.graph_path).unwrap();
+        let mut relations: Vec<(&str, &str, &str)> = graph
+            .edges
+            .iter()
+            .filter(|e| e.confidence.is_some())
+            .map(|e| (e.kind.as_str(), e.src.as_str(), e.dst.as_str()))
+            .collect();
+        relations.sort();
+        assert_eq!(
+            relations,
+            vec![
+                ("CAUSED_BY", "ghost/Timeout", "ghost/Spike"),
+                ("RELATED_TO", "ghost/Cache", "ghost/Spike"),
+                ("RELATED_TO", "ghost/Spike", "ghost/Cache"),
+            ]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn parse_relations_with_alternate_grammars() {
+        let arrow = relation_regex(None, RelationGrammar::Arrow).unwrap();
+        let sample = "- API Timeout -> Latency Spike (caused by, 0.7)\nCache -> Queue (feeds)\n";
+        let relations = parse_relations(sample, &arrow);
+        assert_eq!(relations.len(), 2);
+        assert_eq!(
+            (
+                relations[0].rel_type.as_str(),
+                relations[0].from.as_str(),
+                relations[0].to.as_str()
+            ),
+            ("caused_by", "API Timeout", "Latency Spike")
+        );
+        assert!((relations[0].confidence - 0.7).abs() < 1e-9);
+        assert_eq!(relations[1].confidence, 1.0);
+
+        let custom = relation_regex(
+            Some(r"(?P<from>\w+) (?P<type>blocks) (?P<to>\w+)"),
+            RelationGrammar::Rel,
+        )
+        .unwrap();
+        let relations = parse_relations("Auth blocks Billing", &custom);
+        assert_eq!(relations[0].to, "Billing");
+
+        let err =
+            relation_regex(Some(r"(?P<from>\w+)->(?P<to>\w+)"), RelationGrammar::Rel).unwrap_err();
+        assert!(err.to_string().contains("missing type"));
+    }
+
+    #[test]
+    fn compute_decay_score_bounds() {
+        let linear = |days| compute_decay_score(days, DecayFn::Linear, DEFAULT_DECAY_SCALE);
+        assert_eq!(linear(0.0), 0.0);
+        assert!((linear(45.0) - 0.5).abs() < 1e-6);
+        assert_eq!(linear(200.0), 1.0);
+    }
+
+    #[test]
+    fn decay_curves_differ_and_stay_in_unit_range() {
+        for curve in [DecayFn::Linear, DecayFn::Exponential, DecayFn::Sigmoid] {
+            for days in [0.0, 10.0, 45.0, 90.0, 365.0, 5000.0] {
+                let score = compute_decay_score(days, curve, 90.0);
+                assert!((0.0..=1.0).contains(&score), "{} at {} days", curve, days);
+            }
+        }
+        let linear = compute_decay_score(45.0, DecayFn::Linear, 90.0);
+        let exponential = compute_decay_score(45.0, DecayFn::Exponential, 90.0);
+        assert!((exponential - (1.0 - (-0.5f64).exp())).abs() < 1e-12);
+        assert!((linear - exponential).abs() > 0.05);
+        assert!((compute_decay_score(90.0, DecayFn::Sigmoid, 90.0) - 0.5).abs() < 1e-12);
+    }
+}
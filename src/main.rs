@@ -1,45 +1,18 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Duration, NaiveDate, Utc};
-use clap::{Parser, Subcommand, ValueEnum};
-use once_cell::sync::Lazy;
-use regex::Regex;
+use chrono::Utc;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
 use serde::{Deserialize, Serialize};
-use serde_json;
-use serde_yaml;
-use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
-use std::fmt;
-use std::fs::{self, OpenOptions};
-use std::io::Write;
+use std::fs;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::time::{Duration as StdDuration, Instant, SystemTime};
-use walkdir::WalkDir;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
 
-const NOTE_DIRS: &[&str] = &[
-    "00_Inbox",
-    "10_Projects",
-    "20_Areas",
-    "30_Resources",
-    "99_Archives",
-];
+use exomind::*;
 
-const INBOX_DIR: &str = "00_Inbox";
-const ARCHIVE_INBOX_DIR: &str = "99_Archives/Inbox";
-const CONSOLIDATED_PREFIX: &str = "consolidated";
-const METADATA_PREFIX: &str = "<!-- lifecycle";
-const DECAY_THRESHOLD_DAYS: u64 = 7;
-const CONSOLIDATE_LOOKBACK_DAYS: u64 = 7;
-
-static TOKEN_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"[A-Za-z0-9_-]+").unwrap());
-static WIKILINK_REGEX: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"\[\[([^\]|#]+)(?:#[^\]|]+)?(?:\|[^\]]+)?\]\]").unwrap());
-
-static RELATION_REGEX: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(
-        r"REL:([A-Za-z0-9_]+)\((?P<from>.+?)\s*->\s*(?P<to>.+?)\)\[(?P<confidence>[0-9.]+)\]",
-    )
-    .unwrap()
-});
+const CONFIG_FILE: &str = "exom.toml";
 
 #[derive(Parser)]
 #[command(name = "exom", version = env!("CARGO_PKG_VERSION"), about = "Rust-first ExoMind runtime")]
@@ -48,144 +21,1123 @@ struct Cli {
     command: Commands,
 }
 
+/// Commands with many flags keep them in a boxed `*Args` struct; inlined,
+/// they made the derived parser's frames overflow a 2 MiB test thread.
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize the opinionated workspace layout and neural cache folders
     Init {
         #[arg(long, default_value = ".")]
         path: PathBuf,
+        /// Add example project, area, and resource notes (existing files are kept)
+        #[arg(long, default_value_t = false)]
+        with_templates: bool,
     },
     /// Index notes into the graph cache
-    Index {
+    Index(Box<IndexArgs>),
+    /// Watch the note folders and re-index after changes settle
+    Watch(Box<WatchArgs>),
+    /// Validate a graph built by another tool and write it as an exom graph
+    Import {
+        /// Graph JSON in the import schema (see `ImportGraph`)
         #[arg(long)]
-        notes_root: PathBuf,
-        #[arg(long, default_value = ".neural")]
-        out_root: PathBuf,
+        input: PathBuf,
+        #[arg(long, default_value = ".neural/graph.json")]
+        out: PathBuf,
+        /// Vault the node paths point into; used to fill in missing TF-IDF vectors
+        #[arg(long)]
+        notes_root: Option<PathBuf>,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Export an existing graph for visualization tools
+    Export {
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        #[arg(long, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+        /// Output file; defaults to the graph path with the format's extension.
+        /// With a CSV flag and no `--out`, only the CSV files are written.
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Write `id,title,stem,path,indegree` rows to this CSV file
+        #[arg(long)]
+        nodes_csv: Option<PathBuf>,
+        /// Write `src,dst,kind,confidence` rows to this CSV file
+        #[arg(long)]
+        edges_csv: Option<PathBuf>,
+        /// Keep only these edge kinds, e.g. `CAUSED_BY,RELATED_TO`, and the notes they join
+        #[arg(long, value_delimiter = ',')]
+        only_kinds: Vec<String>,
     },
     /// Capture quick notes with relation extraction
-    Capture {
+    Capture(Box<CaptureArgs>),
+    /// Recall context from an existing graph
+    Recall(Box<RecallArgs>),
+    /// Open the note behind a recall hit in $EDITOR
+    Open {
         #[arg(long)]
-        input: Option<String>,
+        query: String,
+        /// 1-based position of the hit to open
+        #[arg(long, default_value_t = 1)]
+        rank: usize,
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        /// Print the absolute path instead of launching an editor
+        #[arg(long, default_value_t = false)]
+        print_only: bool,
+    },
+    /// Search note bodies for a substring or regex, without needing a graph
+    Search(Box<SearchArgs>),
+    /// List the notes that link to a given note
+    Backlinks {
+        /// Note id, title, or stem
         #[arg(long)]
-        file: Option<PathBuf>,
+        note: String,
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Rank the notes nearest to a note by link distance and vector similarity
+    Related {
+        /// Note id, title, or stem
         #[arg(long)]
-        out_note: Option<PathBuf>,
-        #[arg(long, default_value = ".")]
-        notes_root: PathBuf,
+        note: String,
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        #[arg(long, default_value = "10")]
+        topk: usize,
+        /// Weight of link proximity (1 / hops, ignoring edge direction)
+        #[arg(long, default_value_t = 1.0)]
+        graph_weight: f64,
+        /// Weight of cosine similarity between the notes' TF-IDF vectors
+        #[arg(long, default_value_t = 1.0)]
+        semantic_weight: f64,
         #[arg(long, default_value_t = false)]
         json: bool,
     },
-    /// Recall context from an existing graph
-    Recall {
+    /// List notes linked from the same source notes as a given note
+    Siblings {
+        /// Note id, title, or stem
         #[arg(long)]
-        query: String,
+        note: String,
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        /// Maximum siblings to list (0 = all)
         #[arg(long, default_value = "10")]
         topk: usize,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Show a note's highest-weight TF-IDF tokens from the graph
+    Inspect {
+        /// Note id, title, or stem
+        #[arg(long)]
+        note: String,
         #[arg(long, default_value = ".neural/graph.json")]
         graph: PathBuf,
-        #[arg(long, default_value = "1.0")]
-        lexical_weight: f64,
-        #[arg(long, default_value = "1.0")]
-        graph_weight: f64,
-        #[arg(long, default_value = "1.0")]
-        semantic_weight: f64,
+        /// How many tokens to list; 0 lists the whole vector
+        #[arg(long, default_value = "10")]
+        top_tokens: usize,
         #[arg(long, default_value_t = false)]
         json: bool,
     },
-    /// Validate the runtime environment
-    Doctor {
-        #[arg(long, default_value = ".")]
-        notes_root: PathBuf,
+    /// List frontmatter tags or the notes carrying one
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// Summarize vault and graph health
+    Stats {
         #[arg(long, default_value = ".neural/graph.json")]
         graph: PathBuf,
+        /// How many of the most-linked notes to list
+        #[arg(long, default_value_t = 5)]
+        top: usize,
         #[arg(long, default_value_t = false)]
         json: bool,
     },
-    /// Run recall benchmark against a labeled dataset
-    Benchmark {
+    /// Count connected components, treating links as undirected, and list orphans
+    Components {
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        /// Leave ghost nodes, and the edges into them, out of the count
+        #[arg(long, default_value_t = false)]
+        exclude_ghosts: bool,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Show how the graph grew across index runs
+    History {
+        #[arg(long, default_value = ".neural")]
+        out_root: PathBuf,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Rewrite graph.json as compact JSON, optionally without TF-IDF vectors
+    Compact {
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        /// Output file; defaults to rewriting `--graph` in place
         #[arg(long)]
-        dataset: PathBuf,
+        out: Option<PathBuf>,
+        /// Drop the TF-IDF vectors, leaving a graph-only index
+        #[arg(long, default_value_t = false)]
+        no_semantic: bool,
+        /// Round TF-IDF weights to f32 precision
+        #[arg(long, default_value_t = false)]
+        quantize: bool,
+    },
+    /// List unresolved `[[links]]`, grouped by the missing target
+    LinkCheck {
         #[arg(long, default_value = ".neural/graph.json")]
         graph: PathBuf,
+        /// Write an empty note in `00_Inbox` for each missing target
+        #[arg(long, default_value_t = false, requires = "notes_root")]
+        create_stubs: bool,
+        #[arg(long)]
+        notes_root: Option<PathBuf>,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Compare two graphs: added and removed nodes and edges, changed titles
+    Diff {
+        #[arg(long)]
+        old: PathBuf,
         #[arg(long)]
+        new: PathBuf,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Report pairs of notes with near-identical content
+    Dedupe {
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        #[arg(long, default_value = "0.9")]
+        threshold: f64,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Interactive recall loop that keeps the graph loaded between queries
+    Repl {
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        #[arg(long, default_value = "10")]
         topk: usize,
+    },
+    /// Serve recall over HTTP as a JSON API (`GET /recall?q=...`, `GET /healthz`)
+    Serve {
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(long, default_value = "8080")]
+        port: u16,
+        /// Expect a graph built with `exom index --stem`
+        #[arg(long, default_value_t = false)]
+        stem: bool,
+    },
+    /// Validate the runtime environment
+    Doctor(Box<DoctorArgs>),
+    /// Run recall benchmark against a labeled dataset
+    Benchmark(Box<BenchmarkArgs>),
+    /// Compare two `benchmark --report-json` files query by query
+    BenchCompare {
+        /// Baseline report
+        #[arg(long)]
+        old: PathBuf,
+        /// Report from the changed settings
+        #[arg(long)]
+        new: PathBuf,
         #[arg(long, default_value_t = false)]
         json: bool,
     },
     /// Manage lifecycle states for inbox notes
-    Lifecycle {
-        #[arg(long, default_value_t = LifecycleMode::Consolidate)]
-        mode: LifecycleMode,
-        #[arg(long, default_value_t = 30)]
-        older_than_days: u64,
+    Lifecycle(Box<LifecycleArgs>),
+    /// List the most-decayed notes, from their lifecycle markers, to revisit
+    Review {
+        #[arg(long, default_value = ".")]
+        notes_root: PathBuf,
+        /// How many notes to list; 0 lists every marked note
+        #[arg(long, default_value_t = 10)]
+        topk: usize,
+        /// Scan the whole notes root instead of just the inbox
+        #[arg(long, default_value_t = false)]
+        all: bool,
+        /// With `--all`, also scan `99_Archives`
+        #[arg(long, default_value_t = false, requires = "all")]
+        include_archives: bool,
+        /// File extension of notes, without the dot (repeatable; replaces `md`)
+        #[arg(long = "ext", default_value = "md")]
+        ext: Vec<String>,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Summarize notes and relations added since a git ref (vault must be in git)
+    Digest {
         #[arg(long, default_value = ".")]
         notes_root: PathBuf,
+        /// Git ref to compare HEAD against, e.g. `HEAD~5` or `main@{1.week.ago}`
+        #[arg(long)]
+        since_commit: String,
+        /// File extension of notes, without the dot (repeatable; replaces `md`)
+        #[arg(long = "ext", default_value = "md")]
+        ext: Vec<String>,
         #[arg(long, default_value_t = false)]
         json: bool,
     },
+    /// Fold one note into another, retarget links to it, and archive it
+    Merge(Box<MergeArgs>),
+    /// Rename a note file and point every link at its new stem
+    Rename(Box<RenameArgs>),
+    /// Print a machine-readable description of the graph.json format
+    Schema {
+        #[arg(long, default_value_t = SchemaFormat::JsonSchema)]
+        format: SchemaFormat,
+    },
+    /// Inspect the `exom.toml` defaults found from the current directory
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
 }
 
-#[derive(Clone, ValueEnum, Serialize)]
-#[serde(rename_all = "lowercase")]
-enum LifecycleMode {
-    Decay,
-    Consolidate,
-    Archive,
+#[derive(Args)]
+struct IndexArgs {
+    /// Vault to index; repeat to merge several vaults into one graph
+    #[arg(long, required = true)]
+    notes_root: Vec<PathBuf>,
+    /// With several roots, let `[[...]]` links resolve into other vaults
+    #[arg(long, default_value_t = false)]
+    cross_link: bool,
+    /// Skip notes whose path under the notes root matches this glob (repeatable)
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// File extension of notes, without the dot (repeatable; replaces `md`)
+    #[arg(long = "ext", default_value = "md")]
+    ext: Vec<String>,
+    /// Only collect notes this many levels below each note folder (1 = top level)
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// Threads used to read and tokenize notes (default: one per CPU)
+    #[arg(long)]
+    jobs: Option<usize>,
+    /// Add INFERRED_RELATED edges between notes with similar vocabulary
+    #[arg(long, default_value_t = false)]
+    infer_related: bool,
+    /// Minimum TF-IDF cosine similarity for an inferred edge
+    #[arg(long, default_value_t = 0.3)]
+    related_threshold: f64,
+    /// Most inferred neighbours kept per note
+    #[arg(long, default_value_t = 5)]
+    related_top_n: usize,
+    /// JSONL of precomputed note vectors, one `{"id": ..., "vector": [...]}` per line
+    #[arg(long)]
+    embeddings: Option<PathBuf>,
+    /// What unresolved links add: ghost nodes and edges, edges only, or nothing
+    #[arg(long, default_value_t = GhostMode::Keep)]
+    ghosts: GhostMode,
+    #[arg(long, default_value = ".neural")]
+    out_root: PathBuf,
+    /// Write the graph here instead of `<out-root>/graph.json`
+    #[arg(long)]
+    graph_file: Option<PathBuf>,
+    /// Ignore the index manifest and re-read every note
+    #[arg(long, default_value_t = false)]
+    force: bool,
+    /// Apply English Porter stemming to tokens
+    #[arg(long, default_value_t = false)]
+    stem: bool,
+    /// Drop the words listed in this file (one or more per line, `#` comments)
+    #[arg(long)]
+    stopwords: Option<PathBuf>,
+    /// Drop a built-in list of common English words
+    #[arg(long, default_value_t = false)]
+    default_stopwords: bool,
+    /// Split words by Unicode rules instead of ASCII `[A-Za-z0-9_-]` runs
+    #[arg(long, default_value_t = false)]
+    unicode_tokens: bool,
+    /// Drop tokens with fewer characters than this
+    #[arg(long, default_value_t = 1)]
+    min_token_len: usize,
+    /// Drop tokens made only of digits, such as years and page numbers
+    #[arg(long, default_value_t = false)]
+    drop_numeric: bool,
+    /// Store adjacent-token pairs so recall can reward phrase matches
+    #[arg(long, default_value_t = false)]
+    positions: bool,
+    /// Split notes at `##`/`###` headings into section nodes linked by CONTAINS
+    #[arg(long, default_value_t = false)]
+    chunk_by_heading: bool,
+    /// Leave fenced code blocks out of the indexed text (files are untouched)
+    #[arg(long, default_value_t = false)]
+    strip_code_blocks: bool,
+    /// Fence language `--strip-code-blocks` keeps (repeatable; replaces `yaml`)
+    #[arg(
+        long = "keep-fence",
+        default_value = "yaml",
+        requires = "strip_code_blocks"
+    )]
+    keep_fences: Vec<String>,
+    /// Also leave out inline `code` spans
+    #[arg(long, default_value_t = false, requires = "strip_code_blocks")]
+    strip_inline_code: bool,
+    /// Also write the graph as GraphML or DOT next to graph.json
+    #[arg(long, default_value_t = GraphFormat::Json)]
+    format: GraphFormat,
+    /// Print a running count to stderr while working
+    #[arg(long, default_value_t = false)]
+    progress: bool,
+    /// Skip the summary lines printed on success
+    #[arg(long, default_value_t = false)]
+    quiet: bool,
 }
 
-impl fmt::Display for LifecycleMode {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let label = match self {
-            LifecycleMode::Decay => "decay",
-            LifecycleMode::Consolidate => "consolidate",
-            LifecycleMode::Archive => "archive",
-        };
-        write!(f, "{}", label)
+#[derive(Args)]
+struct WatchArgs {
+    #[arg(long, default_value = ".")]
+    notes_root: PathBuf,
+    #[arg(long, default_value = ".neural")]
+    out_root: PathBuf,
+    /// Quiet period after the last change before re-indexing
+    #[arg(long, default_value_t = 500)]
+    debounce_ms: u64,
+    /// Skip notes whose path under the notes root matches this glob (repeatable)
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// File extension of notes, without the dot (repeatable; replaces `md`)
+    #[arg(long = "ext", default_value = "md")]
+    ext: Vec<String>,
+    #[arg(long, default_value_t = false)]
+    stem: bool,
+    /// Drop the words listed in this file (one or more per line, `#` comments)
+    #[arg(long)]
+    stopwords: Option<PathBuf>,
+    /// Drop a built-in list of common English words
+    #[arg(long, default_value_t = false)]
+    default_stopwords: bool,
+    #[arg(long, default_value_t = false)]
+    unicode_tokens: bool,
+    #[arg(long, default_value_t = false)]
+    positions: bool,
+}
+
+#[derive(Args)]
+struct CaptureArgs {
+    #[arg(long)]
+    input: Option<String>,
+    #[arg(long)]
+    file: Option<PathBuf>,
+    #[arg(long)]
+    out_note: Option<PathBuf>,
+    #[arg(long, default_value = ".")]
+    notes_root: PathBuf,
+    /// Drop relations whose confidence is below this value
+    #[arg(long, default_value_t = 0.0)]
+    min_confidence: f64,
+    /// Also record the reverse of relations with a symmetric type
+    #[arg(long, default_value_t = false)]
+    synthesize_inverse: bool,
+    /// Relation type treated as symmetric by `--synthesize-inverse` (repeatable)
+    #[arg(long = "symmetric-type", default_values_t = [String::from("RELATED_TO")])]
+    symmetric_types: Vec<String>,
+    /// Note id or title to link the capture to as `[[parent]]`
+    #[arg(long)]
+    context: Option<String>,
+    /// Graph used to check that `--context` names an existing note
+    #[arg(long)]
+    graph: Option<PathBuf>,
+    /// Built-in syntax for relations in the captured text
+    #[arg(long, default_value_t = RelationGrammar::Rel)]
+    relation_grammar: RelationGrammar,
+    /// Custom relation regex with named groups `from`, `to`, `type` and
+    /// optionally `confidence`; overrides `--relation-grammar`
+    #[arg(long, conflicts_with = "relation_grammar")]
+    relation_pattern: Option<String>,
+    /// Allowed relation types, one or more per line (`#` comments); parsed
+    /// types are normalized to `UPPER_SNAKE` before matching
+    #[arg(long)]
+    relation_types: Option<PathBuf>,
+    /// Drop relations whose type is not in `--relation-types`
+    #[arg(long, default_value_t = false, requires = "relation_types")]
+    strict_types: bool,
+    /// Entry template using `{{body}}` (required), `{{timestamp}}`, `{{date}}`,
+    /// `{{relations_yaml}}`, and `{{context}}`
+    #[arg(long)]
+    template: Option<PathBuf>,
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct RecallArgs {
+    #[arg(long, required_unless_present = "exact")]
+    query: Option<String>,
+    /// Skip scoring and list notes whose title or stem is exactly this
+    #[arg(long, conflicts_with = "query")]
+    exact: Option<String>,
+    /// Compare `--exact` case-insensitively
+    #[arg(long, default_value_t = false, requires = "exact")]
+    ignore_case: bool,
+    /// Most results returned; 0 returns every note with a positive score
+    #[arg(long, default_value = "10")]
+    topk: usize,
+    #[arg(long, default_value = ".neural/graph.json")]
+    graph: PathBuf,
+    /// Preset weight triple; explicit `--*-weight` flags override it
+    #[arg(long, default_value_t = RecallProfile::Balanced)]
+    profile: RecallProfile,
+    #[arg(long)]
+    lexical_weight: Option<f64>,
+    #[arg(long)]
+    graph_weight: Option<f64>,
+    #[arg(long)]
+    semantic_weight: Option<f64>,
+    #[arg(long, default_value_t = LexicalMode::Overlap)]
+    lexical_mode: LexicalMode,
+    #[arg(long, default_value_t = BM25_K1)]
+    bm25_k1: f64,
+    #[arg(long, default_value_t = BM25_B)]
+    bm25_b: f64,
+    #[arg(long, default_value_t = SemanticMode::Dot)]
+    semantic_mode: SemanticMode,
+    /// Rescale each score component across the candidates before weighting
+    #[arg(long, default_value_t = ScoreNormalization::None)]
+    normalize: ScoreNormalization,
+    /// Indegree factor for typed-relation edges relative to wikilinks
+    #[arg(long, default_value = "1.0")]
+    relation_weight: f64,
+    /// Graph-score factor for one edge kind, e.g. `UNRESOLVED_LINK=0` (repeatable)
+    #[arg(long = "edge-weight", value_name = "KIND=FACTOR")]
+    edge_weight: Vec<String>,
+    /// How the graph component scores a node: capped indegree or PageRank
+    #[arg(long, default_value_t = GraphMode::Indegree)]
+    graph_mode: GraphMode,
+    /// Let PageRank flow through unresolved links into ghost nodes
+    #[arg(long, default_value_t = false)]
+    pagerank_ghosts: bool,
+    /// Lexical bonus per adjacent query pair (needs `index --positions`)
+    #[arg(long, default_value = "1.0")]
+    proximity_weight: f64,
+    /// Give partial lexical credit to title words within a small edit distance
+    #[arg(long, default_value_t = false)]
+    fuzzy: bool,
+    /// Largest edit distance `--fuzzy` accepts
+    #[arg(long, default_value_t = 2)]
+    fuzzy_distance: usize,
+    /// Only consider notes modified on or after this date (YYYY-MM-DD)
+    #[arg(long)]
+    since: Option<String>,
+    /// JSON object mapping a term to its equivalents, e.g. {"kubernetes": ["k8s"]}
+    #[arg(long)]
+    synonyms: Option<PathBuf>,
+    /// Score factor applied to terms added by `--synonyms`
+    #[arg(long, default_value_t = 0.5)]
+    synonym_weight: f64,
+    /// Drop hits scoring below this before `--topk` truncates
+    #[arg(long, default_value_t = 0.0)]
+    min_score: f64,
+    /// Skip this many ranked hits before `--topk`; ranks stay absolute
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
+    /// Show the best-matching line of each hit (reads the note files)
+    #[arg(long, default_value_t = false)]
+    snippets: bool,
+    /// Show each hit's estimated reading time at 200 words per minute
+    #[arg(long, default_value_t = false)]
+    reading_time: bool,
+    /// Text columns to print, in order, e.g. `path,score` (JSON is unaffected)
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<RecallColumn>,
+    /// Print the weights in effect and break each hit's score down into
+    /// lexical, graph, and semantic parts
+    #[arg(long, default_value_t = false)]
+    explain: bool,
+    /// Propagate scores to graph neighbours for this many hops before ranking
+    #[arg(long, default_value_t = 0)]
+    spread: usize,
+    /// Share of a node's score passed across one edge per hop
+    #[arg(long, default_value_t = 0.5)]
+    spread_decay: f64,
+    /// Semantic weight of a query term in the title relative to the body (1 = no boost)
+    #[arg(long, default_value_t = 1.0)]
+    title_boost: f64,
+    /// JSON vector for the query, compared with note embeddings by cosine
+    #[arg(long)]
+    query_embedding_file: Option<PathBuf>,
+    /// Weight of the query-embedding similarity
+    #[arg(long, default_value_t = 1.0)]
+    embedding_weight: f64,
+    /// Halve a note's score for every this many days since it was modified
+    #[arg(long, value_name = "N")]
+    recency_halflife_days: Option<f64>,
+    /// Expand the query with top terms from a first pass, then recall again
+    #[arg(long, default_value_t = false)]
+    prf: bool,
+    /// Top results `--prf` harvests terms from
+    #[arg(long, default_value_t = 3)]
+    prf_docs: usize,
+    /// Feedback terms `--prf` adds to the query
+    #[arg(long, default_value_t = 5)]
+    prf_terms: usize,
+    /// Score factor applied to feedback terms
+    #[arg(long, default_value_t = 0.3)]
+    prf_weight: f64,
+    /// Expect a graph built with `exom index --stem`
+    #[arg(long, default_value_t = false)]
+    stem: bool,
+    #[arg(long, default_value_t = false)]
+    json: bool,
+    /// Emit one compact JSON object per result line
+    #[arg(long, default_value_t = false, conflicts_with = "json")]
+    jsonl: bool,
+    /// Write results to this file instead of stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct SearchArgs {
+    #[arg(long)]
+    pattern: String,
+    #[arg(long, default_value_t = false)]
+    regex: bool,
+    #[arg(long, default_value_t = false)]
+    ignore_case: bool,
+    /// Lines of context to print around each match
+    #[arg(long, default_value_t = 1)]
+    context: usize,
+    /// Skip this many matching lines before printing
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
+    /// Print at most this many matching lines (0 = all)
+    #[arg(long, default_value_t = 0)]
+    limit: usize,
+    /// Only consider notes modified on or after this date (YYYY-MM-DD)
+    #[arg(long)]
+    since: Option<String>,
+    #[arg(long, default_value = ".")]
+    notes_root: PathBuf,
+    /// Skip notes whose path under the notes root matches this glob (repeatable)
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// File extension of notes, without the dot (repeatable; replaces `md`)
+    #[arg(long = "ext", default_value = "md")]
+    ext: Vec<String>,
+    #[arg(long, default_value_t = false)]
+    json: bool,
+    /// Emit one compact JSON object per result line
+    #[arg(long, default_value_t = false, conflicts_with = "json")]
+    jsonl: bool,
+    /// Write results to this file instead of stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct DoctorArgs {
+    #[arg(long, default_value = ".")]
+    notes_root: PathBuf,
+    /// Skip notes whose path under the notes root matches this glob (repeatable)
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// File extension of notes, without the dot (repeatable; replaces `md`)
+    #[arg(long = "ext", default_value = "md")]
+    ext: Vec<String>,
+    #[arg(long, default_value = ".neural/graph.json")]
+    graph: PathBuf,
+    /// Expect the graph to have been built with stemming
+    #[arg(long, default_value_t = false)]
+    stem: bool,
+    /// Create missing folders and build a missing graph
+    #[arg(long, default_value_t = false)]
+    fix: bool,
+    /// Most inbox notes older than the decay threshold before `inbox_backlog` fails
+    #[arg(long, default_value_t = DEFAULT_MAX_INBOX)]
+    max_inbox: usize,
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct BenchmarkArgs {
+    #[arg(long)]
+    dataset: PathBuf,
+    #[arg(long, default_value = ".neural/graph.json")]
+    graph: PathBuf,
+    #[arg(long)]
+    topk: usize,
+    /// Also write a Markdown summary of the run to this file
+    #[arg(long, conflicts_with = "jsonl")]
+    report_md: Option<PathBuf>,
+    /// Also write the full JSON report to this file, for `bench-compare`
+    #[arg(long, conflicts_with = "jsonl")]
+    report_json: Option<PathBuf>,
+    #[arg(long, default_value_t = false)]
+    json: bool,
+    /// Emit one compact JSON object per result line
+    #[arg(long, default_value_t = false, conflicts_with = "json")]
+    jsonl: bool,
+    /// Write results to this file instead of stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Print a running count to stderr while working
+    #[arg(long, default_value_t = false)]
+    progress: bool,
+    /// Skip the summary lines printed on success
+    #[arg(long, default_value_t = false)]
+    quiet: bool,
+}
+
+#[derive(Args)]
+struct LifecycleArgs {
+    #[arg(long, default_value_t = LifecycleMode::Consolidate)]
+    mode: LifecycleMode,
+    #[arg(long, default_value_t = 30)]
+    older_than_days: u64,
+    #[arg(long, default_value = ".")]
+    notes_root: PathBuf,
+    /// Smallest recorded decay score `--mode prune` removes
+    #[arg(long, default_value_t = 1.0)]
+    prune_threshold: f64,
+    /// Split the `--mode consolidate` summary into one section per group
+    #[arg(long, default_value_t = ConsolidateGroup::None)]
+    group_by: ConsolidateGroup,
+    /// Quote this many body lines of each note in the `--mode consolidate` summary
+    #[arg(long, default_value_t = 0)]
+    excerpt_lines: usize,
+    /// First day (YYYY-MM-DD) whose moves `--mode restore` brings back
+    #[arg(long)]
+    from: Option<String>,
+    /// Last day (YYYY-MM-DD) whose moves `--mode restore` brings back
+    #[arg(long)]
+    to: Option<String>,
+    /// Curve mapping a note's age to its `--mode decay` score
+    #[arg(long, default_value_t = DecayFn::Linear)]
+    decay_fn: DecayFn,
+    /// Age in days that sets the curve's pace: full decay for linear, the
+    /// e-folding time for exponential, the midpoint for sigmoid
+    #[arg(long, default_value_t = DEFAULT_DECAY_SCALE)]
+    decay_scale: f64,
+    /// File extension of notes, without the dot (repeatable; replaces `md`)
+    #[arg(long = "ext", default_value = "md")]
+    ext: Vec<String>,
+    /// Report what would change without touching any files
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct MergeArgs {
+    /// Note merged away: a path under the notes root or a note stem
+    #[arg(long)]
+    from: String,
+    /// Note that receives the body and the links
+    #[arg(long)]
+    to: String,
+    #[arg(long, default_value = ".")]
+    notes_root: PathBuf,
+    #[arg(long, default_value = ".neural")]
+    out_root: PathBuf,
+    /// Skip notes whose path under the notes root matches this glob (repeatable)
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// File extension of notes, without the dot (repeatable; replaces `md`)
+    #[arg(long = "ext", default_value = "md")]
+    ext: Vec<String>,
+    /// Report what would change without touching any files
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct RenameArgs {
+    /// Note to rename: a path under the notes root or a note stem
+    #[arg(long)]
+    from: String,
+    /// New file stem; the note keeps its folder and extension
+    #[arg(long)]
+    to: String,
+    #[arg(long, default_value = ".")]
+    notes_root: PathBuf,
+    #[arg(long, default_value = ".neural")]
+    out_root: PathBuf,
+    /// Skip notes whose path under the notes root matches this glob (repeatable)
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// File extension of notes, without the dot (repeatable; replaces `md`)
+    #[arg(long = "ext", default_value = "md")]
+    ext: Vec<String>,
+    /// Report what would change without touching any files
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the effective defaults and the file they came from
+    Show {
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TagAction {
+    /// Print every tag with the number of notes carrying it
+    List {
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Print the notes carrying a tag (case-insensitive)
+    Notes {
+        #[arg(long)]
+        tag: String,
+        #[arg(long, default_value = ".neural/graph.json")]
+        graph: PathBuf,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            // Scripts can tell "nothing to read yet" apart from a real failure.
+            match err.downcast_ref::<ExomError>() {
+                Some(ExomError::GraphNotFound { .. } | ExomError::NoteNotFound { .. }) => {
+                    ExitCode::from(2)
+                }
+                _ => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+/// Running `label done/total` count on stderr, redrawn in place and ended
+/// with a newline once the total is reached.
+fn progress_bar(label: &'static str) -> Arc<ProgressFn> {
+    Arc::new(move |done, total| {
+        // Redraw about a hundred times at most, however large the run.
+        let step = (total / 100).max(1);
+        if done.is_multiple_of(step) || done == total {
+            eprint!("\r{} {}/{}", label, done, total);
+            if done == total {
+                eprintln!();
+            }
+        }
+    })
+}
+
+fn report_unmatched_embeddings(result: &IndexResult) {
+    if result.unmatched_embeddings > 0 {
+        eprintln!(
+            "WARN {} embeddings name no indexed note",
+            result.unmatched_embeddings
+        );
     }
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+fn run() -> Result<()> {
+    let config = find_config(&env::current_dir()?)?;
+    let matches = cli_command(config.as_ref()).get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
 
     match cli.command {
-        Commands::Init { path } => {
+        Commands::Init {
+            path,
+            with_templates,
+        } => {
             let root = normalize_path(path);
             init_workflow(&root)?;
+            if with_templates {
+                let written = write_seed_notes(&root)?;
+                println!(
+                    "TEMPLATES written={} skipped={}",
+                    written.len(),
+                    SEED_NOTES.len() - written.len()
+                );
+            }
             println!("INIT_OK {}", root.display());
         }
-        Commands::Index {
-            notes_root,
-            out_root,
-        } => {
+        Commands::Index(args) => {
+            let IndexArgs {
+                notes_root,
+                embeddings,
+                ghosts,
+                cross_link,
+                exclude,
+                ext,
+                max_depth,
+                jobs,
+                infer_related,
+                related_threshold,
+                related_top_n,
+                out_root,
+                graph_file,
+                force,
+                stem,
+                stopwords,
+                default_stopwords,
+                unicode_tokens,
+                min_token_len,
+                drop_numeric,
+                positions,
+                chunk_by_heading,
+                strip_code_blocks,
+                keep_fences,
+                strip_inline_code,
+                format,
+                progress,
+                quiet,
+            } = *args;
+            let notes_roots: Vec<PathBuf> = notes_root.into_iter().map(normalize_path).collect();
+            let out_root = normalize_path(out_root);
+            let options = IndexOptions {
+                force,
+                tokens: TokenOptions {
+                    stem,
+                    stopwords: load_stopwords(stopwords.as_deref(), default_stopwords)?,
+                    unicode: unicode_tokens,
+                    min_len: min_token_len,
+                    drop_numeric,
+                },
+                positions,
+                chunk_by_heading,
+                strip_code: strip_code_blocks.then(|| CodeStripping {
+                    keep_fences: keep_fences.iter().map(|lang| lang.to_lowercase()).collect(),
+                    inline: strip_inline_code,
+                }),
+                cross_link,
+                exclude: build_exclude_set(&exclude)?,
+                extensions: NoteExtensions::new(&ext),
+                max_depth,
+                jobs,
+                related: infer_related.then_some(RelatedOptions {
+                    threshold: related_threshold,
+                    top_n: related_top_n,
+                }),
+                graph_file: graph_file.map(normalize_path),
+                embeddings: embeddings
+                    .map(|path| load_embeddings(&normalize_path(path)))
+                    .transpose()?,
+                ghosts,
+                progress: progress.then(|| progress_bar("indexed")),
+            };
+            for root in &notes_roots {
+                if let Some(hint) = uninitialized_workspace_hint(root) {
+                    eprintln!("WARN {}", hint);
+                }
+            }
+            let result = index_vaults(&notes_roots, &out_root, &options)?;
+            report_unmatched_embeddings(&result);
+            if !quiet {
+                println!(
+                    "INDEX_OK notes={} nodes={} edges={} reused={} rebuilt={} excluded={} -> {}",
+                    result.notes,
+                    result.nodes,
+                    result.edges,
+                    result.reused,
+                    result.rebuilt,
+                    result.excluded,
+                    result.graph_path.display()
+                );
+            }
+            if format != GraphFormat::Json {
+                let graph_data = load_graph(&result.graph_path)?;
+                let target = result.graph_path.with_extension(format.extension());
+                write_graph_export(&graph_data, format, &target)?;
+                if !quiet {
+                    println!("EXPORT_OK {} -> {}", format, target.display());
+                }
+            }
+        }
+        Commands::Watch(args) => {
+            let WatchArgs {
+                notes_root,
+                out_root,
+                debounce_ms,
+                exclude,
+                ext,
+                stem,
+                stopwords,
+                default_stopwords,
+                unicode_tokens,
+                positions,
+            } = *args;
             let notes_root = normalize_path(notes_root);
             let out_root = normalize_path(out_root);
-            let result = index_graph_data(&notes_root, &out_root)?;
-            println!(
-                "INDEX_OK notes={} nodes={} edges={} -> {}",
-                result.notes,
-                result.nodes,
-                result.edges,
-                result.graph_path.display()
+            // Re-index the way the last `index` run did; flags given here win.
+            let mut options = stored_index_options(
+                &out_root,
+                build_exclude_set(&exclude)?,
+                NoteExtensions::new(&ext),
             );
+            options.tokens.stem |= stem;
+            options.tokens.unicode |= unicode_tokens;
+            if stopwords.is_some() || default_stopwords {
+                options.tokens.stopwords = load_stopwords(stopwords.as_deref(), default_stopwords)?;
+            }
+            options.positions |= positions;
+            run_watch(
+                &notes_root,
+                &out_root,
+                &options,
+                StdDuration::from_millis(debounce_ms),
+                |event| match event {
+                    WatchEvent::Indexed(result) => {
+                        report_unmatched_embeddings(&result);
+                        println!(
+                            "[{}] WATCH_INDEX notes={} nodes={} edges={} reused={} rebuilt={}",
+                            Utc::now().format("%Y-%m-%d %H:%M:%S UTC"),
+                            result.notes,
+                            result.nodes,
+                            result.edges,
+                            result.reused,
+                            result.rebuilt
+                        );
+                    }
+                    WatchEvent::IndexFailed(err) => {
+                        eprintln!("WARN re-index failed: {:#}", anyhow::Error::from(err))
+                    }
+                    WatchEvent::WatcherFailed(err) => eprintln!("WARN {}", err),
+                },
+            )?;
+            println!("WATCH_STOPPED");
         }
-        Commands::Capture {
+        Commands::Import {
             input,
-            file,
-            out_note,
+            out,
             notes_root,
             json,
         } => {
+            let input = normalize_path(input);
+            let out = normalize_path(out);
+            let notes_root = notes_root.map(normalize_path);
+            let (graph, report) = import_graph(&input, notes_root.as_deref())?;
+            write_graph_export(&graph, GraphFormat::Json, &out)?;
+            if json {
+                print_json(&report)?;
+            } else {
+                println!(
+                    "IMPORT_OK nodes={} edges={} ghosts_added={} recomputed={} -> {}",
+                    report.nodes,
+                    report.edges,
+                    report.ghosts_added,
+                    report.recomputed,
+                    out.display()
+                );
+            }
+        }
+        Commands::Export {
+            graph,
+            format,
+            out,
+            nodes_csv,
+            edges_csv,
+            only_kinds,
+        } => {
+            let graph_path = normalize_path(graph);
+            let mut graph_data = require_graph(&graph_path)?;
+            if !only_kinds.is_empty() {
+                retain_edge_kinds(&mut graph_data, &only_kinds);
+            }
+            let csv_only = out.is_none() && (nodes_csv.is_some() || edges_csv.is_some());
+            if let Some(target) = nodes_csv.map(normalize_path) {
+                write_nodes_csv(&graph_data, &target)?;
+                println!("EXPORT_OK nodes-csv -> {}", target.display());
+            }
+            if let Some(target) = edges_csv.map(normalize_path) {
+                write_edges_csv(&graph_data, &target)?;
+                println!("EXPORT_OK edges-csv -> {}", target.display());
+            }
+            if !csv_only {
+                let target = out
+                    .map(normalize_path)
+                    .unwrap_or_else(|| graph_path.with_extension(format.extension()));
+                write_graph_export(&graph_data, format, &target)?;
+                println!("EXPORT_OK {} -> {}", format, target.display());
+            }
+        }
+        Commands::Capture(args) => {
+            let CaptureArgs {
+                input,
+                file,
+                out_note,
+                notes_root,
+                min_confidence,
+                synthesize_inverse,
+                symmetric_types,
+                context,
+                graph,
+                relation_grammar,
+                relation_pattern,
+                relation_types,
+                strict_types,
+                template,
+                json,
+            } = *args;
             let notes_root = normalize_path(notes_root);
+            if let Some(hint) = uninitialized_workspace_hint(&notes_root) {
+                eprintln!("WARN {}; creating the folders", hint);
+            }
             ensure_workflow_dirs(&notes_root)?;
-            let source = capture_input_text(input, file)?;
+            let stdin = std::io::stdin();
+            let piped = (!stdin.is_terminal()).then(|| stdin.lock());
+            let source = capture_input_text(input, file, piped)?;
             let target = resolve_capture_note(&notes_root, out_note);
-            let report = run_capture(&notes_root, &target, &source)?;
+            let context = match (context, graph) {
+                (Some(context), Some(graph)) => {
+                    let graph_data = require_graph(&normalize_path(graph))?;
+                    Some(
+                        resolve_capture_context(&graph_data, &context).unwrap_or_else(|| {
+                            eprintln!(
+                                "WARN capture context {:?} is not a note in the graph; linking it anyway",
+                                context
+                            );
+                            context
+                        }),
+                    )
+                }
+                (context, _) => context,
+            };
+            let options = CaptureOptions {
+                min_confidence,
+                synthesize_inverse,
+                symmetric_types,
+                context,
+                relation_regex: Some(relation_regex(
+                    relation_pattern.as_deref(),
+                    relation_grammar,
+                )?),
+                relation_types: relation_types
+                    .map(|path| RelationTypes::load(&normalize_path(path), strict_types))
+                    .transpose()?,
+                template: template
+                    .map(|path| load_capture_template(&normalize_path(path)))
+                    .transpose()?,
+            };
+            let report = run_capture(&notes_root, &target, &source, &options)?;
             if json {
                 print_json(&report)?;
             } else {
                 println!(
-                    "CAPTURE_OK note={} relations={}",
-                    report.note, report.relation_count
+                    "CAPTURE_OK note={} relations={} filtered={} synthesized={}",
+                    report.note,
+                    report.relation_count,
+                    report.filtered_count,
+                    report.synthesized_count
                 );
                 for relation in &report.relations {
                     println!(
@@ -193,57 +1145,596 @@ fn main() -> Result<()> {
                         relation.rel_type, relation.from, relation.to, relation.confidence
                     );
                 }
+                for change in &report.normalized {
+                    println!("  normalized {} -> {}", change.original, change.normalized);
+                }
+                for relation in &report.rejected {
+                    println!(
+                        "  rejected {}({} -> {}): type not allowed",
+                        relation.rel_type, relation.from, relation.to
+                    );
+                }
+            }
+        }
+        Commands::Recall(args) => {
+            let RecallArgs {
+                query,
+                exact,
+                ignore_case,
+                topk,
+                graph,
+                profile,
+                lexical_weight,
+                graph_weight,
+                semantic_weight,
+                lexical_mode,
+                bm25_k1,
+                bm25_b,
+                semantic_mode,
+                normalize,
+                relation_weight,
+                edge_weight,
+                graph_mode,
+                pagerank_ghosts,
+                proximity_weight,
+                fuzzy,
+                fuzzy_distance,
+                since,
+                synonyms,
+                synonym_weight,
+                min_score,
+                offset,
+                snippets,
+                reading_time,
+                columns,
+                explain,
+                spread,
+                spread_decay,
+                title_boost,
+                query_embedding_file,
+                embedding_weight,
+                recency_halflife_days,
+                prf,
+                prf_docs,
+                prf_terms,
+                prf_weight,
+                stem,
+                json,
+                jsonl,
+                output,
+            } = *args;
+            let graph_path = normalize_path(graph);
+            let graph_data = require_graph(&graph_path)?;
+            ensure_stemming_matches(&graph_data, stem)?;
+            let query_embedding = query_embedding_file
+                .map(|path| load_query_embedding(&normalize_path(path)))
+                .transpose()?;
+            if let Some(vector) = &query_embedding {
+                check_embedding_dimension(&graph_data, vector)?;
+            }
+            let weights =
+                profile
+                    .weights()
+                    .with_overrides(lexical_weight, graph_weight, semantic_weight);
+            let options = RecallOptions {
+                lexical_mode,
+                bm25_k1,
+                bm25_b,
+                semantic_mode,
+                normalize,
+                edge_weights: EdgeWeights::parse(relation_weight, &edge_weight)?,
+                graph_mode,
+                pagerank_ghosts,
+                proximity_weight,
+                fuzzy,
+                fuzzy_distance,
+                since: since.as_deref().map(parse_since).transpose()?,
+                synonyms: synonyms
+                    .map(|path| Synonyms::load(&path, synonym_weight))
+                    .transpose()?,
+                min_score,
+                offset,
+                snippets: snippets || columns.contains(&RecallColumn::Snippet),
+                reading_time,
+                explain,
+                spread,
+                spread_decay,
+                title_boost,
+                query_embedding,
+                embedding_weight,
+                recency_halflife_days,
+                prf: prf.then_some(PrfOptions {
+                    docs: prf_docs,
+                    terms: prf_terms,
+                    weight: prf_weight,
+                }),
+            };
+            let (query, rows) = match exact {
+                Some(title) => {
+                    let mut rows = exact_title_matches(&graph_data, &title, ignore_case, 0);
+                    page_recall_rows(&mut rows, offset, topk);
+                    (title, rows)
+                }
+                None => {
+                    let query = query.unwrap_or_default();
+                    let rows = recall_from_graph(&graph_data, &query, topk, &weights, &options);
+                    (query, rows)
+                }
+            };
+            let mut out = output_writer(output.map(normalize_path).as_deref())?;
+            if json {
+                write_json(
+                    &mut out,
+                    &RecallResponse {
+                        query,
+                        top_k: topk,
+                        results: rows,
+                    },
+                )?;
+            } else if jsonl {
+                write_jsonl(&mut out, &rows)?;
+            } else if !columns.is_empty() {
+                write_recall_columns(&mut out, &graph_data, &rows, &columns)?;
+            } else {
+                if explain {
+                    writeln!(
+                        out,
+                        "weights profile={} lexical={:.2} graph={:.2} semantic={:.2}",
+                        profile, weights.lexical, weights.graph, weights.semantic
+                    )?;
+                }
+                write_recall_rows(&mut out, &rows)?;
             }
+            out.flush()?;
         }
-        Commands::Recall {
+        Commands::Open {
             query,
-            topk,
+            rank,
+            graph,
+            print_only,
+        } => {
+            let graph_path = normalize_path(graph);
+            let graph_data = require_graph(&graph_path)?;
+            let target = resolve_recall_hit(&graph_data, &query, rank)?;
+            if print_only {
+                println!("{}", target.display());
+            } else {
+                open_in_editor(&target)?;
+            }
+        }
+        Commands::Repl { graph, topk } => {
+            let graph_path = normalize_path(graph);
+            let graph_data = LoadedGraph::new(require_graph(&graph_path)?);
+            let stdin = std::io::stdin();
+            let prompt = stdin.is_terminal();
+            if prompt {
+                eprintln!(
+                    "Loaded {} nodes. Type a query, `:weights L G S`, `:topk N`, or Ctrl-D to exit.",
+                    graph_data.nodes.len()
+                );
+            }
+            run_repl(
+                &graph_data,
+                stdin.lock(),
+                &mut std::io::stdout(),
+                topk,
+                prompt,
+            )?;
+        }
+        Commands::Serve {
+            graph,
+            host,
+            port,
+            stem,
+        } => {
+            let graph_path = normalize_path(graph);
+            let graph_data = LoadedGraph::new(require_graph(&graph_path)?);
+            ensure_stemming_matches(&graph_data, stem)?;
+            let address = format!("{}:{}", host, port);
+            let server = tiny_http::Server::http(&address)
+                .map_err(|err| anyhow::anyhow!("failed to bind {}: {}", address, err))?;
+            println!(
+                "SERVE_OK address=http://{} nodes={}",
+                address,
+                graph_data.nodes.len()
+            );
+            for request in server.incoming_requests() {
+                let (status, body) = if *request.method() == tiny_http::Method::Get {
+                    handle_api_request(&graph_data, request.url())
+                } else {
+                    api_error(405, "only GET is supported")
+                };
+                let header = tiny_http::Header::from_bytes("Content-Type", "application/json")
+                    .expect("static header is valid");
+                let response = tiny_http::Response::from_string(body)
+                    .with_status_code(status)
+                    .with_header(header);
+                if let Err(err) = request.respond(response) {
+                    eprintln!("serve: failed to send response: {}", err);
+                }
+            }
+        }
+        Commands::Search(args) => {
+            let SearchArgs {
+                pattern,
+                regex,
+                ignore_case,
+                context,
+                offset,
+                limit,
+                since,
+                notes_root,
+                exclude,
+                ext,
+                json,
+                jsonl,
+                output,
+            } = *args;
+            let notes_root = normalize_path(notes_root);
+            let matcher = build_search_regex(&pattern, regex, ignore_case)?;
+            let since = since.as_deref().map(parse_since).transpose()?;
+            let exclude = build_exclude_set(&exclude)?;
+            let extensions = NoteExtensions::new(&ext);
+            let mut hits =
+                search_notes(&notes_root, &matcher, context, since, &exclude, &extensions)?;
+            hits.drain(..offset.min(hits.len()));
+            if limit > 0 {
+                hits.truncate(limit);
+            }
+            let mut out = output_writer(output.map(normalize_path).as_deref())?;
+            if json {
+                write_json(&mut out, &hits)?;
+            } else if jsonl {
+                write_jsonl(&mut out, &hits)?;
+            } else {
+                for hit in &hits {
+                    for (line, text) in &hit.before {
+                        writeln!(out, "{}-{}- {}", hit.id, line, text)?;
+                    }
+                    writeln!(out, "{}:{}: {}", hit.id, hit.line, hit.text)?;
+                    for (line, text) in &hit.after {
+                        writeln!(out, "{}-{}- {}", hit.id, line, text)?;
+                    }
+                }
+            }
+            out.flush()?;
+        }
+        Commands::Backlinks { note, graph, json } => {
+            let graph_path = normalize_path(graph);
+            let graph_data = require_graph(&graph_path)?;
+            let target = require_node(&graph_data, &note)?;
+            let links = backlinks_for(&graph_data, &target.id);
+            if json {
+                print_json(&links)?;
+            } else {
+                write_backlinks(&mut std::io::stdout().lock(), target, &links)?;
+            }
+        }
+        Commands::Related {
+            note,
             graph,
-            lexical_weight,
+            topk,
             graph_weight,
             semantic_weight,
             json,
         } => {
             let graph_path = normalize_path(graph);
-            if !graph_path.exists() {
-                anyhow::bail!(
-                    "Graph not found: {}. Run `exom index` first.",
-                    graph_path.display()
-                );
-            }
-            let graph_data = load_graph(&graph_path)?;
+            let graph_data = require_graph(&graph_path)?;
+            let target = require_node(&graph_data, &note)?;
             let weights = RecallWeights {
-                lexical: lexical_weight,
+                lexical: 0.0,
                 graph: graph_weight,
                 semantic: semantic_weight,
             };
-            let rows = recall_from_graph(&graph_data, &query, topk, &weights);
+            let rows = related_notes(&graph_data, target, topk, &weights);
             if json {
-                print_json(&RecallResponse {
-                    query,
+                print_json(&RelatedResponse {
+                    note: target.id.clone(),
                     top_k: topk,
                     results: rows,
                 })?;
             } else {
-                for row in &rows {
-                    println!(
-                        "{:02}. score={:.2} | {} | {}",
-                        row.rank,
-                        row.score,
-                        row.title,
-                        row.path.as_deref().unwrap_or("None")
-                    );
-                }
+                println!("RELATED {} count={}", target.id, rows.len());
+                write_recall_rows(&mut std::io::stdout().lock(), &rows)?;
             }
         }
-        Commands::Doctor {
-            notes_root,
+        Commands::Siblings {
+            note,
             graph,
+            topk,
             json,
         } => {
+            let graph_path = normalize_path(graph);
+            let graph_data = require_graph(&graph_path)?;
+            let target = require_node(&graph_data, &note)?;
+            let siblings = sibling_notes(&graph_data, &target.id, topk);
+            if json {
+                print_json(&siblings)?;
+            } else {
+                println!("SIBLINGS {} count={}", target.id, siblings.len());
+                for sibling in &siblings {
+                    println!(
+                        "  {} (shared_parents={})",
+                        sibling.id, sibling.shared_parents
+                    );
+                }
+            }
+        }
+        Commands::Inspect {
+            note,
+            graph,
+            top_tokens,
+            json,
+        } => {
+            let graph_path = normalize_path(graph);
+            let graph_data = require_graph(&graph_path)?;
+            let target = require_node(&graph_data, &note)?;
+            let report = NodeInspection {
+                id: target.id.clone(),
+                title: target.title.clone(),
+                vector_size: target.semantic.len(),
+                tokens: top_tokens_of(target, top_tokens),
+            };
+            if json {
+                print_json(&report)?;
+            } else {
+                println!(
+                    "INSPECT {} title={:?} vector_size={}",
+                    report.id, report.title, report.vector_size
+                );
+                for token in &report.tokens {
+                    println!("  {:<24} {:.4}", token.token, token.weight);
+                }
+            }
+        }
+        Commands::Tag { action } => match action {
+            TagAction::List { graph, json } => {
+                let graph_path = normalize_path(graph);
+                let graph_data = require_graph(&graph_path)?;
+                let counts = tag_counts(&graph_data);
+                if json {
+                    print_json(&counts)?;
+                } else {
+                    println!("TAGS count={}", counts.len());
+                    for (tag, count) in &counts {
+                        println!("  {} ({})", tag, count);
+                    }
+                }
+            }
+            TagAction::Notes { tag, graph, json } => {
+                let graph_path = normalize_path(graph);
+                let graph_data = require_graph(&graph_path)?;
+                let notes = notes_with_tag(&graph_data, &tag);
+                if json {
+                    print_json(&notes)?;
+                } else {
+                    println!("TAG {} count={}", tag, notes.len());
+                    for id in &notes {
+                        println!("  - {}", id);
+                    }
+                }
+            }
+        },
+        Commands::Stats { graph, top, json } => {
+            let graph_path = normalize_path(graph);
+            let graph_data = require_graph(&graph_path)?;
+            let report = graph_stats(&graph_data, top);
+            if json {
+                print_json(&report)?;
+            } else {
+                println!("{:<22} {}", "nodes", report.nodes);
+                println!("{:<22} {}", "real nodes", report.real_nodes);
+                println!("{:<22} {}", "ghost nodes", report.ghost_nodes);
+                println!("{:<22} {}", "edges", report.edges);
+                for (kind, count) in &report.edges_by_kind {
+                    println!("{:<22} {}", format!("  {}", kind), count);
+                }
+                println!("{:<22} {}", "orphans", report.orphans);
+                println!("{:<22} {:.1}", "avg vector size", report.avg_vector_size);
+                println!(
+                    "{:<22} {:.1}",
+                    "median vector size", report.median_vector_size
+                );
+                println!("{:<22} {}", "total words", report.total_words);
+                println!("{:<22} {:.1}", "avg words", report.avg_words);
+                println!("top linked:");
+                for (idx, entry) in report.top_linked.iter().enumerate() {
+                    println!("{:02}. {} | indegree={}", idx + 1, entry.id, entry.indegree);
+                }
+            }
+        }
+        Commands::Components {
+            graph,
+            exclude_ghosts,
+            json,
+        } => {
+            let graph_path = normalize_path(graph);
+            let graph_data = require_graph(&graph_path)?;
+            let report = connected_components(&graph_data, !exclude_ghosts);
+            if json {
+                print_json(&report)?;
+            } else {
+                println!(
+                    "COMPONENTS count={} largest={} nodes={}",
+                    report.components,
+                    report.largest,
+                    report.sizes.iter().sum::<usize>()
+                );
+                let sizes: Vec<String> = report.sizes.iter().map(usize::to_string).collect();
+                println!("  sizes: {}", sizes.join(" "));
+                println!("  orphans ({})", report.orphans.len());
+                for id in &report.orphans {
+                    println!("    - {}", id);
+                }
+            }
+        }
+        Commands::History { out_root, json } => {
+            let path = normalize_path(out_root).join(HISTORY_FILE);
+            let records = load_history(&path)?;
+            if json {
+                print_json(&records)?;
+            } else {
+                for record in &records {
+                    println!(
+                        "{} notes={} nodes={} edges={} ghosts={}",
+                        record.timestamp,
+                        record.notes,
+                        record.nodes,
+                        record.edges,
+                        record.ghost_nodes
+                    );
+                }
+                let nodes: Vec<usize> = records.iter().map(|record| record.nodes).collect();
+                if let (Some(first), Some(last)) = (nodes.first(), nodes.last()) {
+                    println!(
+                        "nodes {} {} -> {} ({:+})",
+                        sparkline(&nodes),
+                        first,
+                        last,
+                        *last as i64 - *first as i64
+                    );
+                }
+            }
+        }
+        Commands::Compact {
+            graph,
+            out,
+            no_semantic,
+            quantize,
+        } => {
+            let graph_path = normalize_path(graph);
+            let mut graph_data = require_graph(&graph_path)?;
+            let before = fs::metadata(&graph_path)?.len();
+            let target = out
+                .map(normalize_path)
+                .unwrap_or_else(|| graph_path.clone());
+            compact_graph(&mut graph_data, no_semantic, quantize);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            write_atomic(&target, serde_json::to_string(&graph_data)?)?;
+            let after = fs::metadata(&target)?.len();
+            println!(
+                "COMPACT_OK {} bytes={}->{} saved={:.1}%",
+                target.display(),
+                before,
+                after,
+                100.0 * (1.0 - after as f64 / before.max(1) as f64)
+            );
+        }
+        Commands::LinkCheck {
+            graph,
+            create_stubs,
+            notes_root,
+            json,
+        } => {
+            let graph_path = normalize_path(graph);
+            let graph_data = require_graph(&graph_path)?;
+            let links = unresolved_links(&graph_data);
+            let stubs = match notes_root.filter(|_| create_stubs) {
+                Some(root) => create_link_stubs(&normalize_path(root), &links)?,
+                None => Vec::new(),
+            };
+            if json {
+                print_json(&links)?;
+            } else {
+                let groups = group_unresolved(&links);
+                println!(
+                    "LINK_CHECK unresolved={} targets={}",
+                    links.len(),
+                    groups.len()
+                );
+                for (target, sources) in &groups {
+                    println!("{} ({})", target, sources.len());
+                    for source in sources {
+                        println!("  {} -> {}", source, target);
+                    }
+                }
+                for stub in &stubs {
+                    println!("STUB_CREATED {}", stub);
+                }
+            }
+        }
+        Commands::Diff { old, new, json } => {
+            let old = require_graph(&normalize_path(old))?;
+            let new = require_graph(&normalize_path(new))?;
+            let diff = diff_graphs(&old, &new);
+            if json {
+                print_json(&diff)?;
+            } else {
+                println!(
+                    "DIFF nodes=+{}/-{} edges=+{}/-{} retitled={}",
+                    diff.added_nodes.len(),
+                    diff.removed_nodes.len(),
+                    diff.added_edges.len(),
+                    diff.removed_edges.len(),
+                    diff.retitled.len()
+                );
+                for id in &diff.added_nodes {
+                    println!("+ node {}", id);
+                }
+                for id in &diff.removed_nodes {
+                    println!("- node {}", id);
+                }
+                for edge in &diff.added_edges {
+                    println!("+ edge {} -> {} [{}]", edge.src, edge.dst, edge.kind);
+                }
+                for edge in &diff.removed_edges {
+                    println!("- edge {} -> {} [{}]", edge.src, edge.dst, edge.kind);
+                }
+                for change in &diff.retitled {
+                    println!(
+                        "~ title {}: {:?} -> {:?}",
+                        change.id, change.old, change.new
+                    );
+                }
+            }
+        }
+        Commands::Dedupe {
+            graph,
+            threshold,
+            json,
+        } => {
+            let graph_path = normalize_path(graph);
+            let graph_data = require_graph(&graph_path)?;
+            let real_nodes = graph_data.nodes.iter().filter(|n| n.path.is_some()).count();
+            if real_nodes > DEDUPE_WARN_NODES {
+                eprintln!(
+                    "WARN dedupe over {} notes may be slow; consider a higher --threshold",
+                    real_nodes
+                );
+            }
+            let pairs = find_duplicates(&graph_data, threshold);
+            if json {
+                print_json(&pairs)?;
+            } else {
+                for pair in &pairs {
+                    println!("{:.3} | {} | {}", pair.similarity, pair.a, pair.b);
+                }
+                println!("DEDUPE_OK pairs={}", pairs.len());
+            }
+        }
+        Commands::Doctor(args) => {
+            let DoctorArgs {
+                notes_root,
+                exclude,
+                ext,
+                graph,
+                stem,
+                fix,
+                max_inbox,
+                json,
+            } = *args;
             let notes_root = normalize_path(notes_root);
             let graph_path = normalize_path(graph);
-            let report = doctor_report(&notes_root, &graph_path);
+            let options = DoctorOptions {
+                stem,
+                exclude: build_exclude_set(&exclude)?,
+                extensions: NoteExtensions::new(&ext),
+                fix,
+                max_inbox,
+            };
+            let report = doctor_report(&notes_root, &graph_path, &options);
             if json {
                 print_json(&report)?;
             } else {
@@ -265,66 +1756,211 @@ fn main() -> Result<()> {
                 );
             }
         }
-        Commands::Benchmark {
-            dataset,
-            graph,
-            topk,
-            json,
-        } => {
+        Commands::Benchmark(args) => {
+            let BenchmarkArgs {
+                dataset,
+                graph,
+                topk,
+                report_md,
+                report_json,
+                json,
+                jsonl,
+                output,
+                progress,
+                quiet,
+            } = *args;
             let graph_path = normalize_path(graph);
-            if !graph_path.exists() {
-                anyhow::bail!(
-                    "Graph not found: {}. Run `exom index` first.",
-                    graph_path.display()
-                );
-            }
             let dataset_path = normalize_path(dataset);
-            let graph_data = load_graph(&graph_path)?;
+            let graph_data = LoadedGraph::new(require_graph(&graph_path)?);
             let dataset_file = fs::read_to_string(&dataset_path)
                 .with_context(|| format!("failed to read dataset {}", dataset_path.display()))?;
             let queries: Vec<BenchmarkQuery> = serde_json::from_str(&dataset_file)
                 .with_context(|| format!("failed to parse dataset {}", dataset_path.display()))?;
-            let report = run_benchmark(&graph_data, &queries, topk)?;
+            let mut out = output_writer(output.map(normalize_path).as_deref())?;
+            if jsonl {
+                let progress = progress.then(|| progress_bar("queries"));
+                let mut done = 0;
+                let summary = stream_benchmark(&graph_data, &queries, topk, |query| {
+                    done += 1;
+                    if let Some(progress) = &progress {
+                        progress(done, queries.len());
+                    }
+                    write_jsonl_line(&mut out, &query)
+                })?;
+                write_jsonl_line(&mut out, &summary)?;
+                out.flush()?;
+                return Ok(());
+            }
+            let progress = progress.then(|| progress_bar("queries"));
+            let report = run_benchmark(&graph_data, &queries, topk, progress.as_deref())?;
+            if let Some(path) = report_md {
+                let path = normalize_path(path);
+                let markdown = render_benchmark_markdown(&report, &graph_path, Utc::now());
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&path, markdown)
+                    .with_context(|| format!("failed to write report {}", path.display()))?;
+                if !json && !quiet {
+                    writeln!(out, "REPORT_OK {}", path.display())?;
+                }
+            }
+            if let Some(path) = report_json {
+                let path = normalize_path(path);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                write_atomic(&path, serde_json::to_string_pretty(&report)?)?;
+                if !json && !quiet {
+                    writeln!(out, "REPORT_OK {}", path.display())?;
+                }
+            }
             if json {
-                print_json(&report)?;
-            } else {
-                println!("hit@1: {:.3}", report.hit_at_1);
-                println!("hit@3: {:.3}", report.hit_at_3);
-                println!("hit@5: {:.3}", report.hit_at_5);
-                println!("avg latency ms: {:.3}", report.avg_latency_ms);
-                println!("per-query summary:");
+                write_json(&mut out, &report)?;
+            } else if !quiet {
+                writeln!(out, "hit@1: {:.3}", report.summary.hit_at_1)?;
+                writeln!(out, "hit@3: {:.3}", report.summary.hit_at_3)?;
+                writeln!(out, "hit@5: {:.3}", report.summary.hit_at_5)?;
+                writeln!(out, "mrr: {:.3}", report.summary.mrr)?;
+                writeln!(
+                    out,
+                    "ndcg@{}: {:.3}",
+                    report.summary.top_k, report.summary.ndcg
+                )?;
+                writeln!(
+                    out,
+                    "precision@{}: {:.3}",
+                    report.summary.top_k, report.summary.precision_at_k
+                )?;
+                writeln!(
+                    out,
+                    "recall@{}: {:.3}",
+                    report.summary.top_k, report.summary.recall_at_k
+                )?;
+                writeln!(out, "avg latency ms: {:.3}", report.summary.avg_latency_ms)?;
+                writeln!(out, "per-query summary:")?;
                 for (idx, summary) in report.queries.iter().enumerate() {
                     let hit_info = summary
                         .hit_rank
                         .map(|rank| format!("rank {}", rank))
                         .unwrap_or_else(|| "MISS".to_string());
                     let target = summary.hit_path.as_deref().unwrap_or("no hit within topk");
-                    println!(
+                    writeln!(
+                        out,
                         "{:02}. {} | {} | latency={:.2}ms | {}",
                         idx + 1,
                         summary.query,
                         hit_info,
                         summary.latency_ms,
                         target
+                    )?;
+                }
+            }
+            out.flush()?;
+        }
+        Commands::BenchCompare { old, new, json } => {
+            let comparison = compare_benchmarks(
+                &load_benchmark_report(&normalize_path(old))?,
+                &load_benchmark_report(&normalize_path(new))?,
+            );
+            if json {
+                print_json(&comparison)?;
+            } else {
+                let count = |change: RankChange| {
+                    comparison
+                        .queries
+                        .iter()
+                        .filter(|query| query.change == change)
+                        .count()
+                };
+                println!(
+                    "BENCH_COMPARE queries={} regressed={} improved={} unchanged={}",
+                    comparison.queries.len(),
+                    count(RankChange::Regressed),
+                    count(RankChange::Improved),
+                    count(RankChange::Unchanged)
+                );
+                for metric in &comparison.metrics {
+                    println!(
+                        "  {:<14} {:.3} -> {:.3} ({:+.3})",
+                        metric.name, metric.old, metric.new, metric.delta
                     );
                 }
+                let rank = |rank: Option<usize>| {
+                    rank.map(|rank| format!("rank {}", rank))
+                        .unwrap_or_else(|| "MISS".to_string())
+                };
+                for query in &comparison.queries {
+                    println!(
+                        "  {} {} | {} -> {}",
+                        query.change,
+                        query.query,
+                        rank(query.old_rank),
+                        rank(query.new_rank)
+                    );
+                }
+                for query in &comparison.only_old {
+                    println!("  DROPPED {}", query);
+                }
+                for query in &comparison.only_new {
+                    println!("  ADDED {}", query);
+                }
             }
         }
-        Commands::Lifecycle {
-            mode,
-            older_than_days,
-            notes_root,
-            json,
-        } => {
+        Commands::Lifecycle(args) => {
+            let LifecycleArgs {
+                mode,
+                older_than_days,
+                notes_root,
+                prune_threshold,
+                group_by,
+                excerpt_lines,
+                from,
+                to,
+                decay_fn,
+                decay_scale,
+                ext,
+                dry_run,
+                json,
+            } = *args;
+            if decay_scale <= 0.0 {
+                anyhow::bail!("--decay-scale must be positive");
+            }
+            if matches!(mode, LifecycleMode::Restore) && from.is_none() && to.is_none() {
+                anyhow::bail!("--mode restore needs --from, --to, or both");
+            }
             let notes_root = normalize_path(notes_root);
-            ensure_workflow_dirs(&notes_root)?;
-            let report = run_lifecycle(&notes_root, mode, older_than_days)?;
+            if !dry_run {
+                ensure_workflow_dirs(&notes_root)?;
+            }
+            let options = LifecycleOptions {
+                older_than_days,
+                prune_threshold,
+                group_by,
+                excerpt_lines,
+                restore_from: from
+                    .as_deref()
+                    .map(|raw| parse_day(raw, "--from"))
+                    .transpose()?,
+                restore_to: to
+                    .as_deref()
+                    .map(|raw| parse_day(raw, "--to"))
+                    .transpose()?,
+                decay_fn,
+                decay_scale,
+                extensions: NoteExtensions::new(&ext),
+                dry_run,
+            };
+            let report = run_lifecycle(&notes_root, mode, &options)?;
             if json {
                 print_json(&report)?;
             } else {
                 println!(
-                    "LIFECYCLE {} processed={} affected={}",
-                    report.mode, report.processed, report.touched
+                    "LIFECYCLE {} processed={} affected={}{}",
+                    report.mode,
+                    report.processed,
+                    report.touched,
+                    if report.dry_run { " dry_run=true" } else { "" }
                 );
                 if let Some(target) = &report.summary_path {
                     println!("  summary: {}", target);
@@ -334,936 +1970,421 @@ fn main() -> Result<()> {
                 }
             }
         }
-    }
-
-    Ok(())
-}
-
-fn normalize_path(path: PathBuf) -> PathBuf {
-    if path.is_absolute() {
-        path
-    } else {
-        env::current_dir()
-            .unwrap_or_else(|_| PathBuf::from("."))
-            .join(path)
-    }
-}
-
-fn init_workflow(root: &Path) -> Result<()> {
-    let extras = [".neural/cache", ".neural/exports"];
-    for dir in NOTE_DIRS.iter().chain(extras.iter()) {
-        let target = root.join(dir);
-        fs::create_dir_all(&target)
-            .with_context(|| format!("failed to create directory {:?}", target))?;
-    }
-    Ok(())
-}
-
-fn collect_notes(notes_root: &Path) -> Result<Vec<PathBuf>> {
-    let mut notes = Vec::new();
-    for dir in NOTE_DIRS {
-        let target = notes_root.join(dir);
-        if !target.exists() {
-            continue;
-        }
-        for entry in WalkDir::new(&target)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-        {
-            if entry
-                .path()
-                .extension()
-                .map(|ext| ext.eq_ignore_ascii_case("md"))
-                .unwrap_or(false)
-            {
-                notes.push(entry.into_path());
+        Commands::Review {
+            notes_root,
+            topk,
+            all,
+            include_archives,
+            ext,
+            json,
+        } => {
+            let notes_root = normalize_path(notes_root);
+            let items = review_queue(
+                &notes_root,
+                all,
+                include_archives,
+                topk,
+                &NoteExtensions::new(&ext),
+            )?;
+            if json {
+                print_json(&items)?;
+            } else {
+                println!("REVIEW count={}", items.len());
+                for (idx, item) in items.iter().enumerate() {
+                    println!(
+                        "{:02}. decay={:.3} last_reviewed={} | {}",
+                        idx + 1,
+                        item.decay_score,
+                        item.last_reviewed,
+                        item.path
+                    );
+                }
             }
         }
-    }
-    Ok(notes)
-}
-
-fn relative_note_id(note: &Path, base: &Path) -> Result<String> {
-    let rel = note
-        .strip_prefix(base)
-        .with_context(|| format!("{} is not inside {}", note.display(), base.display()))?;
-    Ok(rel
-        .iter()
-        .map(|os| os.to_string_lossy())
-        .collect::<Vec<_>>()
-        .join("/"))
-}
-
-fn title_from_file(path: &Path) -> Result<String> {
-    let data = fs::read_to_string(path).unwrap_or_default();
-    for line in data.lines() {
-        if let Some(rest) = line.strip_prefix("# ") {
-            return Ok(rest.trim().to_string());
+        Commands::Digest {
+            notes_root,
+            since_commit,
+            ext,
+            json,
+        } => {
+            let notes_root = normalize_path(notes_root);
+            let changes = git_note_changes(&notes_root, &since_commit, &NoteExtensions::new(&ext))?;
+            let report = digest_notes(&notes_root, &since_commit, &changes)?;
+            if json {
+                print_json(&report)?;
+            } else {
+                println!(
+                    "DIGEST since={} notes={} new_notes={} new_relations={}",
+                    report.since,
+                    report.notes.len(),
+                    report.notes.iter().filter(|note| note.added).count(),
+                    report
+                        .notes
+                        .iter()
+                        .map(|note| note.new_relations.len())
+                        .sum::<usize>()
+                );
+                for note in &report.notes {
+                    println!(
+                        "  {} {} | {}",
+                        if note.added { "+" } else { "~" },
+                        note.path,
+                        note.title
+                    );
+                    for relation in &note.new_relations {
+                        println!(
+                            "      {}: {} -> {} ({:.2})",
+                            relation.rel_type, relation.from, relation.to, relation.confidence
+                        );
+                    }
+                }
+            }
         }
-    }
-    Ok(path
-        .file_stem()
-        .map(|stem| stem.to_string_lossy().to_string())
-        .unwrap_or_else(|| path.display().to_string()))
-}
-
-#[derive(Clone, Serialize, Deserialize)]
-struct Node {
-    id: String,
-    path: Option<String>,
-    title: String,
-    stem: String,
-    #[serde(default)]
-    semantic: BTreeMap<String, f64>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct Edge {
-    src: String,
-    dst: String,
-    #[serde(rename = "type")]
-    kind: String,
-}
-
-#[derive(Serialize, Deserialize)]
-struct Stats {
-    notes: usize,
-    nodes: usize,
-    edges: usize,
-}
-
-#[derive(Serialize, Deserialize)]
-struct GraphData {
-    notes_root: String,
-    nodes: Vec<Node>,
-    edges: Vec<Edge>,
-    stats: Stats,
-}
-
-struct IndexResult {
-    graph_path: PathBuf,
-    notes: usize,
-    nodes: usize,
-    edges: usize,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-struct TypedRelation {
-    #[serde(rename = "type")]
-    rel_type: String,
-    from: String,
-    to: String,
-    confidence: f64,
-}
-
-#[derive(Serialize)]
-struct CaptureReport {
-    note: String,
-    appended_at: String,
-    relation_count: usize,
-    relations: Vec<TypedRelation>,
-}
-
-#[derive(Serialize)]
-struct RecallResponse {
-    query: String,
-    top_k: usize,
-    results: Vec<RecallRow>,
-}
-
-#[derive(Serialize)]
-struct LifecycleReport {
-    mode: LifecycleMode,
-    processed: usize,
-    touched: usize,
-    details: Vec<String>,
-    summary_path: Option<String>,
-}
-
-#[derive(Deserialize)]
-struct BenchmarkQuery {
-    query: String,
-    expected: Vec<String>,
-}
-
-#[derive(Serialize)]
-struct BenchmarkReport {
-    hit_at_1: f64,
-    hit_at_3: f64,
-    hit_at_5: f64,
-    avg_latency_ms: f64,
-    queries: Vec<QuerySummary>,
-}
-
-#[derive(Serialize)]
-struct QuerySummary {
-    query: String,
-    hit_rank: Option<usize>,
-    hit_path: Option<String>,
-    latency_ms: f64,
-}
-
-fn index_graph_data(notes_root: &Path, out_root: &Path) -> Result<IndexResult> {
-    struct NoteEntry {
-        id: String,
-        title: String,
-        stem: String,
-        content: String,
-    }
-
-    let notes = collect_notes(notes_root)?;
-    let mut id_by_stem: HashMap<String, Vec<String>> = HashMap::new();
-    let mut node_map: BTreeMap<String, Node> = BTreeMap::new();
-    let mut entries = Vec::new();
-
-    for note in &notes {
-        let id = relative_note_id(note, notes_root)?;
-        let stem = note
-            .file_stem()
-            .map(|stem| stem.to_string_lossy().to_string())
-            .unwrap_or_default();
-        let title = title_from_file(note)?;
-        let content = fs::read_to_string(note).unwrap_or_default();
-        entries.push(NoteEntry {
-            id: id.clone(),
-            title: title.clone(),
-            stem: stem.clone(),
-            content,
-        });
-        node_map.insert(
-            id.clone(),
-            Node {
-                id: id.clone(),
-                path: Some(id.clone()),
-                title,
-                stem: stem.clone(),
-                semantic: BTreeMap::new(),
-            },
-        );
-        id_by_stem.entry(stem.to_lowercase()).or_default().push(id);
-    }
-
-    let mut edges = Vec::new();
-    for entry in &entries {
-        for link in WIKILINK_REGEX.captures_iter(&entry.content) {
-            let raw = link.get(1).map(|m| m.as_str().trim()).unwrap_or("");
-            let key = Path::new(raw)
-                .file_name()
-                .map(|s| s.to_string_lossy().to_lowercase())
-                .unwrap_or_else(|| raw.to_lowercase());
-            if let Some(candidates) = id_by_stem.get(&key) {
-                for dst in candidates {
-                    edges.push(Edge {
-                        src: entry.id.clone(),
-                        dst: dst.clone(),
-                        kind: "WIKILINK".into(),
-                    });
+        Commands::Merge(args) => {
+            let MergeArgs {
+                from,
+                to,
+                notes_root,
+                out_root,
+                exclude,
+                ext,
+                dry_run,
+                json,
+            } = *args;
+            let notes_root = normalize_path(notes_root);
+            let out_root = normalize_path(out_root);
+            let exclude = build_exclude_set(&exclude)?;
+            let extensions = NoteExtensions::new(&ext);
+            let report = run_merge(&notes_root, &from, &to, &exclude, &extensions, dry_run)?;
+            // Rebuild with the settings the existing graph was indexed with.
+            let index = if dry_run {
+                None
+            } else {
+                let mut options = stored_index_options(&out_root, exclude, extensions);
+                // The archived note keeps its stored embedding under its new id.
+                if let Some(vectors) = options.embeddings.as_mut() {
+                    if let Some(vector) = vectors.remove(&report.from) {
+                        vectors.insert(report.archived_to.clone(), vector);
+                    }
                 }
+                Some(index_graph_data(&notes_root, &out_root, &options)?)
+            };
+            if json {
+                print_json(&report)?;
             } else {
-                let ghost = format!("ghost/{}", raw);
-                node_map.entry(ghost.clone()).or_insert_with(|| Node {
-                    id: ghost.clone(),
-                    path: None,
-                    title: raw.to_string(),
-                    stem: raw.to_string(),
-                    semantic: BTreeMap::new(),
-                });
-                edges.push(Edge {
-                    src: entry.id.clone(),
-                    dst: ghost,
-                    kind: "UNRESOLVED_LINK".into(),
-                });
+                println!(
+                    "MERGE_OK from={} to={} archived={} links={}{}",
+                    report.from,
+                    report.to,
+                    report.archived_to,
+                    report.links,
+                    if report.dry_run { " dry_run=true" } else { "" }
+                );
+                for detail in &report.details {
+                    println!("  {}", detail);
+                }
+                if let Some(result) = index {
+                    println!(
+                        "INDEX_OK notes={} nodes={} edges={} -> {}",
+                        result.notes,
+                        result.nodes,
+                        result.edges,
+                        result.graph_path.display()
+                    );
+                }
             }
         }
-    }
-
-    let mut doc_token_counts: HashMap<String, HashMap<String, usize>> = HashMap::new();
-    for entry in &entries {
-        let corpus = format!("{} {}", entry.title, entry.content);
-        let counts = token_counts(&corpus);
-        doc_token_counts.insert(entry.id.clone(), counts);
-    }
-
-    let total_docs = entries.len().max(1);
-    let mut doc_freq: HashMap<String, usize> = HashMap::new();
-    for counts in doc_token_counts.values() {
-        for token in counts.keys() {
-            *doc_freq.entry(token.clone()).or_default() += 1;
-        }
-    }
-
-    for entry in &entries {
-        if let Some(counts) = doc_token_counts.get(&entry.id) {
-            let mut tfidf = BTreeMap::new();
-            for (token, count) in counts {
-                let df = *doc_freq.get(token).unwrap_or(&0) as f64;
-                let idf = ((total_docs as f64 + 1.0) / (df + 1.0)).ln() + 1.0;
-                tfidf.insert(token.clone(), (*count as f64) * idf);
-            }
-            if let Some(node) = node_map.get_mut(&entry.id) {
-                node.semantic = tfidf;
+        Commands::Rename(args) => {
+            let RenameArgs {
+                from,
+                to,
+                notes_root,
+                out_root,
+                exclude,
+                ext,
+                dry_run,
+                json,
+            } = *args;
+            let notes_root = normalize_path(notes_root);
+            let out_root = normalize_path(out_root);
+            let exclude = build_exclude_set(&exclude)?;
+            let extensions = NoteExtensions::new(&ext);
+            let report = run_rename(&notes_root, &from, &to, &exclude, &extensions, dry_run)?;
+            // Rebuild with the settings the existing graph was indexed with.
+            let index = if dry_run {
+                None
+            } else {
+                let mut options = stored_index_options(&out_root, exclude, extensions);
+                // The renamed note keeps its stored embedding under its new id.
+                if let Some(vectors) = options.embeddings.as_mut() {
+                    if let Some(vector) = vectors.remove(&report.from) {
+                        vectors.insert(report.to.clone(), vector);
+                    }
+                }
+                Some(index_graph_data(&notes_root, &out_root, &options)?)
+            };
+            if json {
+                print_json(&report)?;
+            } else {
+                println!(
+                    "RENAME_OK from={} to={} links={}{}",
+                    report.from,
+                    report.to,
+                    report.links,
+                    if report.dry_run { " dry_run=true" } else { "" }
+                );
+                for detail in &report.details {
+                    println!("  {}", detail);
+                }
+                if let Some(result) = index {
+                    println!(
+                        "INDEX_OK notes={} nodes={} edges={} -> {}",
+                        result.notes,
+                        result.nodes,
+                        result.edges,
+                        result.graph_path.display()
+                    );
+                }
             }
         }
-    }
-
-    let edges_count = edges.len();
-    let graph = GraphData {
-        notes_root: notes_root.display().to_string(),
-        nodes: node_map.values().cloned().collect(),
-        edges,
-        stats: Stats {
-            notes: notes.len(),
-            nodes: node_map.len(),
-            edges: edges_count,
+        Commands::Schema { format } => match format {
+            SchemaFormat::JsonSchema => print_json(&graph_json_schema())?,
         },
-    };
-
-    let graph_path = out_root.join("graph.json");
-    if let Some(parent) = graph_path.parent() {
-        fs::create_dir_all(parent)?;
-    }
-    fs::write(&graph_path, serde_json::to_string_pretty(&graph)?)?;
-
-    Ok(IndexResult {
-        graph_path,
-        notes: graph.stats.notes,
-        nodes: graph.stats.nodes,
-        edges: graph.stats.edges,
-    })
-}
-
-fn load_graph(graph_path: &Path) -> Result<GraphData> {
-    let data = fs::read_to_string(graph_path)?;
-    let graph: GraphData = serde_json::from_str(&data)?;
-    Ok(graph)
-}
-
-#[derive(Serialize)]
-struct RecallRow {
-    rank: usize,
-    score: f64,
-    title: String,
-    path: Option<String>,
-}
-
-struct RecallWeights {
-    lexical: f64,
-    graph: f64,
-    semantic: f64,
-}
-
-fn recall_from_graph(
-    graph: &GraphData,
-    query: &str,
-    topk: usize,
-    weights: &RecallWeights,
-) -> Vec<RecallRow> {
-    let query_tokens = tokens(query);
-    let query_counts = token_counts(query);
-    let mut indegree: HashMap<&str, usize> = HashMap::new();
-    for edge in &graph.edges {
-        *indegree.entry(edge.dst.as_str()).or_default() += 1;
-    }
-
-    let mut scored = Vec::new();
-    for node in &graph.nodes {
-        let text = format!("{} {}", node.title, node.path.as_deref().unwrap_or(""));
-        let lexical = lexical_overlap_score(&query_tokens, &text);
-        let graph_value = graph_influence(indegree.get(node.id.as_str()).copied().unwrap_or(0));
-        let semantic = semantic_score(&query_counts, &node.semantic);
-        let score =
-            weights.lexical * lexical + weights.graph * graph_value + weights.semantic * semantic;
-        if score <= 0.0 {
-            continue;
-        }
-        scored.push(RecallRow {
-            rank: 0,
-            score,
-            title: node.title.clone(),
-            path: node.path.clone(),
-        });
-    }
-
-    scored.sort_by(|a, b| {
-        b.score
-            .partial_cmp(&a.score)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-    scored.truncate(topk);
-    for (idx, row) in scored.iter_mut().enumerate() {
-        row.rank = idx + 1;
-    }
-    scored
-}
-
-fn token_counts(text: &str) -> HashMap<String, usize> {
-    let mut counts = HashMap::new();
-    for token in TOKEN_REGEX.find_iter(text) {
-        let normalized = token.as_str().to_lowercase();
-        *counts.entry(normalized).or_default() += 1;
-    }
-    counts
-}
-
-fn tokens(text: &str) -> HashSet<String> {
-    token_counts(text)
-        .into_iter()
-        .map(|(token, _)| token)
-        .collect()
-}
-
-fn lexical_overlap_score(query_tokens: &HashSet<String>, text: &str) -> f64 {
-    let node_tokens = tokens(text);
-    (query_tokens.intersection(&node_tokens).count() * 2) as f64
-}
-
-fn graph_influence(indegree: usize) -> f64 {
-    (indegree.min(10) as f64) * 0.1
-}
-
-fn semantic_score(query_counts: &HashMap<String, usize>, vector: &BTreeMap<String, f64>) -> f64 {
-    query_counts
-        .iter()
-        .map(|(token, count)| vector.get(token).copied().unwrap_or(0.0) * (*count as f64))
-        .sum()
-}
-
-fn run_benchmark(
-    graph: &GraphData,
-    dataset: &[BenchmarkQuery],
-    topk: usize,
-) -> Result<BenchmarkReport> {
-    let weights = RecallWeights {
-        lexical: 1.0,
-        graph: 1.0,
-        semantic: 1.0,
-    };
-    let mut total_latency = 0.0;
-    let mut hit1 = 0;
-    let mut hit3 = 0;
-    let mut hit5 = 0;
-    let mut queries = Vec::new();
-
-    for entry in dataset {
-        let expected: HashSet<String> = entry.expected.iter().cloned().collect();
-        let start = Instant::now();
-        let rows = recall_from_graph(graph, &entry.query, topk, &weights);
-        let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
-        total_latency += latency_ms;
-        let mut hit_rank = None;
-        let mut hit_path = None;
-        for (idx, row) in rows.iter().enumerate() {
-            let matched = row
-                .path
-                .as_deref()
-                .map(|p| expected.contains(p))
-                .unwrap_or(false)
-                || expected.contains(&row.title);
-            if matched {
-                hit_rank = Some(idx + 1);
-                hit_path = row.path.clone().or_else(|| Some(row.title.clone()));
-                break;
-            }
-        }
-        if let Some(rank) = hit_rank {
-            if rank <= 1 {
-                hit1 += 1;
-            }
-            if rank <= 3 {
-                hit3 += 1;
-            }
-            if rank <= 5 {
-                hit5 += 1;
+        Commands::Config {
+            action: ConfigAction::Show { json },
+        } => {
+            let effective = effective_config(config.as_ref());
+            if json {
+                print_json(&effective)?;
+            } else {
+                println!(
+                    "CONFIG source={}",
+                    effective.source.as_deref().unwrap_or("(none)")
+                );
+                println!("  notes_root = {}", effective.notes_root);
+                println!("  out_root = {}", effective.out_root);
+                println!("  graph = {}", effective.graph);
+                println!(
+                    "  recall profile={} lexical={:.2} graph={:.2} semantic={:.2}",
+                    effective.profile,
+                    effective.lexical_weight,
+                    effective.graph_weight,
+                    effective.semantic_weight
+                );
             }
         }
-        queries.push(QuerySummary {
-            query: entry.query.clone(),
-            hit_rank,
-            hit_path,
-            latency_ms,
-        });
     }
 
-    let total = dataset.len() as f64;
-    let report = BenchmarkReport {
-        hit_at_1: if total > 0.0 {
-            hit1 as f64 / total
-        } else {
-            0.0
-        },
-        hit_at_3: if total > 0.0 {
-            hit3 as f64 / total
-        } else {
-            0.0
-        },
-        hit_at_5: if total > 0.0 {
-            hit5 as f64 / total
-        } else {
-            0.0
-        },
-        avg_latency_ms: if total > 0.0 {
-            total_latency / total
-        } else {
-            0.0
-        },
-        queries,
-    };
-    Ok(report)
-}
-
-#[derive(Serialize)]
-struct DoctorReport {
-    ok: bool,
-    checks: Vec<CheckResult>,
-}
-
-#[derive(Serialize)]
-struct CheckResult {
-    name: &'static str,
-    ok: bool,
-    info: String,
+    Ok(())
 }
 
-fn doctor_report(notes_root: &Path, graph_path: &Path) -> DoctorReport {
-    let mut checks = Vec::new();
-    let notes_root_exists = notes_root.exists();
-    checks.push(CheckResult {
-        name: "notes_root_exists",
-        ok: notes_root_exists,
-        info: notes_root.display().to_string(),
-    });
-
-    let markdown_count = if notes_root_exists {
-        NOTE_DIRS
-            .iter()
-            .map(|dir| notes_root.join(dir))
-            .filter(|dir| dir.exists())
-            .map(|dir| {
-                WalkDir::new(dir)
-                    .into_iter()
-                    .filter_map(|entry| entry.ok())
-                    .filter(|entry| entry.file_type().is_file())
-                    .filter(|entry| {
-                        entry
-                            .path()
-                            .extension()
-                            .map(|ext| ext.eq_ignore_ascii_case("md"))
-                            .unwrap_or(false)
-                    })
-                    .count()
-            })
-            .sum()
-    } else {
-        0
-    };
-
-    checks.push(CheckResult {
-        name: "markdown_notes_detected",
-        ok: markdown_count > 0,
-        info: format!("count={}", markdown_count),
-    });
-
-    let graph_exists = graph_path.exists();
-    checks.push(CheckResult {
-        name: "graph_exists",
-        ok: graph_exists,
-        info: graph_path.display().to_string(),
-    });
-
-    let ok = checks.iter().all(|c| c.ok);
-    DoctorReport { ok, checks }
+/// Defaults read from `exom.toml`. Explicit flags still win.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExomConfig {
+    /// File the values came from; relative paths in it are resolved against
+    /// its directory.
+    #[serde(skip)]
+    source: PathBuf,
+    notes_root: Option<PathBuf>,
+    out_root: Option<PathBuf>,
+    graph: Option<PathBuf>,
+    #[serde(default)]
+    recall: RecallConfig,
 }
 
-fn ensure_workflow_dirs(root: &Path) -> Result<()> {
-    init_workflow(root)
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecallConfig {
+    profile: Option<RecallProfile>,
+    lexical_weight: Option<f64>,
+    graph_weight: Option<f64>,
+    semantic_weight: Option<f64>,
 }
 
-fn capture_input_text(input: Option<String>, file: Option<PathBuf>) -> Result<String> {
-    if let Some(text) = input {
-        Ok(text)
-    } else if let Some(path) = file {
-        let normalized = normalize_path(path);
-        fs::read_to_string(&normalized)
-            .with_context(|| format!("failed to read capture input {}", normalized.display()))
-    } else {
-        anyhow::bail!("Either --input or --file is required for capture.");
+/// Nearest `exom.toml` in `start` or one of its ancestors, like git's
+/// repository discovery.
+fn find_config(start: &Path) -> Result<Option<ExomConfig>> {
+    for dir in start.ancestors() {
+        let path = dir.join(CONFIG_FILE);
+        if path.is_file() {
+            return load_config(&path).map(Some);
+        }
     }
+    Ok(None)
 }
 
-fn resolve_capture_note(notes_root: &Path, out_note: Option<PathBuf>) -> PathBuf {
-    if let Some(path) = out_note {
-        if path.is_absolute() {
-            path
-        } else {
-            notes_root.join(path)
+fn load_config(path: &Path) -> Result<ExomConfig> {
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config {}", path.display()))?;
+    let mut config: ExomConfig =
+        toml::from_str(&raw).with_context(|| format!("invalid config {}", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    for value in [
+        &mut config.notes_root,
+        &mut config.out_root,
+        &mut config.graph,
+    ] {
+        if let Some(relative) = value.as_ref().filter(|p| p.is_relative()) {
+            *value = Some(if relative == Path::new(".") {
+                base.to_path_buf()
+            } else {
+                base.join(relative)
+            });
         }
-    } else {
-        let date = Utc::now().date_naive();
-        notes_root
-            .join(INBOX_DIR)
-            .join(format!("{}-auto.md", date.format("%Y-%m-%d")))
     }
+    config.source = path.to_path_buf();
+    Ok(config)
 }
 
-fn run_capture(notes_root: &Path, target: &Path, input_text: &str) -> Result<CaptureReport> {
-    let now = Utc::now();
-    let body = input_text.trim_end();
-    let relations = parse_relations(body);
-    let yaml_block = build_relations_yaml(&relations)?;
-    let entry = format!(
-        "## Capture @{}\n\n{}\n\n```yaml\n{}\n```\n\n",
-        now.format("%Y-%m-%d %H:%M:%S UTC"),
-        body,
-        yaml_block
-    );
-
-    if let Some(parent) = target.parent() {
-        fs::create_dir_all(parent)?;
-    }
-    let note_exists = target.exists();
-    let note_len = if note_exists {
-        fs::metadata(target)?.len()
-    } else {
-        0
+/// The clap command with config values installed as argument defaults, so
+/// flags given on the command line override them.
+fn cli_command(config: Option<&ExomConfig>) -> clap::Command {
+    let command = Cli::command();
+    let Some(config) = config else {
+        return command;
     };
-    let mut file = OpenOptions::new().create(true).append(true).open(target)?;
-    if note_exists && note_len > 0 {
-        writeln!(file)?;
-    }
-    if !note_exists {
-        writeln!(file, "# Auto capture\n")?;
-    }
-    file.write_all(entry.as_bytes())?;
-
-    let relative =
-        relative_note_id(target, notes_root).unwrap_or_else(|_| target.display().to_string());
-    Ok(CaptureReport {
-        note: relative,
-        appended_at: now.to_rfc3339(),
-        relation_count: relations.len(),
-        relations,
-    })
-}
-
-fn build_relations_yaml(relations: &[TypedRelation]) -> Result<String> {
-    #[derive(Serialize)]
-    struct Block<'a> {
-        relations: &'a [TypedRelation],
-    }
-
-    let raw = serde_yaml::to_string(&Block { relations })?;
-    let trimmed = raw.strip_prefix("---\n").unwrap_or(&raw);
-    Ok(trimmed.trim_end_matches('\n').to_string())
+    let path = |value: &Option<PathBuf>| value.as_ref().map(|p| p.display().to_string());
+    let number = |value: Option<f64>| value.map(|v| v.to_string());
+    let defaults: Vec<(&str, String)> = [
+        ("notes_root", path(&config.notes_root)),
+        ("out_root", path(&config.out_root)),
+        ("graph", path(&config.graph)),
+        ("profile", config.recall.profile.map(|p| p.to_string())),
+        ("lexical_weight", number(config.recall.lexical_weight)),
+        ("graph_weight", number(config.recall.graph_weight)),
+        ("semantic_weight", number(config.recall.semantic_weight)),
+    ]
+    .into_iter()
+    .filter_map(|(id, value)| Some((id, value?)))
+    .collect();
+    apply_config_defaults(command, &defaults)
 }
 
-fn parse_relations(text: &str) -> Vec<TypedRelation> {
-    let mut relations = Vec::new();
-    for cap in RELATION_REGEX.captures_iter(text).map(|c| c) {
-        let rel_type = cap
-            .get(1)
-            .map(|m| m.as_str().trim())
-            .unwrap_or("")
-            .to_string();
-        let from = cap
-            .name("from")
-            .map(|m| m.as_str().trim())
-            .unwrap_or("")
-            .to_string();
-        let to = cap
-            .name("to")
-            .map(|m| m.as_str().trim())
-            .unwrap_or("")
-            .to_string();
-        let confidence = cap
-            .name("confidence")
-            .and_then(|m| m.as_str().parse::<f64>().ok())
-            .unwrap_or(0.0);
-        if rel_type.is_empty() || from.is_empty() || to.is_empty() {
+fn apply_config_defaults(mut command: clap::Command, defaults: &[(&str, String)]) -> clap::Command {
+    for (id, value) in defaults {
+        let Some(arg) = command.get_arguments().find(|arg| arg.get_id() == id) else {
+            continue;
+        };
+        // Optional paths such as `capture --graph` stay opt-in.
+        let is_path = matches!(*id, "notes_root" | "out_root" | "graph");
+        if is_path && arg.get_default_values().is_empty() && !arg.is_required_set() {
             continue;
         }
-        relations.push(TypedRelation {
-            rel_type,
-            from,
-            to,
-            confidence,
-        });
-    }
-    relations
-}
-
-fn print_json<T: Serialize>(value: &T) -> Result<()> {
-    println!("{}", serde_json::to_string_pretty(value)?);
-    Ok(())
-}
-
-fn run_lifecycle(
-    notes_root: &Path,
-    mode: LifecycleMode,
-    older_than_days: u64,
-) -> Result<LifecycleReport> {
-    match mode {
-        LifecycleMode::Decay => run_decay(notes_root),
-        LifecycleMode::Consolidate => run_consolidate(notes_root),
-        LifecycleMode::Archive => run_archive(notes_root, older_than_days),
-    }
-}
-
-fn run_decay(notes_root: &Path) -> Result<LifecycleReport> {
-    let notes = gather_inbox_notes(notes_root)?;
-    let mut details = Vec::new();
-    let now = SystemTime::now();
-    for note in &notes {
-        let metadata = fs::metadata(note)?;
-        let modified = metadata.modified().unwrap_or(now);
-        let age_days = duration_since_days(now, modified);
-        if age_days >= DECAY_THRESHOLD_DAYS as f64 {
-            let last_reviewed = DateTime::<Utc>::from(modified).date_naive();
-            let score = compute_decay_score(age_days);
-            if apply_decay_metadata(note, last_reviewed, score)? {
-                details.push(format!(
-                    "Marked {} decay_score={:.3}",
-                    relative_note_id(note, notes_root)?,
-                    score
-                ));
-            }
-        }
-    }
-    Ok(LifecycleReport {
-        mode: LifecycleMode::Decay,
-        processed: notes.len(),
-        touched: details.len(),
-        details,
-        summary_path: None,
-    })
-}
-
-fn run_consolidate(notes_root: &Path) -> Result<LifecycleReport> {
-    let notes = gather_inbox_notes(notes_root)?;
-    let now = Utc::now();
-    let cutoff = now - Duration::days(CONSOLIDATE_LOOKBACK_DAYS as i64);
-    let mut candidates = Vec::new();
-    for note in &notes {
-        let metadata = fs::metadata(note)?;
-        let modified = metadata.modified().unwrap_or(SystemTime::now());
-        let modified_dt = DateTime::<Utc>::from(modified);
-        if modified_dt < cutoff {
-            let rel = relative_note_id(note, notes_root)?;
-            let title = title_from_file(note)?;
-            candidates.push((note.clone(), modified_dt, title, rel));
-        }
-    }
-    candidates.sort_by(|a, b| a.0.cmp(&b.0));
-
-    let summary_name = format!("{}-{}.md", CONSOLIDATED_PREFIX, now.format("%Y-%m"));
-    let summary_path = notes_root.join("99_Archives").join(summary_name);
-    let mut content = String::new();
-    content.push_str(&format!(
-        "# Consolidated summary for {}\nGenerated: {}\n\n## Notes older than {} days\n\n",
-        now.format("%B %Y"),
-        now.format("%Y-%m-%d %H:%M:%S UTC"),
-        CONSOLIDATE_LOOKBACK_DAYS
-    ));
-    if candidates.is_empty() {
-        content.push_str("No eligible inbox notes.\n");
-    } else {
-        for (_, modified_dt, title, rel) in &candidates {
-            content.push_str(&format!(
-                "- {} | {} | {}\n",
-                rel,
-                modified_dt.format("%Y-%m-%d %H:%M:%S UTC"),
-                title
-            ));
-        }
-    }
-
-    if let Some(parent) = summary_path.parent() {
-        fs::create_dir_all(parent)?;
+        command = command.mut_arg(*id, |arg| arg.required(false).default_value(value.clone()));
     }
-    fs::write(&summary_path, content)?;
-    let details = candidates
-        .iter()
-        .map(|(_, _, _, rel)| format!("Summarized {}", rel))
+    let names: Vec<String> = command
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
         .collect();
-    Ok(LifecycleReport {
-        mode: LifecycleMode::Consolidate,
-        processed: notes.len(),
-        touched: candidates.len(),
-        details,
-        summary_path: Some(summary_path.display().to_string()),
-    })
-}
-
-fn run_archive(notes_root: &Path, older_than_days: u64) -> Result<LifecycleReport> {
-    let notes = gather_inbox_notes(notes_root)?;
-    let mut details = Vec::new();
-    let now = SystemTime::now();
-    let lookback_secs = older_than_days.saturating_mul(86_400);
-    let cutoff = now
-        .checked_sub(StdDuration::from_secs(lookback_secs))
-        .unwrap_or(SystemTime::UNIX_EPOCH);
-    let inbox_root = notes_root.join(INBOX_DIR);
-    let archive_root = notes_root.join(ARCHIVE_INBOX_DIR);
-
-    for note in &notes {
-        let metadata = fs::metadata(note)?;
-        let modified = metadata.modified().unwrap_or(now);
-        if modified <= cutoff {
-            let rel = relative_note_id(note, notes_root)?;
-            let relative_inbox = note.strip_prefix(&inbox_root).unwrap_or(note);
-            let target = archive_root.join(relative_inbox);
-            if let Some(parent) = target.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            if target.exists() {
-                details.push(format!("Skipped exists {}", rel));
-                continue;
-            }
-            fs::rename(note, &target)?;
-            let target_rel = relative_note_id(&target, notes_root)
-                .unwrap_or_else(|_| target.display().to_string());
-            details.push(format!("Moved {} -> {}", rel, target_rel));
-        }
+    for name in names {
+        command = command.mut_subcommand(name, |sub| apply_config_defaults(sub, defaults));
     }
-
-    Ok(LifecycleReport {
-        mode: LifecycleMode::Archive,
-        processed: notes.len(),
-        touched: details
-            .iter()
-            .filter(|line| line.starts_with("Moved"))
-            .count(),
-        details,
-        summary_path: None,
-    })
-}
-
-fn duration_since_days(now: SystemTime, earlier: SystemTime) -> f64 {
-    now.duration_since(earlier)
-        .unwrap_or_else(|_| StdDuration::ZERO)
-        .as_secs_f64()
-        / 86_400.0
+    command
 }
 
-fn compute_decay_score(days: f64) -> f64 {
-    (days / 90.0).min(1.0)
+#[derive(Serialize)]
+struct EffectiveConfig {
+    source: Option<String>,
+    notes_root: String,
+    out_root: String,
+    graph: String,
+    profile: RecallProfile,
+    lexical_weight: f64,
+    graph_weight: f64,
+    semantic_weight: f64,
 }
 
-fn apply_decay_metadata(note: &Path, last_reviewed: NaiveDate, decay_score: f64) -> Result<bool> {
-    let content = fs::read_to_string(note)?;
-    let new_line = format!(
-        "<!-- lifecycle last_reviewed={} decay_score={:.3} -->",
-        last_reviewed, decay_score
+fn effective_config(config: Option<&ExomConfig>) -> EffectiveConfig {
+    let fallback = ExomConfig::default();
+    let config = config.unwrap_or(&fallback);
+    let shown = |value: &Option<PathBuf>, default: &str| {
+        value
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| default.to_string())
+    };
+    let profile = config.recall.profile.unwrap_or(RecallProfile::Balanced);
+    let weights = profile.weights().with_overrides(
+        config.recall.lexical_weight,
+        config.recall.graph_weight,
+        config.recall.semantic_weight,
     );
-    if content
-        .lines()
-        .rev()
-        .find(|line| line.trim_start().starts_with(METADATA_PREFIX))
-        .map(|line| line.trim())
-        == Some(new_line.as_str())
-    {
-        return Ok(false);
+    EffectiveConfig {
+        source: (!config.source.as_os_str().is_empty())
+            .then(|| config.source.display().to_string()),
+        notes_root: shown(&config.notes_root, "."),
+        out_root: shown(&config.out_root, ".neural"),
+        graph: shown(&config.graph, ".neural/graph.json"),
+        profile,
+        lexical_weight: weights.lexical,
+        graph_weight: weights.graph,
+        semantic_weight: weights.semantic,
     }
-
-    let filtered: Vec<&str> = content
-        .lines()
-        .filter(|line| !line.trim_start().starts_with(METADATA_PREFIX))
-        .collect();
-    let mut rebuilt = filtered.join("\n");
-    if !rebuilt.is_empty() {
-        rebuilt.push('\n');
-    }
-    rebuilt.push_str(&new_line);
-    rebuilt.push('\n');
-    fs::write(note, rebuilt)?;
-    Ok(true)
-}
-
-fn gather_inbox_notes(notes_root: &Path) -> Result<Vec<PathBuf>> {
-    let mut notes = Vec::new();
-    let inbox = notes_root.join(INBOX_DIR);
-    if !inbox.exists() {
-        return Ok(notes);
-    }
-    for entry in WalkDir::new(&inbox)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-    {
-        if entry
-            .path()
-            .extension()
-            .map(|ext| ext.eq_ignore_ascii_case("md"))
-            .unwrap_or(false)
-        {
-            notes.push(entry.into_path());
-        }
-    }
-    notes.sort();
-    Ok(notes)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn tokens_split() {
-        let result = tokens("Hello, WORLD-42_test!");
-        assert!(result.contains("hello"));
-        assert!(result.contains("world-42_test"));
-    }
-
-    #[test]
-    fn token_counts_tracks_multiples() {
-        let counts = token_counts("Rust rust RUST!!!");
-        assert_eq!(counts.get("rust"), Some(&3));
-    }
-
-    #[test]
-    fn lexical_overlap_zero_when_no_shared_tokens() {
-        let query = tokens("alpha beta");
-        let score = lexical_overlap_score(&query, "gamma delta");
-        assert_eq!(score, 0.0);
+    fn temp_root(label: &str) -> PathBuf {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let unique = COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let root = env::temp_dir().join(format!(
+            "exom-cli-test-{}-{}-{}",
+            label,
+            std::process::id(),
+            unique
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
     }
 
     #[test]
-    fn graph_influence_caps_at_ten() {
-        assert_eq!(graph_influence(5), 0.5);
-        assert_eq!(graph_influence(20), 1.0);
-    }
-
-    #[test]
-    fn semantic_score_considers_weights() {
-        let mut vector = BTreeMap::new();
-        vector.insert("foo".to_string(), 2.5);
-        vector.insert("bar".to_string(), 1.0);
-        let mut query_counts = HashMap::new();
-        query_counts.insert("foo".to_string(), 2);
-        query_counts.insert("baz".to_string(), 1);
-        let score = semantic_score(&query_counts, &vector);
-        assert!((score - 5.0).abs() < f64::EPSILON);
-    }
-
-    #[test]
-    fn parse_typed_relations() {
-        let sample =
-            "REL:CAUSED_BY(API Timeout->Latency Spike)[0.82] and REL:RELATED_TO(Demo->Spec)[1]";
-        let relations = parse_relations(sample);
-        assert_eq!(relations.len(), 2);
-        assert_eq!(relations[0].rel_type, "CAUSED_BY");
-        assert_eq!(relations[0].from, "API Timeout");
-        assert_eq!(relations[0].to, "Latency Spike");
-        assert!((relations[0].confidence - 0.82).abs() < f64::EPSILON);
-        assert_eq!(relations[1].rel_type, "RELATED_TO");
-        assert_eq!(relations[1].confidence, 1.0);
+    fn config_file_supplies_defaults_that_flags_override() {
+        let root = temp_root("config");
+        fs::write(
+            root.join(CONFIG_FILE),
+            "graph = \"vault/.neural/graph.json\"\n\n[recall]\nlexical_weight = 2.5\n",
+        )
+        .unwrap();
+        let nested = root.join("10_Projects/deep");
+        fs::create_dir_all(&nested).unwrap();
+        let config = find_config(&nested).unwrap().unwrap();
+        assert_eq!(config.source, root.join(CONFIG_FILE));
+
+        let parse = |args: &[&str]| {
+            let matches = cli_command(Some(&config))
+                .try_get_matches_from(args)
+                .unwrap();
+            Cli::from_arg_matches(&matches).unwrap()
+        };
+        let Commands::Stats { graph, .. } = parse(&["exom", "stats"]).command else {
+            panic!("expected stats");
+        };
+        assert_eq!(graph, root.join("vault/.neural/graph.json"));
+        let Commands::Stats { graph, .. } = parse(&["exom", "stats", "--graph", "g.json"]).command
+        else {
+            panic!("expected stats");
+        };
+        assert_eq!(graph, PathBuf::from("g.json"));
+        let Commands::Recall(recall) = parse(&["exom", "recall", "--query", "x"]).command else {
+            panic!("expected recall");
+        };
+        assert_eq!(recall.lexical_weight, Some(2.5));
+        assert_eq!(effective_config(Some(&config)).lexical_weight, 2.5);
+        fs::remove_dir_all(&root).unwrap();
     }
 
     #[test]
-    fn compute_decay_score_bounds() {
-        assert_eq!(compute_decay_score(0.0), 0.0);
-        let mid = compute_decay_score(45.0);
-        assert!((mid - 0.5).abs() < 1e-6);
-        assert_eq!(compute_decay_score(200.0), 1.0);
+    fn recall_columns_split_on_commas() {
+        let cli =
+            Cli::try_parse_from(["exom", "recall", "--query", "x", "--columns", "path,score"])
+                .unwrap();
+        let Commands::Recall(recall) = cli.command else {
+            panic!("expected recall");
+        };
+        assert!(recall.columns == vec![RecallColumn::Path, RecallColumn::Score]);
     }
 }